@@ -0,0 +1 @@
+DROP TABLE routine_exercises;
@@ -0,0 +1,6 @@
+CREATE TABLE routine_exercises (
+    routine_id INT NOT NULL REFERENCES routines (id),
+    exercise_id INT NOT NULL REFERENCES exercises (id),
+    position INT NOT NULL,
+    PRIMARY KEY (routine_id, exercise_id)
+);
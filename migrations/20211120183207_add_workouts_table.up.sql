@@ -0,0 +1,6 @@
+CREATE TABLE workouts (
+    id INT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    routine_id INT NOT NULL REFERENCES routines (id),
+    performed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    notes TEXT
+);
@@ -0,0 +1,8 @@
+CREATE TABLE sets (
+    id INT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    workout_id INT NOT NULL REFERENCES workouts (id),
+    exercise_id INT NOT NULL REFERENCES exercises (id),
+    reps INT NOT NULL CHECK (reps >= 1),
+    weight_kg DOUBLE PRECISION CHECK (weight_kg >= 0),
+    position INT NOT NULL
+);
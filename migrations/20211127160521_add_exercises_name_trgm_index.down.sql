@@ -0,0 +1 @@
+DROP INDEX exercises_name_trgm_idx;
@@ -0,0 +1,3 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX exercises_name_trgm_idx ON exercises USING GIN (name gin_trgm_ops);
@@ -0,0 +1,4 @@
+ALTER TABLE routines
+DROP COLUMN user_id;
+
+DROP TABLE users;
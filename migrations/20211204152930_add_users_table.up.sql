@@ -0,0 +1,7 @@
+CREATE TABLE users (
+    id INT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    email TEXT NOT NULL UNIQUE
+);
+
+ALTER TABLE routines
+ADD COLUMN user_id INT REFERENCES users (id);
@@ -0,0 +1,13 @@
+ALTER TABLE workouts
+DROP COLUMN distance_m,
+DROP COLUMN duration_s,
+DROP COLUMN avg_heart_rate;
+
+DELETE FROM sets
+WHERE workout_id IN (SELECT id FROM workouts WHERE routine_id IS NULL);
+
+DELETE FROM workouts
+WHERE routine_id IS NULL;
+
+ALTER TABLE workouts
+ALTER COLUMN routine_id SET NOT NULL;
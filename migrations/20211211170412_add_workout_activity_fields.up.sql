@@ -0,0 +1,7 @@
+ALTER TABLE workouts
+ALTER COLUMN routine_id DROP NOT NULL;
+
+ALTER TABLE workouts
+ADD COLUMN distance_m DOUBLE PRECISION,
+ADD COLUMN duration_s DOUBLE PRECISION,
+ADD COLUMN avg_heart_rate INT;
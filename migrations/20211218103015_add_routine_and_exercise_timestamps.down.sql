@@ -0,0 +1,13 @@
+DROP TRIGGER exercises_set_updated_at ON exercises;
+
+DROP TRIGGER routines_set_updated_at ON routines;
+
+DROP FUNCTION set_updated_at();
+
+ALTER TABLE exercises
+DROP COLUMN created_at,
+DROP COLUMN updated_at;
+
+ALTER TABLE routines
+DROP COLUMN created_at,
+DROP COLUMN updated_at;
@@ -0,0 +1,22 @@
+ALTER TABLE routines
+ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+ALTER TABLE exercises
+ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE FUNCTION set_updated_at() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.updated_at = now();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER routines_set_updated_at
+BEFORE UPDATE ON routines
+FOR EACH ROW EXECUTE FUNCTION set_updated_at();
+
+CREATE TRIGGER exercises_set_updated_at
+BEFORE UPDATE ON exercises
+FOR EACH ROW EXECUTE FUNCTION set_updated_at();
@@ -0,0 +1,2 @@
+ALTER TABLE routines
+ADD COLUMN archived_at TIMESTAMPTZ;
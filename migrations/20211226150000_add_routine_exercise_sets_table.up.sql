@@ -0,0 +1,9 @@
+CREATE TABLE routine_exercise_sets (
+    routine_id INT NOT NULL,
+    exercise_id INT NOT NULL,
+    set_number INT NOT NULL CHECK (set_number >= 1),
+    reps INT NOT NULL CHECK (reps >= 1),
+    weight_kg DOUBLE PRECISION CHECK (weight_kg >= 0),
+    PRIMARY KEY (routine_id, exercise_id, set_number),
+    FOREIGN KEY (routine_id, exercise_id) REFERENCES routine_exercises (routine_id, exercise_id) ON DELETE CASCADE
+);
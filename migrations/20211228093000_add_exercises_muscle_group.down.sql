@@ -0,0 +1,4 @@
+ALTER TABLE exercises
+DROP COLUMN muscle_group;
+
+DROP TYPE muscle_group;
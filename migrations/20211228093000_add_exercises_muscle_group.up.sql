@@ -0,0 +1,13 @@
+CREATE TYPE muscle_group AS ENUM (
+    'chest',
+    'back',
+    'legs',
+    'shoulders',
+    'arms',
+    'core',
+    'full_body',
+    'other'
+);
+
+ALTER TABLE exercises
+ADD COLUMN muscle_group muscle_group NOT NULL DEFAULT 'other';
@@ -0,0 +1,4 @@
+ALTER TABLE exercises
+DROP COLUMN equipment;
+
+DROP TYPE equipment;
@@ -0,0 +1,13 @@
+CREATE TYPE equipment AS ENUM (
+    'barbell',
+    'dumbbell',
+    'machine',
+    'cable',
+    'bodyweight',
+    'kettlebell',
+    'band',
+    'other'
+);
+
+ALTER TABLE exercises
+ADD COLUMN equipment equipment NOT NULL DEFAULT 'other';
@@ -0,0 +1,9 @@
+CREATE TABLE exercise_aliases (
+    exercise_id INT NOT NULL REFERENCES exercises (id) ON DELETE CASCADE,
+    alias TEXT NOT NULL,
+    PRIMARY KEY (exercise_id, alias)
+);
+
+CREATE UNIQUE INDEX exercise_aliases_lower_alias_idx ON exercise_aliases (lower(alias));
+
+CREATE INDEX exercise_aliases_alias_trgm_idx ON exercise_aliases USING GIN (alias gin_trgm_ops);
@@ -0,0 +1 @@
+DROP INDEX sets_exercise_id_workout_id_idx;
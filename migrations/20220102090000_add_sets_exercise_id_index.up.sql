@@ -0,0 +1 @@
+CREATE INDEX sets_exercise_id_workout_id_idx ON sets (exercise_id, workout_id);
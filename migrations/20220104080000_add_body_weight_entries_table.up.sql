@@ -0,0 +1,9 @@
+CREATE TABLE body_weight_entries (
+    id INT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    user_id INT REFERENCES users (id),
+    measured_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    weight_kg DOUBLE PRECISION NOT NULL CHECK (weight_kg BETWEEN 20 AND 500),
+    notes TEXT
+);
+
+CREATE INDEX body_weight_entries_user_id_measured_at_idx ON body_weight_entries (user_id, measured_at);
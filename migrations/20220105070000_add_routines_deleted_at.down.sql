@@ -0,0 +1,2 @@
+ALTER TABLE routines
+DROP COLUMN deleted_at;
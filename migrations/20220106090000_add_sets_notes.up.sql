@@ -0,0 +1,2 @@
+ALTER TABLE sets
+ADD COLUMN notes TEXT;
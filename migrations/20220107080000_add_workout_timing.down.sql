@@ -0,0 +1,7 @@
+ALTER TABLE sets
+DROP COLUMN rest_seconds;
+
+ALTER TABLE workouts
+DROP CONSTRAINT workouts_finished_after_started,
+DROP COLUMN finished_at,
+DROP COLUMN started_at;
@@ -0,0 +1,7 @@
+ALTER TABLE workouts
+ADD COLUMN started_at TIMESTAMPTZ,
+ADD COLUMN finished_at TIMESTAMPTZ,
+ADD CONSTRAINT workouts_finished_after_started CHECK (finished_at >= started_at);
+
+ALTER TABLE sets
+ADD COLUMN rest_seconds INT CHECK (rest_seconds >= 0);
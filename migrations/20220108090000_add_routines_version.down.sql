@@ -0,0 +1,6 @@
+DROP TRIGGER routines_bump_version ON routines;
+
+DROP FUNCTION bump_version();
+
+ALTER TABLE routines
+DROP COLUMN version;
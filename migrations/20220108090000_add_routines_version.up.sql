@@ -0,0 +1,13 @@
+ALTER TABLE routines
+ADD COLUMN version INT NOT NULL DEFAULT 1;
+
+CREATE FUNCTION bump_version() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.version = OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER routines_bump_version
+BEFORE UPDATE ON routines
+FOR EACH ROW EXECUTE FUNCTION bump_version();
@@ -0,0 +1,5 @@
+DROP INDEX users_lower_email_key;
+
+ALTER TABLE users
+DROP COLUMN created_at,
+DROP COLUMN password_hash;
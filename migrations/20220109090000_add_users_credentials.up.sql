@@ -0,0 +1,5 @@
+ALTER TABLE users
+ADD COLUMN password_hash TEXT,
+ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE UNIQUE INDEX users_lower_email_key ON users (lower(email));
@@ -0,0 +1,4 @@
+DROP INDEX workouts_user_id_performed_at_idx;
+
+ALTER TABLE workouts
+DROP COLUMN user_id;
@@ -0,0 +1,11 @@
+ALTER TABLE workouts
+ADD COLUMN user_id INT REFERENCES users (id);
+
+-- Workouts logged against a routine belong to the routine's owner. Anything
+-- left without an owner is legacy data, only visible to anonymous requests.
+UPDATE workouts
+SET user_id = routines.user_id
+FROM routines
+WHERE workouts.routine_id = routines.id;
+
+CREATE INDEX workouts_user_id_performed_at_idx ON workouts (user_id, performed_at);
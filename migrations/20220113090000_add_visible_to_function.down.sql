@@ -0,0 +1 @@
+DROP FUNCTION visible_to(INT, INT);
@@ -0,0 +1,8 @@
+-- Whether a row owned by `owner` is visible to `viewer`, the signed-in user
+-- or NULL for an anonymous request. Rows without an owner are legacy data,
+-- only visible to anonymous requests, and an anonymous request sees nothing
+-- else. Written as an OR rather than IS NOT DISTINCT FROM so that the
+-- inlined function can still use the user_id indexes.
+CREATE FUNCTION visible_to(owner INT, viewer INT) RETURNS BOOLEAN AS $$
+    SELECT owner = viewer OR (owner IS NULL AND viewer IS NULL)
+$$ LANGUAGE SQL IMMUTABLE;
@@ -0,0 +1,18 @@
+DROP INDEX routines_name_without_user_key;
+
+ALTER TABLE routines
+DROP CONSTRAINT routines_user_id_name_key;
+
+-- Names unique per user may be shared between users, which a name unique
+-- across all routines can't allow. Those routines have to be renamed by hand
+-- before this migration can be reverted.
+DO $$
+BEGIN
+    IF EXISTS (SELECT FROM routines GROUP BY name HAVING COUNT(*) > 1) THEN
+        RAISE EXCEPTION 'Routines of different users share a name; rename them before reverting';
+    END IF;
+END
+$$;
+
+ALTER TABLE routines
+ADD CONSTRAINT routines_name_key UNIQUE (name);
@@ -0,0 +1,13 @@
+-- Routine names only need to be unique among a user's own routines. A name
+-- taken across all users told the caller that someone else had a routine by
+-- that name.
+ALTER TABLE routines
+DROP CONSTRAINT routines_name_key;
+
+ALTER TABLE routines
+ADD CONSTRAINT routines_user_id_name_key UNIQUE (user_id, name);
+
+-- `UNIQUE (user_id, name)` treats NULL owners as distinct, so routines
+-- without an owner keep unique names through an index of their own.
+CREATE UNIQUE INDEX routines_name_without_user_key ON routines (name)
+WHERE user_id IS NULL;
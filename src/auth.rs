@@ -0,0 +1,343 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use async_graphql::dataloader::DataLoader;
+use async_graphql::guard::Guard;
+use async_graphql::Context;
+use chrono::{Duration, Utc};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{digest, hmac};
+use serde::{Deserialize, Serialize};
+use sqlx::{Done, Pool, Postgres};
+use tide::{Middleware, Next, Request};
+
+use crate::error::AppError;
+use crate::loaders::UserLoader;
+use crate::models::Role;
+
+/// The user a request was made on behalf of, taken from a verified JWT or
+/// API key.
+#[derive(Clone, Debug)]
+pub struct AuthenticatedUser {
+    pub id: i32,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    alg: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Claims {
+    sub: i32,
+    exp: i64,
+}
+
+/// How long a token issued by `login` or `register` stays valid.
+const TOKEN_LIFETIME_DAYS: i64 = 30;
+
+/// Issues the HS256-signed JWTs `AuthMiddleware` accepts. The server adds
+/// one to the schema when `JWT_SECRET` is set.
+#[derive(Clone)]
+pub(crate) struct TokenIssuer {
+    key: hmac::Key,
+}
+
+impl TokenIssuer {
+    pub(crate) fn new(secret: &str) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()),
+        }
+    }
+
+    pub(crate) fn issue(&self, user_id: i32) -> String {
+        let encode = |json: Vec<u8>| base64::encode_config(json, base64::URL_SAFE_NO_PAD);
+        let header = encode(br#"{"alg":"HS256","typ":"JWT"}"#.to_vec());
+        let claims = encode(
+            serde_json::to_vec(&Claims {
+                sub: user_id,
+                exp: (Utc::now() + Duration::days(TOKEN_LIFETIME_DAYS)).timestamp(),
+            })
+            .expect("claims serialize to JSON"),
+        );
+        let signed = format!("{}.{}", header, claims);
+        let signature = hmac::sign(&self.key, signed.as_bytes());
+
+        format!("{}.{}", signed, encode(signature.as_ref().to_vec()))
+    }
+}
+
+/// Hashes a password with Argon2id and a random salt, in PHC string format.
+/// Hashing is deliberately slow, so it runs on a blocking thread.
+pub(crate) async fn hash_password(password: String) -> Result<String, AppError> {
+    async_std::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+    })
+    .await
+    .map_err(|error| {
+        tracing::error!("password hashing failed: {}", error);
+        AppError::Internal
+    })
+}
+
+/// Checks a password against a hash made by `hash_password`. Malformed
+/// hashes never match.
+pub(crate) async fn verify_password(password: String, hash: String) -> bool {
+    async_std::task::spawn_blocking(move || {
+        PasswordHash::new(&hash)
+            .map(|hash| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    })
+    .await
+}
+
+/// Prefixes every API key, so that a leaked key is recognizable as one.
+const API_KEY_PREFIX: &str = "fit_";
+
+/// How many random bytes an API key is made from.
+const API_KEY_BYTES: usize = 32;
+
+/// How stale an API key's `last_used_at` may get before a request using the
+/// key updates it. Keeps scripts that send many requests from writing to the
+/// row on every one.
+const API_KEY_LAST_USED_RESOLUTION_MINUTES: i64 = 5;
+
+/// Generates a new API key from the system CSPRNG. Returns the key, to be
+/// shown to the user once, and the hash to store in its place.
+pub(crate) fn generate_api_key() -> Result<(String, String), AppError> {
+    let mut bytes = [0u8; API_KEY_BYTES];
+    SystemRandom::new().fill(&mut bytes).map_err(|_| {
+        tracing::error!("failed to generate an API key");
+        AppError::Internal
+    })?;
+    let key = format!(
+        "{}{}",
+        API_KEY_PREFIX,
+        base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+    );
+    let hash = hash_api_key(&key);
+
+    Ok((key, hash))
+}
+
+/// Hashes an API key for storage and lookup. Keys are random rather than
+/// chosen by users, so a fast unsalted hash is enough; unlike a password
+/// hash it can be looked up directly.
+fn hash_api_key(key: &str) -> String {
+    base64::encode_config(
+        digest::digest(&digest::SHA256, key.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    )
+}
+
+/// Returns the owner of an unrevoked API key, or `None` when there is no
+/// such key. Records when the key was used, at most once per
+/// `API_KEY_LAST_USED_RESOLUTION_MINUTES`.
+async fn authenticate_api_key(
+    postgres_pool: &Pool<Postgres>,
+    key: &str,
+) -> Result<Option<AuthenticatedUser>, sqlx::Error> {
+    let api_key = sqlx::query!(
+        "SELECT id, user_id, last_used_at FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
+        hash_api_key(key)
+    )
+    .fetch_optional(postgres_pool)
+    .await?;
+
+    let api_key = match api_key {
+        Some(api_key) => api_key,
+        None => return Ok(None),
+    };
+
+    let stale_before = Utc::now() - Duration::minutes(API_KEY_LAST_USED_RESOLUTION_MINUTES);
+    if api_key
+        .last_used_at
+        .is_none_or(|last_used_at| last_used_at < stale_before)
+    {
+        sqlx::query!(
+            "UPDATE api_keys SET last_used_at = now() WHERE id = $1",
+            api_key.id
+        )
+        .execute(postgres_pool)
+        .await?;
+    }
+
+    Ok(Some(AuthenticatedUser {
+        id: api_key.user_id,
+    }))
+}
+
+/// Why a bearer token was rejected. Stored in place of the
+/// `AuthenticatedUser` so `require_user` can tell the client whether to sign
+/// in again or fix its token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TokenError {
+    /// The token was issued by this server but has expired.
+    Expired,
+    /// The token is malformed or wasn't signed with `JWT_SECRET`, or the API
+    /// key is unknown or revoked.
+    Invalid,
+}
+
+/// Verifies an HS256-signed JWT and returns the user it was issued to.
+/// Expiry is only checked once the signature is known to be good.
+fn decode_token(key: &hmac::Key, token: &str) -> Result<AuthenticatedUser, TokenError> {
+    let claims = verify_token(key, token).ok_or(TokenError::Invalid)?;
+    if claims.exp <= Utc::now().timestamp() {
+        return Err(TokenError::Expired);
+    }
+
+    Ok(AuthenticatedUser { id: claims.sub })
+}
+
+/// The claims of a well-formed token signed with `key`, whether or not it
+/// has expired.
+fn verify_token(key: &hmac::Key, token: &str) -> Option<Claims> {
+    let mut parts = token.split('.');
+    let (header, claims, signature) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(header), Some(claims), Some(signature)) => (header, claims, signature),
+        _ => return None,
+    };
+
+    if parts.next().is_some() {
+        return None;
+    }
+
+    let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD).ok()?;
+    let signed = &token[..header.len() + 1 + claims.len()];
+    hmac::verify(key, signed.as_bytes(), &signature).ok()?;
+
+    let header: Header = decode_part(header)?;
+    if header.alg != "HS256" {
+        return None;
+    }
+
+    decode_part(claims)
+}
+
+fn decode_part<T: serde::de::DeserializeOwned>(part: &str) -> Option<T> {
+    let json = base64::decode_config(part, base64::URL_SAFE_NO_PAD).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// Reads `Authorization: Bearer <jwt>`, or an API key from
+/// `Authorization: ApiKey <key>` or `X-Api-Key`, and stores the
+/// `AuthenticatedUser` in the request extensions when the credential is
+/// valid, or the `TokenError` when it isn't. Revoked API keys are invalid.
+/// Either way the request is passed on, so operations that don't need a
+/// user still work.
+pub struct AuthMiddleware {
+    key: Option<hmac::Key>,
+    postgres_pool: Pool<Postgres>,
+}
+
+impl AuthMiddleware {
+    pub fn new(secret: Option<String>, postgres_pool: Pool<Postgres>) -> Self {
+        Self {
+            key: secret.map(|secret| hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes())),
+            postgres_pool,
+        }
+    }
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for AuthMiddleware {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let authorization = req
+            .header("Authorization")
+            .map(|header| header.as_str().to_owned());
+        let api_key = match (req.header("X-Api-Key"), &authorization) {
+            (Some(header), _) => Some(header.as_str().trim().to_owned()),
+            (None, Some(header)) => header
+                .strip_prefix("ApiKey ")
+                .map(|key| key.trim().to_owned()),
+            (None, None) => None,
+        };
+
+        let user = match (api_key, &self.key, authorization) {
+            (Some(api_key), _, _) => Some(
+                authenticate_api_key(&self.postgres_pool, &api_key)
+                    .await?
+                    .ok_or(TokenError::Invalid),
+            ),
+            (None, Some(key), Some(header)) => Some(
+                header
+                    .strip_prefix("Bearer ")
+                    .ok_or(TokenError::Invalid)
+                    .and_then(|token| decode_token(key, token.trim())),
+            ),
+            _ => None,
+        };
+
+        match user {
+            Some(Ok(user)) => {
+                req.set_ext(user);
+            }
+            Some(Err(error)) => {
+                req.set_ext(error);
+            }
+            None => {}
+        }
+
+        Ok(next.run(req).await)
+    }
+}
+
+/// Returns the authenticated user. Requests without a token get an
+/// `UNAUTHENTICATED` error, and those whose token was rejected get
+/// `TOKEN_EXPIRED` or `INVALID_TOKEN`.
+pub fn require_user<'a>(ctx: &Context<'a>) -> Result<&'a AuthenticatedUser, AppError> {
+    ctx.data_opt::<AuthenticatedUser>()
+        .ok_or_else(|| match ctx.data_opt::<TokenError>() {
+            Some(TokenError::Expired) => AppError::TokenExpired,
+            Some(TokenError::Invalid) => AppError::InvalidToken,
+            None => AppError::Unauthenticated,
+        })
+}
+
+/// Restricts a field to users whose role grants `required`. Requests without
+/// a user fail as `require_user` does, and signed-in users without the role
+/// get `FORBIDDEN`.
+pub(crate) struct RoleGuard {
+    pub(crate) required: Role,
+}
+
+#[async_trait::async_trait]
+impl Guard for RoleGuard {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        let user = require_user(ctx)?;
+        let user = ctx
+            .data_unchecked::<DataLoader<UserLoader>>()
+            .load_one(user.id)
+            .await?;
+
+        match user {
+            Some(user) if user.role.grants(self.required) => Ok(()),
+            _ => Err(AppError::Forbidden.into()),
+        }
+    }
+}
+
+/// Makes the user with `email` an admin, returning false when there is no
+/// such user. The server does this at startup for `ADMIN_EMAIL` so that the
+/// first admin can be created without touching the database.
+pub async fn promote_admin(
+    postgres_pool: &Pool<Postgres>,
+    email: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE users SET role = 'admin' WHERE lower(email) = lower($1)",
+        email.trim()
+    )
+    .execute(postgres_pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
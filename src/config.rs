@@ -0,0 +1,342 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::env;
+use std::fmt;
+use std::num::{NonZeroU32, NonZeroUsize};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::limits::{QueryLimits, DEFAULT_MAX_COMPLEXITY, DEFAULT_MAX_DEPTH};
+
+/// Settings for running the HTTP server, read from the environment.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub database_url: String,
+    pub database_max_connections: u32,
+    pub database_min_connections: u32,
+    /// How long to wait for a pooled connection before giving up.
+    pub database_connect_timeout: Duration,
+    pub database_idle_timeout: Duration,
+    pub jwt_secret: Option<String>,
+    /// The email of a user to make an admin at startup, for bootstrapping the
+    /// first admin.
+    pub admin_email: Option<String>,
+    /// Origins browsers may call the API from. `*` allows any origin.
+    pub allowed_origins: Vec<String>,
+    /// How long browsers may cache the answer to a CORS preflight request.
+    pub cors_max_age: Duration,
+    /// The largest `POST /graphql` body accepted, in bytes.
+    pub graphql_max_body_bytes: usize,
+    /// The largest `POST /import/exercises` or `POST /upload/fit` body
+    /// accepted, in bytes.
+    pub upload_max_body_bytes: usize,
+    /// How long a GraphQL request may run before it is cancelled.
+    pub request_timeout: Duration,
+    /// SQL statements and root resolvers taking at least this long are
+    /// logged at `warn`.
+    pub slow_query_threshold: Duration,
+    /// How long in-flight requests may run after SIGINT or SIGTERM before
+    /// the server exits anyway.
+    pub shutdown_timeout: Duration,
+    /// Apply pending migrations before serving.
+    pub run_migrations: bool,
+    /// Insert a default exercise catalog when there are no exercises.
+    pub seed: bool,
+    pub query_limits: QueryLimits,
+    /// How many automatic persisted queries to remember. Disabled when
+    /// `None`.
+    pub apq_cache_size: Option<NonZeroUsize>,
+    /// Requests each client may make per minute. Unlimited when `None`.
+    pub rate_limit_per_minute: Option<NonZeroU32>,
+    pub app_env: AppEnv,
+    /// Report how many SQL statements each GraphQL operation runs, and warn
+    /// when one runs more than this many. Disabled when `None`; meant for
+    /// development.
+    pub debug_sql_count: Option<NonZeroUsize>,
+    /// Serve the GraphQL Playground at `/`.
+    pub enable_playground: bool,
+    /// Answer `__schema` and `__type` introspection queries.
+    pub enable_introspection: bool,
+}
+
+/// The kind of deployment the server runs in. Production hides the
+/// playground and schema introspection unless they are enabled explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppEnv {
+    Development,
+    Production,
+}
+
+impl FromStr for AppEnv {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "development" | "dev" => Ok(AppEnv::Development),
+            "production" | "prod" => Ok(AppEnv::Production),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for AppEnv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppEnv::Development => write!(f, "development"),
+            AppEnv::Production => write!(f, "production"),
+        }
+    }
+}
+
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 8000;
+const DEFAULT_PG_PORT: u16 = 5432;
+const DEFAULT_DATABASE_MAX_CONNECTIONS: u32 = 10;
+const DEFAULT_DATABASE_MIN_CONNECTIONS: u32 = 0;
+const DEFAULT_DATABASE_CONNECT_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_DATABASE_IDLE_TIMEOUT_SECS: u64 = 600;
+const DEFAULT_CORS_MAX_AGE_SECS: u64 = 600;
+const DEFAULT_GRAPHQL_MAX_BODY_BYTES: usize = 512 * 1024;
+const DEFAULT_UPLOAD_MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_SLOW_QUERY_MS: u64 = 200;
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 15;
+const DEFAULT_APQ_CACHE_SIZE: usize = 1000;
+
+/// The `PG*` variables a database URL is assembled from when
+/// `DATABASE_URL` is not set, which must all be present.
+const REQUIRED_PG_VARS: [&str; 3] = ["PGHOST", "PGUSER", "PGDATABASE"];
+
+/// Characters escaped in the user and password of an assembled database URL.
+const USERINFO: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// The database location could not be determined or a variable could not
+/// be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Neither `DATABASE_URL` nor every variable in `missing` is set.
+    MissingDatabaseUrl {
+        missing: Vec<&'static str>,
+    },
+    Invalid {
+        name: &'static str,
+        value: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MissingDatabaseUrl { missing } => write!(
+                f,
+                "DATABASE_URL must be set, or {} to connect without it (missing {})",
+                REQUIRED_PG_VARS.join(", "),
+                missing.join(", ")
+            ),
+            ConfigError::Invalid { name, value } => {
+                write!(f, "{} has an invalid value: {:?}", name, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads `HOST`, `PORT`, `DATABASE_URL` (or `PGHOST`, `PGPORT`,
+    /// `PGUSER`, `PGPASSWORD` and `PGDATABASE`; see `resolve_database_url`),
+    /// `DATABASE_MAX_CONNECTIONS`, `DATABASE_MIN_CONNECTIONS`,
+    /// `DATABASE_CONNECT_TIMEOUT_SECS`, `DATABASE_IDLE_TIMEOUT_SECS`,
+    /// `JWT_SECRET`, the comma-separated `CORS_ALLOWED_ORIGINS` (or, failing
+    /// that, `ALLOWED_ORIGINS`), `CORS_MAX_AGE_SECS`,
+    /// `GRAPHQL_MAX_BODY_BYTES`, `UPLOAD_MAX_BODY_BYTES`,
+    /// `REQUEST_TIMEOUT_SECS`, `SLOW_QUERY_MS`, `SHUTDOWN_TIMEOUT_SECS`,
+    /// `RUN_MIGRATIONS`, `SEED`, `GRAPHQL_MAX_DEPTH`,
+    /// `GRAPHQL_MAX_COMPLEXITY`, `APQ_CACHE_SIZE`
+    /// (0 to disable), `RATE_LIMIT_PER_MINUTE` (0 or unset for no limit),
+    /// `DEBUG_SQL_COUNT` (0 or unset to disable), `APP_ENV` (`development`
+    /// or `production`), `ENABLE_PLAYGROUND`, `ENABLE_INTROSPECTION` and
+    /// `DISABLE_INTROSPECTION` from the environment.
+    /// `DISABLE_INTROSPECTION=true` turns off both the playground and
+    /// introspection, whatever the other two say.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// Builds a config from `var`, which looks up a variable by name. Unset
+    /// variables fall back to their defaults.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let app_env = parse_var(&var, "APP_ENV", AppEnv::Development)?;
+        let development = app_env == AppEnv::Development;
+        let disable_introspection: bool = parse_var(&var, "DISABLE_INTROSPECTION", false)?;
+
+        Ok(Self {
+            host: var("HOST").unwrap_or_else(|| DEFAULT_HOST.to_owned()),
+            port: parse_var(&var, "PORT", DEFAULT_PORT)?,
+            database_url: resolve_database_url(&var)?,
+            database_max_connections: parse_var(
+                &var,
+                "DATABASE_MAX_CONNECTIONS",
+                DEFAULT_DATABASE_MAX_CONNECTIONS,
+            )?,
+            database_min_connections: parse_var(
+                &var,
+                "DATABASE_MIN_CONNECTIONS",
+                DEFAULT_DATABASE_MIN_CONNECTIONS,
+            )?,
+            database_connect_timeout: Duration::from_secs(parse_var(
+                &var,
+                "DATABASE_CONNECT_TIMEOUT_SECS",
+                DEFAULT_DATABASE_CONNECT_TIMEOUT_SECS,
+            )?),
+            database_idle_timeout: Duration::from_secs(parse_var(
+                &var,
+                "DATABASE_IDLE_TIMEOUT_SECS",
+                DEFAULT_DATABASE_IDLE_TIMEOUT_SECS,
+            )?),
+            jwt_secret: var("JWT_SECRET"),
+            admin_email: var("ADMIN_EMAIL").filter(|email| !email.trim().is_empty()),
+            allowed_origins: parse_allowed_origins(
+                var("CORS_ALLOWED_ORIGINS").or_else(|| var("ALLOWED_ORIGINS")),
+            ),
+            cors_max_age: Duration::from_secs(parse_var(
+                &var,
+                "CORS_MAX_AGE_SECS",
+                DEFAULT_CORS_MAX_AGE_SECS,
+            )?),
+            graphql_max_body_bytes: parse_var(
+                &var,
+                "GRAPHQL_MAX_BODY_BYTES",
+                DEFAULT_GRAPHQL_MAX_BODY_BYTES,
+            )?,
+            upload_max_body_bytes: parse_var(
+                &var,
+                "UPLOAD_MAX_BODY_BYTES",
+                DEFAULT_UPLOAD_MAX_BODY_BYTES,
+            )?,
+            request_timeout: Duration::from_secs(parse_var(
+                &var,
+                "REQUEST_TIMEOUT_SECS",
+                DEFAULT_REQUEST_TIMEOUT_SECS,
+            )?),
+            slow_query_threshold: Duration::from_millis(parse_var(
+                &var,
+                "SLOW_QUERY_MS",
+                DEFAULT_SLOW_QUERY_MS,
+            )?),
+            shutdown_timeout: Duration::from_secs(parse_var(
+                &var,
+                "SHUTDOWN_TIMEOUT_SECS",
+                DEFAULT_SHUTDOWN_TIMEOUT_SECS,
+            )?),
+            run_migrations: parse_var(&var, "RUN_MIGRATIONS", false)?,
+            seed: parse_var(&var, "SEED", false)?,
+            query_limits: QueryLimits {
+                max_depth: parse_limit(&var, "GRAPHQL_MAX_DEPTH", DEFAULT_MAX_DEPTH)?,
+                max_complexity: parse_limit(
+                    &var,
+                    "GRAPHQL_MAX_COMPLEXITY",
+                    DEFAULT_MAX_COMPLEXITY,
+                )?,
+            },
+            apq_cache_size: NonZeroUsize::new(parse_var(
+                &var,
+                "APQ_CACHE_SIZE",
+                DEFAULT_APQ_CACHE_SIZE,
+            )?),
+            rate_limit_per_minute: NonZeroU32::new(parse_var(&var, "RATE_LIMIT_PER_MINUTE", 0)?),
+            debug_sql_count: NonZeroUsize::new(parse_var(&var, "DEBUG_SQL_COUNT", 0)?),
+            app_env,
+            enable_playground: parse_var(&var, "ENABLE_PLAYGROUND", development)?
+                && !disable_introspection,
+            enable_introspection: parse_var(&var, "ENABLE_INTROSPECTION", development)?
+                && !disable_introspection,
+        })
+    }
+
+    /// The `host:port` address the server binds to.
+    pub fn listen_addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// Uses `DATABASE_URL` when it is set. Otherwise assembles a URL from
+/// `PGHOST`, `PGPORT` (default 5432), `PGUSER`, `PGPASSWORD` (optional) and
+/// `PGDATABASE`, as some platforms provide those instead.
+fn resolve_database_url(var: impl Fn(&str) -> Option<String>) -> Result<String, ConfigError> {
+    if let Some(url) = var("DATABASE_URL") {
+        return Ok(url);
+    }
+
+    let missing: Vec<&'static str> = REQUIRED_PG_VARS
+        .iter()
+        .copied()
+        .filter(|name| var(name).is_none())
+        .collect();
+    if !missing.is_empty() {
+        return Err(ConfigError::MissingDatabaseUrl { missing });
+    }
+
+    let port: u16 = parse_var(&var, "PGPORT", DEFAULT_PG_PORT)?;
+    let encoded = |name| utf8_percent_encode(&var(name).unwrap_or_default(), USERINFO).to_string();
+    let credentials = match var("PGPASSWORD") {
+        Some(_) => format!("{}:{}", encoded("PGUSER"), encoded("PGPASSWORD")),
+        None => encoded("PGUSER"),
+    };
+
+    Ok(format!(
+        "postgres://{}@{}:{}/{}",
+        credentials,
+        var("PGHOST").unwrap_or_default(),
+        port,
+        encoded("PGDATABASE")
+    ))
+}
+
+fn parse_var<T: FromStr>(
+    var: impl Fn(&str) -> Option<String>,
+    name: &'static str,
+    default: T,
+) -> Result<T, ConfigError> {
+    match var(name) {
+        Some(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| ConfigError::Invalid { name, value }),
+        None => Ok(default),
+    }
+}
+
+/// Parses a query limit, which must be at least 1: a limit of 0 would
+/// reject every query.
+fn parse_limit(
+    var: impl Fn(&str) -> Option<String>,
+    name: &'static str,
+    default: usize,
+) -> Result<usize, ConfigError> {
+    let default = NonZeroUsize::new(default).expect("default limits are non-zero");
+    Ok(parse_var(var, name, default)?.get())
+}
+
+/// Splits a comma-separated origin list, falling back to `*` when it is
+/// unset or empty.
+fn parse_allowed_origins(origins: Option<String>) -> Vec<String> {
+    let origins: Vec<String> = origins
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(str::to_owned)
+        .collect();
+
+    if origins.is_empty() {
+        vec!["*".to_owned()]
+    } else {
+        origins
+    }
+}
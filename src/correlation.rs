@@ -0,0 +1,76 @@
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextExecute};
+use async_graphql::{Response, ServerError};
+use std::sync::Arc;
+
+use crate::trace::{new_request_id, RequestId};
+
+/// Error codes for failures the client can't act on, which get a
+/// `requestId` so they can be matched with the server logs.
+const CORRELATED_CODES: [&str; 2] = ["INTERNAL", "DATABASE_UNAVAILABLE"];
+
+/// Tags server-side failures in a response with a per-request `requestId`
+/// extension and logs the affected paths once per request. Requests served
+/// over HTTP reuse the id of their request span.
+///
+/// A failed loader batch fails every field that was waiting on it with the
+/// same error; those errors share the request id and are logged as one line.
+pub struct ErrorCorrelation;
+
+impl ExtensionFactory for ErrorCorrelation {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(ErrorCorrelationExtension)
+    }
+}
+
+struct ErrorCorrelationExtension;
+
+fn is_correlated(error: &ServerError) -> bool {
+    let code = error
+        .extensions
+        .as_ref()
+        .and_then(|extensions| serde_json::to_value(extensions).ok())
+        .and_then(|extensions| extensions.get("code").cloned());
+
+    matches!(code, Some(serde_json::Value::String(code)) if CORRELATED_CODES.contains(&code.as_str()))
+}
+
+#[async_trait::async_trait]
+impl Extension for ErrorCorrelationExtension {
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let mut response = next.run(ctx, operation_name).await;
+
+        let mut request_id = None;
+        let mut failures = Vec::new();
+        for error in response
+            .errors
+            .iter_mut()
+            .filter(|error| is_correlated(error))
+        {
+            error.extensions.get_or_insert_with(Default::default).set(
+                "requestId",
+                request_id
+                    .get_or_insert_with(|| {
+                        ctx.data_opt::<RequestId>()
+                            .map_or_else(new_request_id, |id| id.0.clone())
+                    })
+                    .as_str(),
+            );
+            failures.push(format!(
+                "{} ({})",
+                serde_json::to_string(&error.path).unwrap_or_default(),
+                error.message
+            ));
+        }
+
+        if let Some(request_id) = request_id {
+            tracing::warn!("request {} failed at {}", request_id, failures.join(", "));
+        }
+
+        response
+    }
+}
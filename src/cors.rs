@@ -0,0 +1,85 @@
+use std::time::Duration;
+use tide::http::headers::{
+    ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+    ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use tide::http::Method;
+use tide::{Middleware, Next, Request, Response, StatusCode};
+
+const ALLOWED_METHODS: &str = "GET, POST, OPTIONS";
+const ALLOWED_HEADERS: &str = "Authorization, Content-Type, X-Api-Key";
+
+/// Adds CORS headers for browser clients on other origins. `*` in
+/// `allowed_origins` allows any origin.
+///
+/// Preflight requests are answered with `204 No Content` here, without
+/// reaching a route. Requests from origins that aren't allowed are served
+/// without `Access-Control-Allow-Origin`, so the browser withholds the
+/// response from the page.
+pub(crate) struct Cors {
+    allowed_origins: Vec<String>,
+    max_age: Duration,
+}
+
+impl Cors {
+    pub(crate) fn new(allowed_origins: Vec<String>, max_age: Duration) -> Self {
+        Self {
+            allowed_origins,
+            max_age,
+        }
+    }
+
+    fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|allowed| allowed == "*")
+    }
+
+    /// The `Access-Control-Allow-Origin` value for a request from `origin`,
+    /// or `None` when it isn't allowed.
+    fn allow_origin(&self, origin: &str) -> Option<String> {
+        if self.allows_any_origin() {
+            Some("*".to_owned())
+        } else if self.allowed_origins.iter().any(|allowed| allowed == origin) {
+            Some(origin.to_owned())
+        } else {
+            None
+        }
+    }
+
+    /// Adds the allowed origin, and `Vary: Origin` when the response depends
+    /// on it.
+    fn insert_origin_headers(&self, resp: &mut Response, allow_origin: Option<String>) {
+        if let Some(allow_origin) = allow_origin {
+            resp.insert_header(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        }
+        if !self.allows_any_origin() {
+            resp.append_header(VARY, "Origin");
+        }
+    }
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for Cors {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let origin = match req.header(ORIGIN) {
+            Some(origin) => origin.last().as_str().to_owned(),
+            None => return Ok(next.run(req).await),
+        };
+        let allow_origin = self.allow_origin(&origin);
+
+        if req.method() == Method::Options && req.header(ACCESS_CONTROL_REQUEST_METHOD).is_some() {
+            let mut resp = Response::new(StatusCode::NoContent);
+            if allow_origin.is_some() {
+                resp.insert_header(ACCESS_CONTROL_ALLOW_METHODS, ALLOWED_METHODS);
+                resp.insert_header(ACCESS_CONTROL_ALLOW_HEADERS, ALLOWED_HEADERS);
+                resp.insert_header(ACCESS_CONTROL_MAX_AGE, self.max_age.as_secs().to_string());
+            }
+            self.insert_origin_headers(&mut resp, allow_origin);
+            return Ok(resp);
+        }
+
+        let mut resp = next.run(req).await;
+        self.insert_origin_headers(&mut resp, allow_origin);
+
+        Ok(resp)
+    }
+}
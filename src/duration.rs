@@ -0,0 +1,27 @@
+use async_graphql::{InputValueError, InputValueResult, Scalar, ScalarType, Value};
+
+/// A length of time in whole seconds, exposed as the `Duration` scalar.
+/// Negative and fractional values are rejected when parsed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Seconds(pub(crate) i64);
+
+#[Scalar(name = "Duration")]
+impl ScalarType for Seconds {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        let seconds = match &value {
+            Value::Number(number) => number.as_i64(),
+            _ => return Err(InputValueError::expected_type(value)),
+        };
+
+        match seconds {
+            Some(seconds) if seconds >= 0 => Ok(Seconds(seconds)),
+            _ => Err(InputValueError::custom(
+                "a Duration must be a non-negative whole number of seconds",
+            )),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Number(self.0.into())
+    }
+}
@@ -0,0 +1,253 @@
+use async_graphql::{Error, ErrorExtensions};
+use sqlx::postgres::PgDatabaseError;
+
+/// An error returned to GraphQL clients. Each variant carries a stable,
+/// machine-readable `code` in the error extensions. Unexpected database
+/// failures are logged server-side and reported to clients as a generic
+/// internal error.
+#[derive(Debug, Clone)]
+pub enum AppError {
+    NotFound(String),
+    /// The request clashes with existing data. `field` is the path of the
+    /// argument that clashes, such as `inputs.3.name`, when it is not obvious
+    /// from the operation.
+    Conflict {
+        message: String,
+        field: Option<String>,
+    },
+    /// The record is still referenced and can't be deleted. `routine_ids`
+    /// lists the routines that use it.
+    InUse {
+        message: String,
+        routine_ids: Vec<i32>,
+    },
+    /// A conflicting write: the record changed since the client read the
+    /// version it sent.
+    StaleVersion(String),
+    /// Invalid input. `field` is the path of the offending argument, such as
+    /// `name` or `sets.2.reps`, when the error can be attributed to one.
+    Validation {
+        message: String,
+        field: Option<String>,
+    },
+    Unauthenticated,
+    /// The user is signed in but their role doesn't allow the operation.
+    Forbidden,
+    /// The request's token has expired; the client should sign in again.
+    TokenExpired,
+    /// The request's token is malformed or badly signed.
+    InvalidToken,
+    /// A login with an unknown email or the wrong password. The two aren't
+    /// told apart, so the error doesn't reveal who has an account.
+    InvalidCredentials,
+    /// The database could not be reached, so the request may succeed if
+    /// retried.
+    Database,
+    /// The request ran longer than the server allows and was cancelled.
+    Timeout,
+    Internal,
+}
+
+impl AppError {
+    fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::Conflict { .. } => "CONFLICT",
+            AppError::InUse { .. } => "IN_USE",
+            AppError::StaleVersion(_) => "STALE_VERSION",
+            AppError::Validation { .. } => "VALIDATION",
+            AppError::Unauthenticated => "UNAUTHENTICATED",
+            AppError::Forbidden => "FORBIDDEN",
+            AppError::TokenExpired => "TOKEN_EXPIRED",
+            AppError::InvalidToken => "INVALID_TOKEN",
+            AppError::InvalidCredentials => "INVALID_CREDENTIALS",
+            AppError::Database => "DATABASE_UNAVAILABLE",
+            AppError::Timeout => "TIMEOUT",
+            AppError::Internal => "INTERNAL",
+        }
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Conflict { message, .. }
+            | AppError::InUse { message, .. }
+            | AppError::StaleVersion(message)
+            | AppError::Validation { message, .. } => message,
+            AppError::Unauthenticated => "You must be signed in to do that",
+            AppError::Forbidden => "You are not allowed to do that",
+            AppError::TokenExpired => "Your session has expired, please sign in again",
+            AppError::InvalidToken => "Your access token is invalid",
+            AppError::InvalidCredentials => "Incorrect email or password",
+            AppError::Database => "The database is unavailable, please try again",
+            AppError::Timeout => "The request took too long and was cancelled",
+            AppError::Internal => "Internal server error",
+        }
+    }
+}
+
+impl ErrorExtensions for AppError {
+    fn extend(&self) -> Error {
+        Error::new(self.message()).extend_with(|_, e| {
+            e.set("code", self.code());
+            if let AppError::Conflict {
+                field: Some(field), ..
+            }
+            | AppError::Validation {
+                field: Some(field), ..
+            } = self
+            {
+                e.set("field", field.as_str());
+            }
+            if let AppError::InUse { routine_ids, .. } = self {
+                e.set("routineIds", routine_ids.clone());
+            }
+        })
+    }
+}
+
+impl From<AppError> for Error {
+    fn from(error: AppError) -> Self {
+        error.extend()
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        if let sqlx::Error::RowNotFound = error {
+            return AppError::NotFound("Record not found".to_owned());
+        }
+
+        if let sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed = error {
+            tracing::warn!("database unavailable: {}", error);
+            return AppError::Database;
+        }
+
+        match pg_error_code(&error) {
+            Some("23505") => unique_violation(&error),
+            _ => {
+                tracing::error!("database error: {}", error);
+                AppError::Internal
+            }
+        }
+    }
+}
+
+/// The SQLSTATE code of a Postgres error, if `error` is one.
+pub(crate) fn pg_error_code(error: &sqlx::Error) -> Option<&str> {
+    error
+        .as_database_error()
+        .and_then(|db_error| db_error.try_downcast_ref::<PgDatabaseError>())
+        .map(|db_error| db_error.code())
+}
+
+/// Describes a unique constraint violation, naming the routine or exercise
+/// whose name is taken when the constraint is on one of those names. Routine
+/// names are only unique per user, so that conflict is with one of the
+/// caller's own routines.
+fn unique_violation(error: &sqlx::Error) -> AppError {
+    let db_error = error
+        .as_database_error()
+        .and_then(|db_error| db_error.try_downcast_ref::<PgDatabaseError>());
+    let constraint = db_error.and_then(|db_error| db_error.constraint());
+    let kind = match constraint {
+        Some("routines_user_id_name_key") => Some("A routine"),
+        Some("exercises_name_key") => Some("An exercise"),
+        _ => None,
+    };
+    let value = db_error
+        .and_then(|db_error| db_error.detail())
+        .and_then(duplicate_key_detail);
+    // The key of `routines_user_id_name_key` is `(user_id, name)`.
+    let name = match constraint {
+        Some("routines_user_id_name_key") => value
+            .and_then(|value| value.split_once(", "))
+            .map(|(_, name)| name),
+        _ => value,
+    };
+
+    match (kind, name) {
+        (Some(kind), Some(name)) => conflict(format!("{} named {:?} already exists", kind, name)),
+        _ => conflict("A record with the same value already exists"),
+    }
+}
+
+/// The value that violated a unique constraint, if `error` is a unique
+/// violation.
+pub(crate) fn duplicate_key_value(error: &sqlx::Error) -> Option<&str> {
+    error
+        .as_database_error()
+        .and_then(|db_error| db_error.try_downcast_ref::<PgDatabaseError>())
+        .filter(|db_error| db_error.code() == "23505")
+        .and_then(|db_error| db_error.detail())
+        .and_then(duplicate_key_detail)
+}
+
+/// The value in a unique violation's detail, which Postgres formats as
+/// `Key (column)=(value) already exists.`
+fn duplicate_key_detail(detail: &str) -> Option<&str> {
+    let start = detail.find(")=(")? + 3;
+    let end = detail.rfind(") already exists")?;
+
+    detail.get(start..end)
+}
+
+pub(crate) fn exercise_not_found(id: i32) -> AppError {
+    AppError::NotFound(format!("Exercise {} not found", id))
+}
+
+pub(crate) fn routine_not_found(id: i32) -> AppError {
+    AppError::NotFound(format!("Routine {} not found", id))
+}
+
+/// Maps constraint violations on `routine_exercises` to client-facing errors.
+pub(crate) fn routine_exercise_error(
+    error: sqlx::Error,
+    routine_id: i32,
+    exercise_id: i32,
+) -> AppError {
+    let db_error = error
+        .as_database_error()
+        .and_then(|db_error| db_error.try_downcast_ref::<PgDatabaseError>());
+
+    if let Some(db_error) = db_error {
+        match (db_error.code(), db_error.constraint()) {
+            ("23503", Some("routine_exercises_routine_id_fkey")) => {
+                return routine_not_found(routine_id)
+            }
+            ("23503", Some("routine_exercises_exercise_id_fkey")) => {
+                return exercise_not_found(exercise_id)
+            }
+            ("23505", _) => {
+                return conflict(format!(
+                    "Exercise {} is already part of routine {}",
+                    exercise_id, routine_id
+                ))
+            }
+            _ => {}
+        }
+    }
+
+    error.into()
+}
+
+pub(crate) fn conflict(message: impl Into<String>) -> AppError {
+    AppError::Conflict {
+        message: message.into(),
+        field: None,
+    }
+}
+
+pub(crate) fn validation_error(message: impl Into<String>) -> AppError {
+    AppError::Validation {
+        message: message.into(),
+        field: None,
+    }
+}
+
+pub(crate) fn invalid_field(field: impl Into<String>, message: impl Into<String>) -> AppError {
+    AppError::Validation {
+        message: message.into(),
+        field: Some(field.into()),
+    }
+}
@@ -0,0 +1,104 @@
+use async_graphql::futures_util::{StreamExt, TryStreamExt};
+use async_std::channel;
+use async_std::task;
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use std::io;
+use tide::{Body, Request, Response, StatusCode};
+
+use crate::auth::AuthenticatedUser;
+
+/// How many encoded rows may be queued ahead of a slow client.
+const EXPORT_BUFFER: usize = 64;
+
+const EXPORT_HEADERS: [&str; 5] = [
+    "routine_id",
+    "routine_name",
+    "exercise_id",
+    "exercise_name",
+    "position",
+];
+
+#[derive(Serialize)]
+struct ExportRow {
+    routine_id: i32,
+    routine_name: String,
+    exercise_id: Option<i32>,
+    exercise_name: Option<String>,
+    position: Option<i32>,
+}
+
+fn encode(write: impl FnOnce(&mut csv::Writer<Vec<u8>>) -> csv::Result<()>) -> io::Result<Vec<u8>> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    write(&mut writer)?;
+
+    writer
+        .into_inner()
+        .map_err(|error| io::Error::other(error.to_string()))
+}
+
+/// `GET /export/routines.csv`: the requester's routines and their exercises,
+/// one row per routine exercise and one row with empty exercise columns for a
+/// routine without any. Anonymous requests get the routines without an owner.
+///
+/// Rows are streamed to the client as they are read from the database
+/// rather than buffered.
+pub(crate) async fn export_routines_endpoint(
+    req: Request<()>,
+    postgres_pool: Pool<Postgres>,
+) -> tide::Result {
+    let user_id = req.ext::<AuthenticatedUser>().map(|user| user.id);
+    let (sender, receiver) = channel::bounded(EXPORT_BUFFER);
+
+    task::spawn(async move {
+        let headers = encode(|writer| writer.write_record(EXPORT_HEADERS));
+        if sender.send(headers).await.is_err() {
+            return;
+        }
+
+        let mut rows = sqlx::query_as!(
+            ExportRow,
+            r#"
+SELECT
+    routines.id AS routine_id,
+    routines.name AS routine_name,
+    exercises.id AS "exercise_id?",
+    exercises.name AS "exercise_name?",
+    routine_exercises.position AS "position?"
+FROM routines
+LEFT JOIN routine_exercises ON routine_exercises.routine_id = routines.id
+LEFT JOIN exercises ON exercises.id = routine_exercises.exercise_id
+WHERE visible_to(routines.user_id, $1) AND routines.deleted_at IS NULL
+ORDER BY routines.id, routine_exercises.position, exercises.id
+            "#,
+            user_id
+        )
+        .fetch(&postgres_pool);
+
+        while let Some(row) = rows.next().await {
+            let line = match row {
+                Ok(row) => encode(|writer| writer.serialize(row)),
+                Err(error) => {
+                    tracing::error!("routine export failed: {}", error);
+                    Err(io::Error::other("routine export failed"))
+                }
+            };
+
+            let failed = line.is_err();
+            if sender.send(line).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    let mut resp = Response::new(StatusCode::Ok);
+    resp.set_body(Body::from_reader(receiver.into_async_read(), None));
+    resp.set_content_type("text/csv");
+    resp.insert_header(
+        "content-disposition",
+        r#"attachment; filename="routines.csv""#,
+    );
+    Ok(resp)
+}
@@ -0,0 +1,2913 @@
+use async_graphql::connection::{self, Connection, CursorType, Edge, EmptyFields};
+use async_graphql::dataloader::DataLoader;
+use async_graphql::futures_util::{Stream, StreamExt, TryStreamExt};
+use async_graphql::guard::Guard;
+use async_graphql::{
+    Context, Enum, ErrorExtensions, MaybeUndefined, Object, Result, Schema, SchemaBuilder,
+    Subscription,
+};
+use async_std::channel::{self, Receiver, Sender, TrySendError};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{Done, FromRow, Pool, Postgres};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::auth::{
+    generate_api_key, hash_password, require_user, verify_password, AuthenticatedUser, RoleGuard,
+    TokenIssuer,
+};
+use crate::correlation::ErrorCorrelation;
+use crate::duration::Seconds;
+use crate::error::{
+    conflict, duplicate_key_value, exercise_not_found, invalid_field, pg_error_code,
+    routine_exercise_error, routine_not_found, validation_error, AppError,
+};
+use crate::limits::QueryLimits;
+use crate::loaders::{
+    ExerciseAliasesLoader, ExerciseLoader, ExerciseRoutinesLoader, IncludingDeleted, MuscleLoader,
+    RoutineExerciseSetsLoader, RoutineExercisesLoader, RoutineLoader, UserLoader,
+    WorkoutSetsLoader,
+};
+use crate::models::{
+    ApiKey, AuthPayload, BodyWeightEntry, CreateApiKeyPayload, CreateExerciseInput,
+    CreateRoutineInput, Equipment, Exercise, MuscleGroup, PersonalRecord, Role, Routine,
+    RoutineExercise, SearchResult, Set, SetHistoryFields, SetInput, TrainingDay, TrainingStreaks,
+    User, VolumeBucket, VolumeGroupBy, Workout, WorkoutSetInput, MAX_ONE_REP_MAX_REPS,
+};
+use crate::routine_document::RoutineDocument;
+use crate::trace::ResolverTiming;
+use crate::units::WeightUnit;
+
+pub type AppSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+pub(crate) const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// The number of routines `routines` lists when no `limit` is given.
+const DEFAULT_ROUTINES_LIMIT: i32 = 50;
+
+/// The most routines `routines` lists. Larger limits are clamped to it rather
+/// than rejected, so a client asking for "everything" gets a bounded page.
+const MAX_ROUTINES_LIMIT: i32 = 200;
+
+const MAX_ROUTINES_BY_IDS: usize = 200;
+
+pub(crate) const MAX_RECENT_WORKOUTS: i32 = 100;
+
+const MAX_BODY_WEIGHT_ENTRIES_LIMIT: i32 = 500;
+
+/// The body weights `logBodyWeight` accepts, in kilograms.
+const BODY_WEIGHT_RANGE_KG: std::ops::RangeInclusive<f64> = 20.0..=500.0;
+
+const MAX_EXERCISES_BY_IDS: usize = 200;
+
+const MAX_CREATE_ROUTINES: usize = 100;
+
+const MAX_CREATE_EXERCISES: usize = 500;
+
+const DEFAULT_HISTORY_PAGE_SIZE: i32 = 10;
+
+const MAX_HISTORY_PAGE_SIZE: i32 = 100;
+
+const DEFAULT_TIME_ZONE: &str = "UTC";
+
+const DEFAULT_SEARCH_RESULTS: i32 = 20;
+
+const MAX_SEARCH_RESULTS: i32 = 100;
+
+/// The longest date range `volumeStats` covers, about two years.
+const MAX_VOLUME_STATS_DAYS: i64 = 731;
+
+/// The number of items assumed for lists without a page size, such as a
+/// routine's exercises, when estimating query complexity.
+pub(crate) const UNPAGINATED_LIST_COMPLEXITY: usize = 10;
+
+/// The complexity of a list field returning up to `size` items, or
+/// `default` when no size is given.
+pub(crate) fn list_complexity(size: Option<i32>, default: usize, child_complexity: usize) -> usize {
+    1 + size.map_or(default, |size| size.max(0) as usize) * child_complexity
+}
+
+/// How many routines a `limit` argument lists: `DEFAULT_ROUTINES_LIMIT`
+/// without one, and at most `MAX_ROUTINES_LIMIT`.
+pub(crate) fn routines_page_size(limit: Option<i32>) -> i32 {
+    limit
+        .unwrap_or(DEFAULT_ROUTINES_LIMIT)
+        .min(MAX_ROUTINES_LIMIT)
+}
+
+/// An opaque, base64-encoded exercise id used as a Relay cursor.
+pub struct ExerciseCursor(i32);
+
+impl CursorType for ExerciseCursor {
+    type Error = String;
+
+    fn decode_cursor(s: &str) -> Result<Self, Self::Error> {
+        base64::decode(s)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .and_then(|id| id.parse().ok())
+            .map(ExerciseCursor)
+            .ok_or_else(|| format!("Invalid cursor: {}", s))
+    }
+
+    fn encode_cursor(&self) -> String {
+        base64::encode(self.0.to_string())
+    }
+}
+
+/// An opaque, base64-encoded position in `exerciseHistory`: the workout's
+/// `performed_at` in microseconds and the set id, which breaks ties.
+pub struct SetHistoryCursor {
+    performed_at: DateTime<Utc>,
+    set_id: i32,
+}
+
+impl CursorType for SetHistoryCursor {
+    type Error = String;
+
+    fn decode_cursor(s: &str) -> Result<Self, Self::Error> {
+        base64::decode(s)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .and_then(|cursor| {
+                let (micros, set_id) = cursor.split_once(':')?;
+                Some(SetHistoryCursor {
+                    performed_at: Utc
+                        .timestamp_nanos(micros.parse::<i64>().ok()?.checked_mul(1000)?),
+                    set_id: set_id.parse().ok()?,
+                })
+            })
+            .ok_or_else(|| format!("Invalid cursor: {}", s))
+    }
+
+    fn encode_cursor(&self) -> String {
+        base64::encode(format!(
+            "{}:{}",
+            self.performed_at.timestamp_nanos() / 1000,
+            self.set_id
+        ))
+    }
+}
+
+/// Filters shared by every query that pages through exercises. `$1` is the
+/// `nameContains` argument, `$2` is the `search` argument, `$3` is the
+/// `muscleGroup` argument and `$4` is the `equipment` argument.
+const EXERCISE_FILTERS: &str = r#"
+    ($1::TEXT IS NULL OR name ILIKE '%' || $1 || '%')
+    AND ($2::TEXT IS NULL OR name ILIKE '%' || $2 || '%' OR $2 <% name OR EXISTS (
+        SELECT 1 FROM exercise_aliases
+        WHERE exercise_aliases.exercise_id = exercises.id
+            AND (alias ILIKE '%' || $2 || '%' OR $2 <% alias)
+    ))
+    AND ($3::muscle_group IS NULL OR muscle_group = $3)
+    AND ($4::TEXT[] IS NULL OR equipment = ANY($4::TEXT[]::equipment[]))
+"#;
+
+/// The arguments bound to `EXERCISE_FILTERS`.
+struct ExerciseFilters {
+    name_contains: Option<String>,
+    search: Option<String>,
+    muscle_group: Option<MuscleGroup>,
+    /// Postgres labels of the accepted equipment. sqlx can't encode arrays
+    /// of custom enums, so they are bound as text.
+    equipment: Option<Vec<&'static str>>,
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum ExerciseOrderBy {
+    IdAsc,
+    IdDesc,
+    NameAsc,
+    NameDesc,
+}
+
+impl From<ExerciseOrderBy> for ExerciseOrdering {
+    fn from(order_by: ExerciseOrderBy) -> Self {
+        let (key, descending) = match order_by {
+            ExerciseOrderBy::IdAsc => ("id", false),
+            ExerciseOrderBy::IdDesc => ("id", true),
+            ExerciseOrderBy::NameAsc => ("name", false),
+            ExerciseOrderBy::NameDesc => ("name", true),
+        };
+
+        Self { key, descending }
+    }
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum RoutineOrderBy {
+    IdAsc,
+    IdDesc,
+    NameAsc,
+    NameDesc,
+    CreatedAtDesc,
+    UpdatedAtDesc,
+}
+
+impl RoutineOrderBy {
+    fn sql(&self) -> &'static str {
+        match self {
+            RoutineOrderBy::IdAsc => "id ASC",
+            RoutineOrderBy::IdDesc => "id DESC",
+            RoutineOrderBy::NameAsc => "name ASC, id ASC",
+            RoutineOrderBy::NameDesc => "name DESC, id DESC",
+            RoutineOrderBy::CreatedAtDesc => "created_at DESC, id DESC",
+            RoutineOrderBy::UpdatedAtDesc => "updated_at DESC, id DESC",
+        }
+    }
+}
+
+/// A whitelisted sort order for paging through exercises. Rows are ordered by
+/// `(key, id)` so every row has a unique position, and cursors (which only
+/// hold an id) are compared against the key of the row they point at.
+#[derive(Clone, Copy)]
+struct ExerciseOrdering {
+    key: &'static str,
+    descending: bool,
+}
+
+impl ExerciseOrdering {
+    /// Ranks exercises by how closely their name, or closest alias, matches
+    /// the search string.
+    const RELEVANCE: Self = Self {
+        key: "GREATEST(word_similarity($2, name), (SELECT MAX(word_similarity($2, alias)) FROM exercise_aliases WHERE exercise_aliases.exercise_id = exercises.id))",
+        descending: true,
+    };
+
+    /// Operators selecting rows that sort after and before a cursor.
+    fn operators(&self) -> (&'static str, &'static str) {
+        if self.descending {
+            ("<", ">")
+        } else {
+            (">", "<")
+        }
+    }
+
+    /// Selects a page of exercises. `$5` and `$6` are the `after` and
+    /// `before` cursors and `$7` is the row limit. Backward pages are
+    /// returned in reverse order.
+    fn page_query(&self, backward: bool) -> String {
+        let (after, before) = self.operators();
+        let direction = if self.descending != backward {
+            "DESC"
+        } else {
+            "ASC"
+        };
+
+        format!(
+            r#"
+SELECT id, name, main_muscle_worked_id, muscle_group, equipment, created_at, updated_at
+FROM exercises
+WHERE {filters}
+    AND ($5::INT IS NULL OR ({key}, id) {after} (SELECT {key}, id FROM exercises WHERE id = $5))
+    AND ($6::INT IS NULL OR ({key}, id) {before} (SELECT {key}, id FROM exercises WHERE id = $6))
+ORDER BY {key} {direction}, id {direction}
+LIMIT $7
+            "#,
+            filters = EXERCISE_FILTERS,
+            key = self.key,
+            after = after,
+            before = before,
+            direction = direction
+        )
+    }
+
+    async fn exists_before(
+        &self,
+        pool: &Pool<Postgres>,
+        filters: &ExerciseFilters,
+        cursor: i32,
+    ) -> Result<bool, AppError> {
+        let (_, before) = self.operators();
+        self.exists(pool, filters, cursor, before).await
+    }
+
+    async fn exists_after(
+        &self,
+        pool: &Pool<Postgres>,
+        filters: &ExerciseFilters,
+        cursor: i32,
+    ) -> Result<bool, AppError> {
+        let (after, _) = self.operators();
+        self.exists(pool, filters, cursor, after).await
+    }
+
+    /// Whether any matching exercise is the cursor row or sorts on the side
+    /// of it selected by `operator`.
+    async fn exists(
+        &self,
+        pool: &Pool<Postgres>,
+        filters: &ExerciseFilters,
+        cursor: i32,
+        operator: &str,
+    ) -> Result<bool, AppError> {
+        let query = format!(
+            r#"
+SELECT EXISTS (
+    SELECT 1 FROM exercises
+    WHERE {filters}
+        AND ({key}, id) {operator}= (SELECT {key}, id FROM exercises WHERE id = $5)
+)
+            "#,
+            filters = EXERCISE_FILTERS,
+            key = self.key,
+            operator = operator
+        );
+        let (exists,): (bool,) = sqlx::query_as(&query)
+            .bind(&filters.name_contains)
+            .bind(&filters.search)
+            .bind(filters.muscle_group)
+            .bind(&filters.equipment)
+            .bind(cursor)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(exists)
+    }
+}
+
+/// Decodes the rows of a list query, leaving out rows that fail to decode
+/// so one bad row doesn't hide the rest. Each row left out is logged and
+/// reported as an `INTERNAL` error on the field. Any other error fails the
+/// whole list.
+///
+/// Rows are decoded here rather than by `query_as`, whose stream ends at the
+/// first row it can't decode.
+async fn collect_rows<T: for<'r> FromRow<'r, PgRow>>(
+    ctx: &Context<'_>,
+    mut rows: impl Stream<Item = Result<PgRow, sqlx::Error>> + Unpin,
+) -> Result<Vec<T>, AppError> {
+    let mut collected = Vec::new();
+
+    while let Some(row) = rows.next().await {
+        match T::from_row(&row?) {
+            Ok(row) => collected.push(row),
+            Err(error @ (sqlx::Error::ColumnDecode { .. } | sqlx::Error::Decode(_))) => {
+                tracing::error!("skipping a row that failed to decode: {}", error);
+                ctx.add_error(
+                    ctx.set_error_path(AppError::Internal.extend().into_server_error(ctx.item.pos)),
+                );
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    Ok(collected)
+}
+
+/// The routines `routines` and `User.routines` list: those visible to
+/// `user_id`, which is `None` for an anonymous viewer.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn list_routines(
+    ctx: &Context<'_>,
+    user_id: Option<i32>,
+    name_contains: Option<String>,
+    order_by: RoutineOrderBy,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    include_archived: bool,
+    include_deleted: bool,
+) -> Result<Vec<Routine>, AppError> {
+    let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+    let name_contains = name_contains.filter(|name| !name.is_empty());
+
+    if matches!(limit, Some(limit) if limit < 0) {
+        return Err(validation_error("limit must not be negative"));
+    }
+
+    if matches!(offset, Some(offset) if offset < 0) {
+        return Err(validation_error("offset must not be negative"));
+    }
+
+    let query = format!(
+        r#"
+SELECT id, name, user_id, created_at, updated_at, archived_at, deleted_at, version
+FROM routines
+WHERE ($1::TEXT IS NULL OR name ILIKE '%' || $1 || '%')
+    AND visible_to(user_id, $4)
+    AND ($5 OR archived_at IS NULL)
+    AND ($6 OR deleted_at IS NULL)
+ORDER BY {}
+LIMIT $2
+OFFSET $3
+        "#,
+        order_by.sql()
+    );
+    let routines = collect_rows(
+        ctx,
+        sqlx::query(&query)
+            .bind(name_contains)
+            .bind(routines_page_size(limit) as i64)
+            .bind(offset.unwrap_or(0) as i64)
+            .bind(user_id)
+            .bind(include_archived)
+            .bind(include_deleted)
+            .fetch(pool),
+    )
+    .await?;
+
+    Ok(routines)
+}
+
+/// Workouts newest first, as `workouts` and `User.recentWorkouts` list them:
+/// those visible to `user_id`, and at most `limit` of them.
+pub(crate) async fn list_workouts(
+    ctx: &Context<'_>,
+    user_id: Option<i32>,
+    routine_id: Option<i32>,
+    limit: Option<i32>,
+) -> Result<Vec<Workout>, AppError> {
+    let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+    collect_rows(
+        ctx,
+        sqlx::query(
+            r#"
+SELECT id, routine_id, performed_at, notes, distance_m, duration_s, avg_heart_rate, started_at, finished_at
+FROM workouts
+WHERE ($1::INT IS NULL OR routine_id = $1) AND visible_to(user_id, $2)
+ORDER BY performed_at DESC, id DESC
+LIMIT $3
+            "#,
+        )
+        .bind(routine_id)
+        .bind(user_id)
+        .bind(limit.map(i64::from))
+        .fetch(pool),
+    )
+    .await
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// The user the request is authenticated as, or null for an anonymous
+    /// request.
+    async fn viewer(&self, ctx: &Context<'_>) -> Result<Option<User>, AppError> {
+        let user = match ctx.data_opt::<AuthenticatedUser>() {
+            Some(user) => {
+                ctx.data_unchecked::<DataLoader<UserLoader>>()
+                    .load_one(user.id)
+                    .await?
+            }
+            None => None,
+        };
+
+        Ok(user)
+    }
+
+    #[graphql(deprecation = "Use `viewer`.")]
+    async fn me(&self, ctx: &Context<'_>) -> Result<Option<User>, AppError> {
+        self.viewer(ctx).await
+    }
+
+    /// Every account, oldest first. Only admins may list them.
+    #[graphql(
+        guard(RoleGuard(required = "Role::Admin")),
+        complexity = "list_complexity(None, UNPAGINATED_LIST_COMPLEXITY, child_complexity)"
+    )]
+    async fn users(&self, ctx: &Context<'_>) -> Result<Vec<User>, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        let users = sqlx::query_as!(
+            User,
+            r#"SELECT id, email, created_at, role AS "role: Role" FROM users ORDER BY id"#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(users)
+    }
+
+    async fn exercise(&self, ctx: &Context<'_>, id: i32) -> Result<Option<Exercise>, AppError> {
+        let exercise = ctx
+            .data_unchecked::<DataLoader<ExerciseLoader>>()
+            .load_one(id)
+            .await?;
+
+        Ok(exercise)
+    }
+
+    /// Fetches exercises by id in the order given, leaving out ids that don't
+    /// exist.
+    async fn exercises_by_ids(
+        &self,
+        ctx: &Context<'_>,
+        ids: Vec<i32>,
+    ) -> Result<Vec<Exercise>, AppError> {
+        if ids.len() > MAX_EXERCISES_BY_IDS {
+            return Err(invalid_field(
+                "ids",
+                format!(
+                    "ids must not contain more than {} ids",
+                    MAX_EXERCISES_BY_IDS
+                ),
+            ));
+        }
+
+        let exercises = ctx
+            .data_unchecked::<DataLoader<ExerciseLoader>>()
+            .load_many(ids.iter().copied())
+            .await?;
+
+        Ok(ids
+            .iter()
+            .filter_map(|id| exercises.get(id).cloned())
+            .collect())
+    }
+
+    /// Pages through exercises. When `search` is given and `orderBy` is not,
+    /// matches are ranked by trigram word similarity to the search string,
+    /// best match first. `equipment` matches exercises using any of the
+    /// listed equipment; an empty list doesn't filter.
+    ///
+    /// Complexity functions only apply to list fields, so the page size of
+    /// this connection is costed by `QueryLimits` instead.
+    #[allow(clippy::too_many_arguments)]
+    async fn exercises(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        before: Option<String>,
+        first: Option<i32>,
+        last: Option<i32>,
+        name_contains: Option<String>,
+        search: Option<String>,
+        muscle_group: Option<MuscleGroup>,
+        equipment: Option<Vec<Equipment>>,
+        order_by: Option<ExerciseOrderBy>,
+    ) -> Result<Connection<ExerciseCursor, Exercise>> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let filters = ExerciseFilters {
+            name_contains: name_contains.filter(|name| !name.is_empty()),
+            search: search
+                .map(|search| search.trim().to_owned())
+                .filter(|search| !search.is_empty()),
+            muscle_group,
+            equipment: equipment
+                .filter(|equipment| !equipment.is_empty())
+                .map(|equipment| equipment.iter().map(Equipment::as_str).collect()),
+        };
+        let ordering = match (order_by, &filters.search) {
+            (Some(order_by), _) => order_by.into(),
+            (None, Some(_)) => ExerciseOrdering::RELEVANCE,
+            (None, None) => ExerciseOrderBy::IdAsc.into(),
+        };
+
+        connection::query(
+            after,
+            before,
+            first,
+            last,
+            |after: Option<ExerciseCursor>, before: Option<ExerciseCursor>, first, last| async move {
+                let after = after.map(|cursor| cursor.0);
+                let before = before.map(|cursor| cursor.0);
+                let backward = last.is_some();
+                let limit = last.or(first).unwrap_or(DEFAULT_PAGE_SIZE);
+
+                let mut exercises: Vec<Exercise> = collect_rows(
+                    ctx,
+                    sqlx::query(&ordering.page_query(backward))
+                        .bind(&filters.name_contains)
+                        .bind(&filters.search)
+                        .bind(filters.muscle_group)
+                        .bind(&filters.equipment)
+                        .bind(after)
+                        .bind(before)
+                        .bind(limit as i64 + 1)
+                        .fetch(pool),
+                )
+                .await?;
+
+                let has_more = exercises.len() > limit;
+                exercises.truncate(limit);
+                if backward {
+                    exercises.reverse();
+                }
+
+                let has_previous_page = match (backward, after) {
+                    (true, _) => has_more,
+                    (false, Some(after)) => {
+                        ordering.exists_before(pool, &filters, after).await?
+                    }
+                    (false, None) => false,
+                };
+                let has_next_page = match (backward, before) {
+                    (false, _) => has_more,
+                    (true, Some(before)) => {
+                        ordering.exists_after(pool, &filters, before).await?
+                    }
+                    (true, None) => false,
+                };
+
+                let mut connection = Connection::new(has_previous_page, has_next_page);
+                connection.append(
+                    exercises
+                        .into_iter()
+                        .map(|exercise| Edge::new(ExerciseCursor(exercise.id), exercise)),
+                );
+
+                Ok(connection)
+            },
+        )
+        .await
+    }
+
+    /// Looks up a routine by id. Deleted routines are only returned when
+    /// `includeDeleted` is true, and someone else's routine is null just like
+    /// a missing one.
+    async fn routine(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        #[graphql(default)] include_deleted: bool,
+    ) -> Result<Option<Routine>, AppError> {
+        let loader = ctx.data_unchecked::<DataLoader<RoutineLoader>>();
+        let routine = if include_deleted {
+            loader.load_one(IncludingDeleted(id)).await?
+        } else {
+            loader.load_one(id).await?
+        };
+
+        Ok(routine.filter(|routine| visible_to_viewer(ctx, routine)))
+    }
+
+    /// A JSON document of a routine with its exercises and prescribed sets,
+    /// which `importRoutine` can recreate on this or another server. Null when
+    /// there is no such routine, or it is deleted or someone else's.
+    async fn routine_export(&self, ctx: &Context<'_>, id: i32) -> Result<Option<String>, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        let document = RoutineDocument::load(pool, id, viewer_id(ctx)).await?;
+
+        document
+            .map(|document| {
+                serde_json::to_string(&document).map_err(|error| {
+                    tracing::error!("encoding routine {} failed: {}", id, error);
+                    AppError::Internal
+                })
+            })
+            .transpose()
+    }
+
+    /// The routines with the given ids, in the order the ids were given.
+    /// Ids that don't match a routine, or match a deleted one or someone
+    /// else's, are omitted, and a repeated id returns its routine once per
+    /// occurrence.
+    #[graphql(complexity = "1 + ids.len() * child_complexity")]
+    async fn routines_by_ids(
+        &self,
+        ctx: &Context<'_>,
+        ids: Vec<i32>,
+    ) -> Result<Vec<Routine>, AppError> {
+        if ids.len() > MAX_ROUTINES_BY_IDS {
+            return Err(invalid_field(
+                "ids",
+                format!("ids must not contain more than {} ids", MAX_ROUTINES_BY_IDS),
+            ));
+        }
+
+        let routines = ctx
+            .data_unchecked::<DataLoader<RoutineLoader>>()
+            .load_many(ids.iter().copied())
+            .await?;
+
+        Ok(ids
+            .iter()
+            .filter_map(|id| routines.get(id).cloned())
+            .filter(|routine| visible_to_viewer(ctx, routine))
+            .collect())
+    }
+
+    /// Lists routines. Archived and deleted routines are left out unless
+    /// `includeArchived` and `includeDeleted` are true respectively. `limit`
+    /// defaults to 50 and is clamped to 200; a negative `limit` or `offset` is
+    /// a validation error.
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "list_complexity(Some(routines_page_size(limit)), 0, child_complexity)")]
+    async fn routines(
+        &self,
+        ctx: &Context<'_>,
+        name_contains: Option<String>,
+        #[graphql(default_with = "RoutineOrderBy::IdAsc")] order_by: RoutineOrderBy,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        #[graphql(default)] include_archived: bool,
+        #[graphql(default)] include_deleted: bool,
+    ) -> Result<Vec<Routine>, AppError> {
+        list_routines(
+            ctx,
+            viewer_id(ctx),
+            name_contains,
+            order_by,
+            limit,
+            offset,
+            include_archived,
+            include_deleted,
+        )
+        .await
+    }
+
+    /// How many routines `routines` would list without a limit: the viewer's,
+    /// leaving out archived and deleted routines unless `includeArchived` and
+    /// `includeDeleted` are true.
+    async fn routine_count(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] include_archived: bool,
+        #[graphql(default)] include_deleted: bool,
+    ) -> Result<i64, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        let count = sqlx::query!(
+            r#"
+SELECT COUNT(*) AS "count!"
+FROM routines
+WHERE visible_to(user_id, $1)
+    AND ($2 OR archived_at IS NULL)
+    AND ($3 OR deleted_at IS NULL)
+            "#,
+            viewer_id(ctx),
+            include_archived,
+            include_deleted
+        )
+        .fetch_one(pool)
+        .await?
+        .count;
+
+        Ok(count)
+    }
+
+    /// Exercises and routines whose names contain `query`, ignoring case.
+    /// Exact matches come first, then names starting with `query`, then the
+    /// rest, each alphabetically. Only the viewer's routines are searched,
+    /// and archived and deleted routines never match. This returns only the
+    /// first `first` results and is not paginated: a search box narrows its
+    /// `query` rather than asking for a next page.
+    #[graphql(complexity = "list_complexity(Some(first), 0, child_complexity)")]
+    async fn search(
+        &self,
+        ctx: &Context<'_>,
+        query: String,
+        #[graphql(default_with = "DEFAULT_SEARCH_RESULTS")] first: i32,
+    ) -> Result<Vec<SearchResult>, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let query = query.trim();
+
+        if query.is_empty() {
+            return Err(invalid_field("query", "query must not be blank"));
+        }
+
+        if !(1..=MAX_SEARCH_RESULTS).contains(&first) {
+            return Err(invalid_field(
+                "first",
+                format!("first must be between 1 and {}", MAX_SEARCH_RESULTS),
+            ));
+        }
+
+        let hits = sqlx::query!(
+            r#"
+SELECT kind AS "kind!", id AS "id!"
+FROM (
+    SELECT 'exercise' AS kind, id, name FROM exercises
+    UNION ALL
+    SELECT 'routine', id, name FROM routines
+    WHERE visible_to(user_id, $2) AND archived_at IS NULL AND deleted_at IS NULL
+) AS candidates
+WHERE strpos(lower(name), lower($1)) > 0
+ORDER BY
+    CASE
+        WHEN lower(name) = lower($1) THEN 0
+        WHEN strpos(lower(name), lower($1)) = 1 THEN 1
+        ELSE 2
+    END,
+    lower(name),
+    kind,
+    id
+LIMIT $3
+            "#,
+            query,
+            viewer_id(ctx),
+            first as i64
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let ids_of = |kind: &str| -> Vec<i32> {
+            hits.iter()
+                .filter(|hit| hit.kind == kind)
+                .map(|hit| hit.id)
+                .collect()
+        };
+        let exercises = ctx
+            .data_unchecked::<DataLoader<ExerciseLoader>>()
+            .load_many(ids_of("exercise"))
+            .await?;
+        let routines = ctx
+            .data_unchecked::<DataLoader<RoutineLoader>>()
+            .load_many(ids_of("routine"))
+            .await?;
+
+        Ok(hits
+            .iter()
+            .filter_map(|hit| match hit.kind.as_str() {
+                "exercise" => exercises.get(&hit.id).cloned().map(SearchResult::Exercise),
+                _ => routines.get(&hit.id).cloned().map(SearchResult::Routine),
+            })
+            .collect())
+    }
+
+    async fn exercise_count(&self, ctx: &Context<'_>) -> Result<i64, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        let count = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM exercises"#)
+            .fetch_one(pool)
+            .await?
+            .count;
+
+        Ok(count)
+    }
+
+    #[graphql(complexity = "list_complexity(None, UNPAGINATED_LIST_COMPLEXITY, child_complexity)")]
+    async fn workouts(
+        &self,
+        ctx: &Context<'_>,
+        routine_id: Option<i32>,
+    ) -> Result<Vec<Workout>, AppError> {
+        list_workouts(ctx, viewer_id(ctx), routine_id, None).await
+    }
+
+    /// The heaviest logged set for each exercise, or just for `exerciseId`
+    /// when given. When the record weight was lifted in more than one
+    /// workout, the most recent one counts. Exercises without a weighted set
+    /// are left out.
+    async fn personal_records(
+        &self,
+        ctx: &Context<'_>,
+        exercise_id: Option<i32>,
+    ) -> Result<Vec<PersonalRecord>, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        let records = sqlx::query!(
+            r#"
+SELECT DISTINCT ON (sets.exercise_id)
+    sets.exercise_id,
+    sets.weight_kg AS "weight_kg!",
+    sets.reps,
+    workouts.id,
+    workouts.routine_id AS "routine_id?",
+    workouts.performed_at,
+    workouts.notes,
+    workouts.distance_m,
+    workouts.duration_s,
+    workouts.avg_heart_rate,
+    workouts.started_at,
+    workouts.finished_at
+FROM sets
+JOIN workouts ON workouts.id = sets.workout_id
+WHERE sets.weight_kg IS NOT NULL
+    AND ($1::INT IS NULL OR sets.exercise_id = $1)
+    AND visible_to(workouts.user_id, $2)
+ORDER BY sets.exercise_id, sets.weight_kg DESC, workouts.performed_at DESC, sets.reps DESC
+            "#,
+            exercise_id,
+            viewer_id(ctx)
+        )
+        .fetch(pool)
+        .map_ok(|row| PersonalRecord {
+            exercise_id: row.exercise_id,
+            max_weight_kg: row.weight_kg,
+            reps_at_max_weight: row.reps,
+            workout: Workout {
+                id: row.id,
+                routine_id: row.routine_id,
+                performed_at: row.performed_at,
+                notes: row.notes,
+                distance_m: row.distance_m,
+                duration_s: row.duration_s,
+                avg_heart_rate: row.avg_heart_rate,
+                started_at: row.started_at,
+                finished_at: row.finished_at,
+            },
+        })
+        .try_collect()
+        .await?;
+
+        Ok(records)
+    }
+
+    /// The sets logged for an exercise, newest workout first. Each edge also
+    /// has the workout the set was performed in and when.
+    async fn exercise_history(
+        &self,
+        ctx: &Context<'_>,
+        exercise_id: i32,
+        #[graphql(default_with = "DEFAULT_HISTORY_PAGE_SIZE")] first: i32,
+        after: Option<String>,
+    ) -> Result<Connection<SetHistoryCursor, Set, EmptyFields, SetHistoryFields>> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let user_id = viewer_id(ctx);
+
+        if !(1..=MAX_HISTORY_PAGE_SIZE).contains(&first) {
+            return Err(invalid_field(
+                "first",
+                format!("first must be between 1 and {}", MAX_HISTORY_PAGE_SIZE),
+            )
+            .into());
+        }
+
+        connection::query(
+            after,
+            None,
+            Some(first),
+            None,
+            |after: Option<SetHistoryCursor>, _: Option<SetHistoryCursor>, first, _| async move {
+                let limit = first.unwrap_or(DEFAULT_HISTORY_PAGE_SIZE as usize);
+
+                let mut rows = sqlx::query!(
+                    r#"
+SELECT sets.id, sets.workout_id, sets.exercise_id, sets.reps, sets.weight_kg, sets.position, sets.notes, sets.rest_seconds, workouts.performed_at
+FROM sets
+JOIN workouts ON workouts.id = sets.workout_id
+WHERE sets.exercise_id = $1
+    AND ($2::TIMESTAMPTZ IS NULL OR (workouts.performed_at, sets.id) < ($2, $3))
+    AND visible_to(workouts.user_id, $5)
+ORDER BY workouts.performed_at DESC, sets.id DESC
+LIMIT $4
+                    "#,
+                    exercise_id,
+                    after.as_ref().map(|cursor| cursor.performed_at),
+                    after.as_ref().map(|cursor| cursor.set_id),
+                    limit as i64 + 1,
+                    user_id
+                )
+                .fetch_all(pool)
+                .await
+                .map_err(AppError::from)?;
+
+                let has_next_page = rows.len() > limit;
+                rows.truncate(limit);
+
+                let mut connection = Connection::new(after.is_some(), has_next_page);
+                connection.append(rows.into_iter().map(|row| {
+                    Edge::with_additional_fields(
+                        SetHistoryCursor {
+                            performed_at: row.performed_at,
+                            set_id: row.id,
+                        },
+                        Set {
+                            id: row.id,
+                            workout_id: row.workout_id,
+                            exercise_id: row.exercise_id,
+                            reps: row.reps,
+                            weight_kg: row.weight_kg,
+                            position: row.position,
+                            notes: row.notes,
+                            rest_seconds: row.rest_seconds,
+                        },
+                        SetHistoryFields {
+                            workout_id: row.workout_id,
+                            performed_at: row.performed_at,
+                        },
+                    )
+                }));
+
+                Ok(connection)
+            },
+        )
+        .await
+    }
+
+    /// Body weight entries measured between `fromDate` and `toDate`, newest
+    /// first: the viewer's.
+    #[graphql(
+        complexity = "list_complexity(limit, MAX_BODY_WEIGHT_ENTRIES_LIMIT as usize, child_complexity)"
+    )]
+    async fn body_weight_entries(
+        &self,
+        ctx: &Context<'_>,
+        from_date: Option<DateTime<Utc>>,
+        to_date: Option<DateTime<Utc>>,
+        limit: Option<i32>,
+    ) -> Result<Vec<BodyWeightEntry>, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        match limit {
+            Some(limit) if limit < 0 => {
+                return Err(validation_error("limit must not be negative"));
+            }
+            Some(limit) if limit > MAX_BODY_WEIGHT_ENTRIES_LIMIT => {
+                return Err(validation_error(format!(
+                    "limit must not be greater than {}",
+                    MAX_BODY_WEIGHT_ENTRIES_LIMIT
+                )));
+            }
+            _ => {}
+        }
+
+        let entries = sqlx::query_as!(
+            BodyWeightEntry,
+            r#"
+SELECT id, measured_at, weight_kg, notes
+FROM body_weight_entries
+WHERE visible_to(user_id, $1)
+    AND ($2::TIMESTAMPTZ IS NULL OR measured_at >= $2)
+    AND ($3::TIMESTAMPTZ IS NULL OR measured_at <= $3)
+ORDER BY measured_at DESC, id DESC
+LIMIT $4
+            "#,
+            viewer_id(ctx),
+            from_date,
+            to_date,
+            limit.unwrap_or(MAX_BODY_WEIGHT_ENTRIES_LIMIT) as i64
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// The viewer's most recent body weight entry.
+    async fn latest_body_weight(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Option<BodyWeightEntry>, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        let entry = sqlx::query_as!(
+            BodyWeightEntry,
+            r#"
+SELECT id, measured_at, weight_kg, notes
+FROM body_weight_entries
+WHERE visible_to(user_id, $1)
+ORDER BY measured_at DESC, id DESC
+LIMIT 1
+            "#,
+            viewer_id(ctx)
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(entry)
+    }
+
+    /// The highest estimated one-rep max over every set logged for an
+    /// exercise, computed as `Set.estimatedOneRepMaxKg` is. Null when no set
+    /// has an estimate.
+    async fn best_estimated_one_rep_max(
+        &self,
+        ctx: &Context<'_>,
+        exercise_id: i32,
+    ) -> Result<Option<f64>, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        let best = sqlx::query!(
+            r#"
+SELECT MAX(CASE WHEN sets.reps = 1 THEN sets.weight_kg ELSE sets.weight_kg * (1 + sets.reps / 30.0) END) AS best
+FROM sets
+JOIN workouts ON workouts.id = sets.workout_id
+WHERE sets.exercise_id = $1
+    AND sets.weight_kg IS NOT NULL
+    AND sets.reps BETWEEN 1 AND $2
+    AND visible_to(workouts.user_id, $3)
+            "#,
+            exercise_id,
+            MAX_ONE_REP_MAX_REPS,
+            viewer_id(ctx)
+        )
+        .fetch_one(pool)
+        .await?
+        .best;
+
+        Ok(best)
+    }
+
+    /// The days between `fromDate` and `toDate`, inclusive, with at least
+    /// one workout, oldest first. Days are calendar days in `timeZone`, an
+    /// IANA time zone name such as `Europe/Berlin`.
+    async fn training_calendar(
+        &self,
+        ctx: &Context<'_>,
+        from_date: NaiveDate,
+        to_date: NaiveDate,
+        #[graphql(default_with = "DEFAULT_TIME_ZONE.to_owned()")] time_zone: String,
+    ) -> Result<Vec<TrainingDay>, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        if from_date > to_date {
+            return Err(invalid_field(
+                "toDate",
+                "toDate must not be before fromDate",
+            ));
+        }
+        validate_time_zone(pool, &time_zone).await?;
+
+        let days = sqlx::query_as!(
+            TrainingDay,
+            r#"
+SELECT
+    (workouts.performed_at AT TIME ZONE $3)::DATE AS "date!",
+    COUNT(DISTINCT workouts.id) AS "workout_count!",
+    COUNT(sets.id) AS "set_count!"
+FROM workouts
+LEFT JOIN sets ON sets.workout_id = workouts.id
+WHERE (workouts.performed_at AT TIME ZONE $3)::DATE BETWEEN $1 AND $2
+    AND visible_to(workouts.user_id, $4)
+GROUP BY 1
+ORDER BY 1
+            "#,
+            from_date,
+            to_date,
+            time_zone,
+            viewer_id(ctx)
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(days)
+    }
+
+    /// Training streaks as of `asOf`, which defaults to today, counting
+    /// calendar days in `timeZone`. Workouts after `asOf` are ignored.
+    async fn training_streaks(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "DEFAULT_TIME_ZONE.to_owned()")] time_zone: String,
+        as_of: Option<NaiveDate>,
+    ) -> Result<TrainingStreaks, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        validate_time_zone(pool, &time_zone).await?;
+
+        // Consecutive days minus their row number are all the same date, which
+        // groups each run.
+        let streaks = sqlx::query_as!(
+            TrainingStreaks,
+            r#"
+WITH as_of AS (
+    SELECT COALESCE($2, (now() AT TIME ZONE $1)::DATE) AS day
+),
+days AS (
+    SELECT DISTINCT (performed_at AT TIME ZONE $1)::DATE AS day
+    FROM workouts
+    WHERE (performed_at AT TIME ZONE $1)::DATE <= (SELECT day FROM as_of)
+        AND visible_to(user_id, $3)
+),
+runs AS (
+    SELECT MAX(day) AS last_day, COUNT(*) AS length
+    FROM (SELECT day, day - ROW_NUMBER() OVER (ORDER BY day)::INT AS run FROM days) AS numbered
+    GROUP BY run
+)
+SELECT
+    COALESCE(MAX(length) FILTER (WHERE last_day >= (SELECT day FROM as_of) - 1), 0) AS "current_streak!",
+    COALESCE(MAX(length), 0) AS "longest_streak!"
+FROM runs
+            "#,
+            time_zone,
+            as_of,
+            viewer_id(ctx)
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(streaks)
+    }
+
+    /// Weekly training volume of the sets performed between `fromDate` and
+    /// `toDate`, oldest week first. Weeks without training are left out
+    /// unless `fillGaps` is true, in which case they are returned with zero
+    /// volume and no muscle group.
+    async fn volume_stats(
+        &self,
+        ctx: &Context<'_>,
+        from_date: DateTime<Utc>,
+        to_date: DateTime<Utc>,
+        group_by: VolumeGroupBy,
+        #[graphql(default)] fill_gaps: bool,
+    ) -> Result<Vec<VolumeBucket>, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        if from_date > to_date {
+            return Err(invalid_field(
+                "toDate",
+                "toDate must not be before fromDate",
+            ));
+        }
+        if to_date - from_date > chrono::Duration::days(MAX_VOLUME_STATS_DAYS) {
+            return Err(invalid_field(
+                "toDate",
+                format!("volumeStats covers at most {} days", MAX_VOLUME_STATS_DAYS),
+            ));
+        }
+
+        let buckets = sqlx::query_as!(
+            VolumeBucket,
+            r#"
+WITH totals AS (
+    SELECT
+        date_trunc('week', workouts.performed_at AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS week_start,
+        CASE WHEN $3 THEN exercises.muscle_group END AS muscle_group,
+        SUM(sets.reps * COALESCE(sets.weight_kg, 0)) AS total_volume_kg,
+        COUNT(*) AS set_count
+    FROM sets
+    JOIN workouts ON workouts.id = sets.workout_id
+    JOIN exercises ON exercises.id = sets.exercise_id
+    WHERE workouts.performed_at BETWEEN $1 AND $2
+        AND visible_to(workouts.user_id, $5)
+    GROUP BY 1, 2
+),
+weeks AS (
+    SELECT generate_series(
+        date_trunc('week', $1::TIMESTAMPTZ AT TIME ZONE 'UTC') AT TIME ZONE 'UTC',
+        $2,
+        INTERVAL '1 week'
+    ) AS week_start
+)
+SELECT
+    weeks.week_start AS "week_start!",
+    totals.muscle_group AS "muscle_group?: MuscleGroup",
+    COALESCE(totals.total_volume_kg, 0) AS "total_volume_kg!",
+    COALESCE(totals.set_count, 0) AS "set_count!"
+FROM weeks
+LEFT JOIN totals ON totals.week_start = weeks.week_start
+WHERE $4 OR totals.week_start IS NOT NULL
+ORDER BY weeks.week_start, totals.muscle_group
+            "#,
+            from_date,
+            to_date,
+            group_by == VolumeGroupBy::MuscleGroupPerWeek,
+            fill_gaps,
+            viewer_id(ctx)
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(buckets)
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    async fn create_exercise(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        main_muscle_worked_id: i32,
+        #[graphql(default_with = "MuscleGroup::Other")] muscle_group: MuscleGroup,
+        #[graphql(default_with = "Equipment::Other")] equipment: Equipment,
+    ) -> Result<Exercise, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let input = validate_exercise_input(
+            None,
+            CreateExerciseInput {
+                name,
+                main_muscle_worked_id,
+                muscle_group,
+                equipment,
+            },
+        )?;
+
+        let mut exercises = insert_exercises(pool, &[input]).await?;
+
+        Ok(exercises.remove(0))
+    }
+
+    /// Creates several exercises at once, returned in the order they were
+    /// given. Either every exercise is created or, if any input is invalid
+    /// or its name is taken, none are; the error's `field` names the
+    /// offending input, such as `inputs.3.name`.
+    async fn create_exercises(
+        &self,
+        ctx: &Context<'_>,
+        inputs: Vec<CreateExerciseInput>,
+    ) -> Result<Vec<Exercise>, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        if inputs.len() > MAX_CREATE_EXERCISES {
+            return Err(invalid_field(
+                "inputs",
+                format!(
+                    "inputs must not contain more than {} exercises",
+                    MAX_CREATE_EXERCISES
+                ),
+            ));
+        }
+
+        let inputs = inputs
+            .into_iter()
+            .enumerate()
+            .map(|(index, input)| validate_exercise_input(Some(index), input))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut first_index: HashMap<&str, usize> = HashMap::new();
+        for (index, input) in inputs.iter().enumerate() {
+            if let Some(first) = first_index.insert(&input.name, index) {
+                return Err(AppError::Conflict {
+                    message: format!(
+                        "inputs[{}]: name {:?} is also used by inputs[{}]",
+                        index, input.name, first
+                    ),
+                    field: Some(format!("inputs.{}.name", index)),
+                });
+            }
+        }
+
+        insert_exercises(pool, &inputs).await.map_err(|error| {
+            let index = duplicate_key_value(&error).and_then(|name| first_index.get(name).copied());
+
+            match (AppError::from(error), index) {
+                (AppError::Conflict { message, .. }, Some(index)) => AppError::Conflict {
+                    message: format!("inputs[{}]: {}", index, message),
+                    field: Some(format!("inputs.{}.name", index)),
+                },
+                (error, _) => error,
+            }
+        })
+    }
+
+    /// Renames an exercise, and sets its `muscleGroup` and `equipment` when
+    /// they are given.
+    async fn update_exercise(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        name: String,
+        muscle_group: Option<MuscleGroup>,
+        equipment: Option<Equipment>,
+    ) -> Result<Exercise, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let name = validate_name("name", &name)?;
+
+        let exercise = sqlx::query_as!(
+            Exercise,
+            r#"
+UPDATE exercises
+SET name = $2, muscle_group = COALESCE($3, muscle_group), equipment = COALESCE($4, equipment)
+WHERE id = $1
+RETURNING id, name, main_muscle_worked_id, muscle_group AS "muscle_group: MuscleGroup", equipment AS "equipment: Equipment", created_at, updated_at
+            "#,
+            id,
+            name,
+            muscle_group as Option<MuscleGroup>,
+            equipment as Option<Equipment>
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        exercise.ok_or_else(|| exercise_not_found(id))
+    }
+
+    /// Deletes an exercise from the shared library. Only admins may.
+    /// Exercises that are still part of a routine fail with `IN_USE`, listing
+    /// the routines in `routineIds`, unless `force` is set, in which case
+    /// they are removed from those routines first. Exercises with logged
+    /// sets can't be deleted either way.
+    #[graphql(guard(RoleGuard(required = "Role::Admin")))]
+    async fn delete_exercise(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        #[graphql(default)] force: bool,
+    ) -> Result<Exercise, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let mut tx = pool.begin().await?;
+
+        let routine_ids: Vec<i32> = sqlx::query!(
+            "SELECT routine_id FROM routine_exercises WHERE exercise_id = $1 ORDER BY routine_id FOR UPDATE",
+            id
+        )
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|row| row.routine_id)
+        .collect();
+
+        if !routine_ids.is_empty() {
+            if !force {
+                return Err(AppError::InUse {
+                    message: format!(
+                        "Exercise {} is part of routines {:?}; pass force to remove it from them",
+                        id, routine_ids
+                    ),
+                    routine_ids,
+                });
+            }
+
+            sqlx::query!("DELETE FROM routine_exercises WHERE exercise_id = $1", id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        let exercise = sqlx::query_as!(
+            Exercise,
+            r#"DELETE FROM exercises WHERE id = $1 RETURNING id, name, main_muscle_worked_id, muscle_group AS "muscle_group: MuscleGroup", equipment AS "equipment: Equipment", created_at, updated_at"#,
+            id
+        )
+        .fetch_optional(&mut tx)
+        .await
+        .map_err(|error| match pg_error_code(&error) {
+            Some("23503") => conflict(format!("Exercise {} has logged sets", id)),
+            _ => error.into(),
+        })?
+        .ok_or_else(|| exercise_not_found(id))?;
+
+        tx.commit().await?;
+
+        Ok(exercise)
+    }
+
+    /// Adds an alternate name to an exercise. Aliases are compared without
+    /// regard to case and may not match another exercise's name or any
+    /// existing alias.
+    async fn add_exercise_alias(
+        &self,
+        ctx: &Context<'_>,
+        exercise_id: i32,
+        alias: String,
+    ) -> Result<Exercise, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let alias = validate_name("alias", &alias)?;
+
+        let exercise = find_exercise(pool, exercise_id).await?;
+
+        let existing = sqlx::query!(
+            r#"
+SELECT id AS "id!", name AS "name!" FROM exercises
+WHERE lower(name) = lower($1) AND id <> $2
+UNION ALL
+SELECT exercises.id, exercises.name FROM exercise_aliases
+INNER JOIN exercises ON exercises.id = exercise_aliases.exercise_id
+WHERE lower(alias) = lower($1)
+LIMIT 1
+            "#,
+            alias,
+            exercise_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        if let Some(existing) = existing {
+            return Err(alias_conflict(&alias, existing.id, &existing.name));
+        }
+
+        sqlx::query!(
+            "INSERT INTO exercise_aliases (exercise_id, alias) VALUES ( $1, $2 )",
+            exercise_id,
+            alias
+        )
+        .execute(pool)
+        .await
+        .map_err(|error| match pg_error_code(&error) {
+            Some("23505") => conflict(format!("Alias {:?} is already in use", alias)),
+            _ => error.into(),
+        })?;
+
+        Ok(exercise)
+    }
+
+    async fn remove_exercise_alias(
+        &self,
+        ctx: &Context<'_>,
+        exercise_id: i32,
+        alias: String,
+    ) -> Result<Exercise, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        let exercise = find_exercise(pool, exercise_id).await?;
+
+        let result = sqlx::query!(
+            "DELETE FROM exercise_aliases WHERE exercise_id = $1 AND lower(alias) = lower($2)",
+            exercise_id,
+            alias.trim()
+        )
+        .execute(pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "Exercise {} has no alias {:?}",
+                exercise_id, alias
+            )));
+        }
+
+        Ok(exercise)
+    }
+
+    /// Records a body weight, given in `unit`, for the signed-in user when
+    /// there is one. `measuredAt` defaults to now.
+    async fn log_body_weight(
+        &self,
+        ctx: &Context<'_>,
+        weight: f64,
+        #[graphql(default_with = "WeightUnit::Kg")] unit: WeightUnit,
+        measured_at: Option<DateTime<Utc>>,
+        notes: Option<String>,
+    ) -> Result<BodyWeightEntry, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let weight_kg = unit.to_kg(weight);
+
+        if !BODY_WEIGHT_RANGE_KG.contains(&weight_kg) {
+            return Err(invalid_field(
+                "weight",
+                format!(
+                    "weight must be between {} and {} kg",
+                    BODY_WEIGHT_RANGE_KG.start(),
+                    BODY_WEIGHT_RANGE_KG.end()
+                ),
+            ));
+        }
+
+        let entry = sqlx::query_as!(
+            BodyWeightEntry,
+            r#"
+INSERT INTO body_weight_entries (user_id, measured_at, weight_kg, notes)
+VALUES ( $1, COALESCE($2, now()), $3, $4 )
+RETURNING id, measured_at, weight_kg, notes
+            "#,
+            viewer_id(ctx),
+            measured_at,
+            weight_kg,
+            notes
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(entry)
+    }
+
+    /// Deletes one of the viewer's body weight entries.
+    async fn delete_body_weight_entry(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+    ) -> Result<BodyWeightEntry, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        let entry = sqlx::query_as!(
+            BodyWeightEntry,
+            r#"
+DELETE FROM body_weight_entries
+WHERE id = $1 AND visible_to(user_id, $2)
+RETURNING id, measured_at, weight_kg, notes
+            "#,
+            id,
+            viewer_id(ctx)
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        entry.ok_or_else(|| AppError::NotFound(format!("Body weight entry {} not found", id)))
+    }
+
+    /// Creates an account and signs it in. Emails are compared without
+    /// regard to case, so an address can only register once however it is
+    /// capitalized.
+    async fn register(
+        &self,
+        ctx: &Context<'_>,
+        email: String,
+        password: String,
+    ) -> Result<AuthPayload, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let issuer = token_issuer(ctx)?;
+        let email = validate_email(&email)?;
+        validate_password(&password)?;
+
+        let password_hash = hash_password(password).await?;
+        let user = sqlx::query_as!(
+            User,
+            r#"INSERT INTO users (email, password_hash) VALUES ( $1, $2 ) RETURNING id, email, created_at, role AS "role: Role""#,
+            email,
+            password_hash
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|error| match pg_error_code(&error) {
+            Some("23505") => AppError::Conflict {
+                message: "An account with that email already exists".to_owned(),
+                field: Some("email".to_owned()),
+            },
+            _ => error.into(),
+        })?;
+
+        Ok(AuthPayload {
+            token: issuer.issue(user.id),
+            user,
+        })
+    }
+
+    /// Signs in with the email and password given to `register`.
+    async fn login(
+        &self,
+        ctx: &Context<'_>,
+        email: String,
+        password: String,
+    ) -> Result<AuthPayload, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let issuer = token_issuer(ctx)?;
+
+        let row = sqlx::query!(
+            r#"SELECT id, email, created_at, role AS "role: Role", password_hash FROM users WHERE lower(email) = lower($1)"#,
+            email.trim()
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        // Users created before passwords existed have no hash and can't log in.
+        let (row, password_hash) = match row {
+            Some(row) => match row.password_hash.clone() {
+                Some(password_hash) => (row, password_hash),
+                None => return Err(AppError::InvalidCredentials),
+            },
+            None => return Err(AppError::InvalidCredentials),
+        };
+        if !verify_password(password, password_hash).await {
+            return Err(AppError::InvalidCredentials);
+        }
+
+        Ok(AuthPayload {
+            token: issuer.issue(row.id),
+            user: User {
+                id: row.id,
+                email: row.email,
+                created_at: row.created_at,
+                role: row.role,
+            },
+        })
+    }
+
+    /// Creates an API key for the signed-in user. The key is only returned
+    /// here; the server keeps just a hash of it.
+    async fn create_api_key(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+    ) -> Result<CreateApiKeyPayload, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let user = require_user(ctx)?;
+        let name = validate_name("name", &name)?;
+
+        let (key, key_hash) = generate_api_key()?;
+        let api_key = sqlx::query_as!(
+            ApiKey,
+            "INSERT INTO api_keys (user_id, name, key_hash) VALUES ( $1, $2, $3 ) RETURNING id, name, created_at, last_used_at, revoked_at",
+            user.id,
+            name,
+            key_hash
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(CreateApiKeyPayload { key, api_key })
+    }
+
+    /// Revokes one of the signed-in user's API keys. Requests using it are
+    /// rejected with `INVALID_TOKEN` from then on. Revoking a key twice
+    /// keeps the first `revokedAt`.
+    async fn revoke_api_key(&self, ctx: &Context<'_>, id: i32) -> Result<ApiKey, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let user = require_user(ctx)?;
+
+        let api_key = sqlx::query_as!(
+            ApiKey,
+            r#"
+UPDATE api_keys
+SET revoked_at = COALESCE(revoked_at, now())
+WHERE id = $1 AND user_id = $2
+RETURNING id, name, created_at, last_used_at, revoked_at
+            "#,
+            id,
+            user.id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        api_key.ok_or_else(|| AppError::NotFound(format!("API key {} not found", id)))
+    }
+
+    /// Creates a routine for the signed-in user. Routine names are unique per
+    /// user, so reusing the name of one of the user's routines is a conflict.
+    async fn create_routine(&self, ctx: &Context<'_>, name: String) -> Result<Routine, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let user = require_user(ctx)?;
+        let name = validate_name("name", &name)?;
+
+        let routine = sqlx::query_as!(
+            Routine,
+            "INSERT INTO routines (name, user_id) VALUES ( $1, $2 ) RETURNING id, name, user_id, created_at, updated_at, archived_at, deleted_at, version",
+            name,
+            user.id
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|error| match pg_error_code(&error) {
+            Some("23503") => AppError::NotFound(format!("User {} not found", user.id)),
+            _ => error.into(),
+        })?;
+
+        ctx.data_unchecked::<RoutineBroadcaster>()
+            .publish(routine.clone());
+
+        Ok(routine)
+    }
+
+    /// Creates a routine along with its exercises and their prescribed sets
+    /// in a single transaction. If any exercise doesn't exist, nothing is
+    /// created.
+    async fn create_routine_with_exercises(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateRoutineInput,
+    ) -> Result<Routine, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let user = require_user(ctx)?;
+        let name = validate_name("input.name", &input.name)?;
+        validate_routine_exercises(&input)?;
+
+        let mut tx = pool.begin().await?;
+
+        let routine = sqlx::query_as!(
+            Routine,
+            "INSERT INTO routines (name, user_id) VALUES ( $1, $2 ) RETURNING id, name, user_id, created_at, updated_at, archived_at, deleted_at, version",
+            name,
+            user.id
+        )
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|error| match pg_error_code(&error) {
+            Some("23503") => AppError::NotFound(format!("User {} not found", user.id)),
+            _ => error.into(),
+        })?;
+
+        for (position, entry) in input.exercises.iter().enumerate() {
+            sqlx::query!(
+                "INSERT INTO routine_exercises (routine_id, exercise_id, position) VALUES ( $1, $2, $3 )",
+                routine.id,
+                entry.exercise_id,
+                position as i32
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|error| routine_exercise_error(error, routine.id, entry.exercise_id))?;
+
+            sqlx::query!(
+                r#"
+INSERT INTO routine_exercise_sets (routine_id, exercise_id, set_number, reps)
+SELECT $1, $2, set_number, $4 FROM generate_series(1, $3::INT) AS set_number
+                "#,
+                routine.id,
+                entry.exercise_id,
+                entry.target_sets,
+                entry.target_reps
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        ctx.data_unchecked::<RoutineBroadcaster>()
+            .publish(routine.clone());
+
+        Ok(routine)
+    }
+
+    /// Creates several routines at once, returned in the order their names
+    /// were given. Either every routine is created or, if any name is
+    /// invalid or already used by one of the user's routines, none are.
+    async fn create_routines(
+        &self,
+        ctx: &Context<'_>,
+        names: Vec<String>,
+    ) -> Result<Vec<Routine>, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let user = require_user(ctx)?;
+
+        if names.len() > MAX_CREATE_ROUTINES {
+            return Err(invalid_field(
+                "names",
+                format!(
+                    "names must not contain more than {} names",
+                    MAX_CREATE_ROUTINES
+                ),
+            ));
+        }
+
+        let names = names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| validate_name(&format!("names.{}", index), name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut routines: HashMap<String, Routine> = sqlx::query_as!(
+            Routine,
+            r#"
+INSERT INTO routines (name, user_id)
+SELECT name, $2 FROM UNNEST($1::TEXT[]) AS name
+RETURNING id, name, user_id, created_at, updated_at, archived_at, deleted_at, version
+            "#,
+            &names,
+            user.id
+        )
+        .fetch(pool)
+        .map_ok(|routine| (routine.name.clone(), routine))
+        .try_collect()
+        .await
+        .map_err(|error| match pg_error_code(&error) {
+            Some("23503") => AppError::NotFound(format!("User {} not found", user.id)),
+            _ => error.into(),
+        })?;
+
+        // A user's routine names are unique, so each name identifies its new
+        // row.
+        let routines: Vec<Routine> = names
+            .iter()
+            .filter_map(|name| routines.remove(name))
+            .collect();
+
+        let broadcaster = ctx.data_unchecked::<RoutineBroadcaster>();
+        for routine in &routines {
+            broadcaster.publish(routine.clone());
+        }
+
+        Ok(routines)
+    }
+
+    /// Recreates a routine from a `routineExport` document for the signed-in
+    /// user. Exercises are matched by name, and any that don't exist yet are
+    /// created.
+    async fn import_routine(&self, ctx: &Context<'_>, data: String) -> Result<Routine, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let user = require_user(ctx)?;
+        let document = RoutineDocument::parse("data", &data)?;
+
+        let mut tx = pool.begin().await?;
+        let routine = document.save(&mut tx, user.id).await?;
+        tx.commit().await?;
+
+        ctx.data_unchecked::<RoutineBroadcaster>()
+            .publish(routine.clone());
+
+        Ok(routine)
+    }
+
+    /// Renames a routine. `expectedVersion` must be the routine's current
+    /// `version`; if the routine has changed since the client read it, the
+    /// rename fails with `STALE_VERSION` instead of overwriting that change.
+    async fn update_routine(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        name: String,
+        expected_version: i32,
+    ) -> Result<Routine, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let name = validate_name("name", &name)?;
+        require_user(ctx)?;
+        check_routine_visible(ctx, id).await?;
+
+        // `version` is bumped by the `routines_bump_version` trigger.
+        let routine = sqlx::query_as!(
+            Routine,
+            r#"
+UPDATE routines
+SET name = $2
+WHERE id = $1 AND deleted_at IS NULL AND version = $3
+RETURNING id, name, user_id, created_at, updated_at, archived_at, deleted_at, version
+            "#,
+            id,
+            name,
+            expected_version
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        if let Some(routine) = routine {
+            return Ok(routine);
+        }
+
+        let current = sqlx::query!(
+            "SELECT version FROM routines WHERE id = $1 AND deleted_at IS NULL",
+            id
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| routine_not_found(id))?;
+
+        Err(AppError::StaleVersion(format!(
+            "Routine {} is at version {}, not {}",
+            id, current.version, expected_version
+        )))
+    }
+
+    /// Deletes a routine, keeping its exercises and sets so that
+    /// `restoreRoutine` can bring it back whole. Returns false when there is
+    /// no such routine, it is someone else's, or it is already deleted.
+    async fn delete_routine(&self, ctx: &Context<'_>, id: i32) -> Result<bool, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let user = require_user(ctx)?;
+
+        let result = sqlx::query!(
+            r#"
+UPDATE routines
+SET deleted_at = now()
+WHERE id = $1 AND deleted_at IS NULL AND visible_to(user_id, $2)
+            "#,
+            id,
+            user.id
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Undoes `deleteRoutine`. Restoring a routine that isn't deleted leaves
+    /// it unchanged.
+    async fn restore_routine(&self, ctx: &Context<'_>, id: i32) -> Result<Routine, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        require_user(ctx)?;
+        check_routine_visible(ctx, id).await?;
+
+        let routine = sqlx::query_as!(
+            Routine,
+            r#"
+UPDATE routines
+SET deleted_at = NULL
+WHERE id = $1 AND deleted_at IS NOT NULL
+RETURNING id, name, user_id, created_at, updated_at, archived_at, deleted_at, version
+            "#,
+            id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        if let Some(routine) = routine {
+            return Ok(routine);
+        }
+
+        sqlx::query_as!(
+            Routine,
+            "SELECT id, name, user_id, created_at, updated_at, archived_at, deleted_at, version FROM routines WHERE id = $1",
+            id
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| routine_not_found(id))
+    }
+
+    /// Copies a routine along with its exercises and their prescribed sets.
+    /// The copy is named `newName`, or `"<original name> (copy)"` when it is
+    /// omitted, numbered `(copy 2)`, `(copy 3)` and so on when the owner
+    /// already has a routine by that name, and with the original name cut
+    /// short if the copy's name would be too long. The copy is never
+    /// archived. Deleted routines can't be copied until they are restored,
+    /// and a `newName` the owner already uses for a routine is a conflict.
+    async fn duplicate_routine(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        new_name: Option<String>,
+    ) -> Result<Routine, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let user = require_user(ctx)?;
+        let new_name = match new_name {
+            Some(new_name) => Some(validate_name("newName", &new_name)?),
+            None => None,
+        };
+
+        let mut tx = pool.begin().await?;
+
+        let original = sqlx::query!(
+            r#"
+SELECT name FROM routines
+WHERE id = $1 AND deleted_at IS NULL AND visible_to(user_id, $2)
+            "#,
+            id,
+            user.id
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| routine_not_found(id))?;
+        let name = match new_name {
+            Some(new_name) => new_name,
+            None => {
+                let taken = sqlx::query!(
+                    "SELECT name FROM routines WHERE visible_to(user_id, $1) AND starts_with(name, $2)",
+                    user.id,
+                    copy_name_prefix(&original.name)
+                )
+                .fetch(&mut tx)
+                .map_ok(|routine| routine.name)
+                .try_collect()
+                .await?;
+                copy_name(&original.name, &taken)
+            }
+        };
+
+        let routine = sqlx::query_as!(
+            Routine,
+            "INSERT INTO routines (name, user_id) VALUES ( $1, $2 ) RETURNING id, name, user_id, created_at, updated_at, archived_at, deleted_at, version",
+            name,
+            user.id
+        )
+        .fetch_one(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+INSERT INTO routine_exercises (routine_id, exercise_id, position)
+SELECT $2, exercise_id, position FROM routine_exercises WHERE routine_id = $1
+            "#,
+            id,
+            routine.id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+INSERT INTO routine_exercise_sets (routine_id, exercise_id, set_number, reps, weight_kg)
+SELECT $2, exercise_id, set_number, reps, weight_kg FROM routine_exercise_sets WHERE routine_id = $1
+            "#,
+            id,
+            routine.id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        ctx.data_unchecked::<RoutineBroadcaster>()
+            .publish(routine.clone());
+
+        Ok(routine)
+    }
+
+    /// Hides a routine from `routines` without deleting it, so workouts
+    /// logged against it keep their history. Archiving an archived routine
+    /// leaves it unchanged.
+    async fn archive_routine(&self, ctx: &Context<'_>, id: i32) -> Result<Routine, AppError> {
+        set_routine_archived(ctx, id, true).await
+    }
+
+    /// Returns an archived routine to `routines`. Unarchiving a routine that
+    /// isn't archived leaves it unchanged.
+    async fn unarchive_routine(&self, ctx: &Context<'_>, id: i32) -> Result<Routine, AppError> {
+        set_routine_archived(ctx, id, false).await
+    }
+
+    /// Adds an exercise to a routine. When `position` is omitted the exercise
+    /// is appended after the routine's current last exercise.
+    async fn add_exercise_to_routine(
+        &self,
+        ctx: &Context<'_>,
+        routine_id: i32,
+        exercise_id: i32,
+        position: Option<i32>,
+    ) -> Result<Routine, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        require_user(ctx)?;
+        check_routine_visible(ctx, routine_id).await?;
+
+        sqlx::query!(
+            r#"
+INSERT INTO routine_exercises (routine_id, exercise_id, position)
+VALUES (
+    $1,
+    $2,
+    COALESCE(
+        $3,
+        (SELECT COALESCE(MAX(position) + 1, 0) FROM routine_exercises WHERE routine_id = $1)
+    )
+)
+            "#,
+            routine_id,
+            exercise_id,
+            position
+        )
+        .execute(pool)
+        .await
+        .map_err(|error| routine_exercise_error(error, routine_id, exercise_id))?;
+
+        let routine = sqlx::query_as!(
+            Routine,
+            "SELECT id, name, user_id, created_at, updated_at, archived_at, deleted_at, version FROM routines WHERE id = $1",
+            routine_id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        Ok(routine)
+    }
+
+    async fn remove_exercise_from_routine(
+        &self,
+        ctx: &Context<'_>,
+        routine_id: i32,
+        exercise_id: i32,
+    ) -> Result<Routine, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        require_user(ctx)?;
+        check_routine_visible(ctx, routine_id).await?;
+
+        let result = sqlx::query!(
+            "DELETE FROM routine_exercises WHERE routine_id = $1 AND exercise_id = $2",
+            routine_id,
+            exercise_id
+        )
+        .execute(pool)
+        .await?;
+
+        let routine = sqlx::query_as!(
+            Routine,
+            "SELECT id, name, user_id, created_at, updated_at, archived_at, deleted_at, version FROM routines WHERE id = $1",
+            routine_id
+        )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| routine_not_found(routine_id))?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "Exercise {} is not part of routine {}",
+                exercise_id, routine_id
+            )));
+        }
+
+        Ok(routine)
+    }
+
+    /// Reorders a routine's exercises to follow `exerciseIds`, which must
+    /// list every exercise in the routine exactly once.
+    async fn reorder_routine_exercises(
+        &self,
+        ctx: &Context<'_>,
+        routine_id: i32,
+        exercise_ids: Vec<i32>,
+    ) -> Result<Routine, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        require_user(ctx)?;
+        check_routine_visible(ctx, routine_id).await?;
+
+        let mut tx = pool.begin().await?;
+
+        let routine = sqlx::query_as!(
+            Routine,
+            "SELECT id, name, user_id, created_at, updated_at, archived_at, deleted_at, version FROM routines WHERE id = $1",
+            routine_id
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| routine_not_found(routine_id))?;
+
+        let current: Vec<i32> = sqlx::query!(
+            "SELECT exercise_id FROM routine_exercises WHERE routine_id = $1 FOR UPDATE",
+            routine_id
+        )
+        .fetch(&mut tx)
+        .map_ok(|row| row.exercise_id)
+        .try_collect()
+        .await?;
+
+        validate_exercise_order(&current, &exercise_ids)?;
+
+        sqlx::query!(
+            r#"
+UPDATE routine_exercises
+SET position = reordered.position - 1
+FROM UNNEST($2::INT[]) WITH ORDINALITY AS reordered (exercise_id, position)
+WHERE routine_exercises.routine_id = $1 AND routine_exercises.exercise_id = reordered.exercise_id
+            "#,
+            routine_id,
+            &exercise_ids
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(routine)
+    }
+
+    /// Replaces the sets prescribed for an exercise in a routine. Set numbers
+    /// follow the order of `sets`, starting at 1, and an empty list clears
+    /// them.
+    async fn set_exercise_sets(
+        &self,
+        ctx: &Context<'_>,
+        routine_id: i32,
+        exercise_id: i32,
+        sets: Vec<WorkoutSetInput>,
+    ) -> Result<RoutineExercise, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        for (index, set) in sets.iter().enumerate() {
+            validate_set(index, set.reps, set.weight_kg)?;
+        }
+        require_user(ctx)?;
+        check_routine_visible(ctx, routine_id).await?;
+
+        let mut tx = pool.begin().await?;
+
+        let position = sqlx::query!(
+            r#"
+SELECT position FROM routine_exercises
+WHERE routine_id = $1 AND exercise_id = $2
+FOR UPDATE
+            "#,
+            routine_id,
+            exercise_id
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Exercise {} is not part of routine {}",
+                exercise_id, routine_id
+            ))
+        })?
+        .position;
+
+        sqlx::query!(
+            "DELETE FROM routine_exercise_sets WHERE routine_id = $1 AND exercise_id = $2",
+            routine_id,
+            exercise_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        for (index, set) in sets.iter().enumerate() {
+            sqlx::query!(
+                r#"
+INSERT INTO routine_exercise_sets (routine_id, exercise_id, set_number, reps, weight_kg)
+VALUES ( $1, $2, $3, $4, $5 )
+                "#,
+                routine_id,
+                exercise_id,
+                index as i32 + 1,
+                set.reps,
+                set.weight_kg
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        let exercise = sqlx::query_as!(
+            Exercise,
+            r#"SELECT id, name, main_muscle_worked_id, muscle_group AS "muscle_group: MuscleGroup", equipment AS "equipment: Equipment", created_at, updated_at FROM exercises WHERE id = $1"#,
+            exercise_id
+        )
+        .fetch_one(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(RoutineExercise {
+            routine_id,
+            position,
+            exercise,
+        })
+    }
+
+    /// Records a workout for a routine along with the sets performed in it.
+    /// The workout and all of its sets are written in a single transaction.
+    /// `performed_at` defaults to now.
+    #[allow(clippy::too_many_arguments)]
+    async fn log_workout(
+        &self,
+        ctx: &Context<'_>,
+        routine_id: i32,
+        performed_at: Option<DateTime<Utc>>,
+        notes: Option<String>,
+        started_at: Option<DateTime<Utc>>,
+        finished_at: Option<DateTime<Utc>>,
+        #[graphql(default)] sets: Vec<SetInput>,
+    ) -> Result<Workout, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+        validate_notes("notes", notes.as_deref())?;
+        if let (Some(started_at), Some(finished_at)) = (started_at, finished_at) {
+            if finished_at < started_at {
+                return Err(invalid_field(
+                    "finishedAt",
+                    "finishedAt must not be before startedAt",
+                ));
+            }
+        }
+
+        let mut rest_seconds = Vec::with_capacity(sets.len());
+        for (index, set) in sets.iter().enumerate() {
+            validate_set(index, set.reps, set.weight_kg)?;
+            validate_notes(&format!("sets.{}.notes", index), set.notes.as_deref())?;
+            rest_seconds.push(validate_rest(index, set.rest_seconds)?);
+        }
+        check_routine_visible(ctx, routine_id).await?;
+
+        let mut tx = pool.begin().await?;
+
+        let workout = sqlx::query_as!(
+            Workout,
+            r#"
+INSERT INTO workouts (routine_id, performed_at, notes, started_at, finished_at, user_id)
+VALUES ( $1, COALESCE($2, now()), $3, $4, $5, $6 )
+RETURNING id, routine_id, performed_at, notes, distance_m, duration_s, avg_heart_rate, started_at, finished_at
+            "#,
+            routine_id,
+            performed_at,
+            notes,
+            started_at,
+            finished_at,
+            viewer_id(ctx)
+        )
+        .fetch_one(&mut tx)
+        .await
+        .map_err(|error| match pg_error_code(&error) {
+            Some("23503") => routine_not_found(routine_id),
+            _ => error.into(),
+        })?;
+
+        for (position, (set, rest_seconds)) in sets.iter().zip(rest_seconds).enumerate() {
+            sqlx::query!(
+                r#"
+INSERT INTO sets (workout_id, exercise_id, reps, weight_kg, position, notes, rest_seconds)
+VALUES ( $1, $2, $3, $4, $5, $6, $7 )
+                "#,
+                workout.id,
+                set.exercise_id,
+                set.reps,
+                set.weight_kg,
+                position as i32,
+                set.notes,
+                rest_seconds
+            )
+            .execute(&mut tx)
+            .await
+            .map_err(|error| match pg_error_code(&error) {
+                Some("23503") => exercise_not_found(set.exercise_id),
+                _ => error.into(),
+            })?;
+        }
+
+        tx.commit().await?;
+
+        Ok(workout)
+    }
+
+    /// Replaces a workout's notes. Passing `null` clears them; omitting
+    /// `notes` leaves the workout unchanged.
+    async fn update_workout_notes(
+        &self,
+        ctx: &Context<'_>,
+        workout_id: i32,
+        notes: MaybeUndefined<String>,
+    ) -> Result<Workout, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        validate_notes("notes", notes.value().map(String::as_str))?;
+
+        let workout = sqlx::query_as!(
+            Workout,
+            r#"
+UPDATE workouts
+SET notes = CASE WHEN $2 THEN $3 ELSE notes END
+WHERE id = $1 AND visible_to(user_id, $4)
+RETURNING id, routine_id, performed_at, notes, distance_m, duration_s, avg_heart_rate, started_at, finished_at
+            "#,
+            workout_id,
+            !notes.is_undefined(),
+            notes.value(),
+            viewer_id(ctx)
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        workout.ok_or_else(|| AppError::NotFound(format!("Workout {} not found", workout_id)))
+    }
+
+    /// Replaces a set's notes. Passing `null` clears them; omitting `notes`
+    /// leaves the set unchanged.
+    async fn update_set_notes(
+        &self,
+        ctx: &Context<'_>,
+        set_id: i32,
+        notes: MaybeUndefined<String>,
+    ) -> Result<Set, AppError> {
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        validate_notes("notes", notes.value().map(String::as_str))?;
+
+        let set = sqlx::query_as!(
+            Set,
+            r#"
+UPDATE sets
+SET notes = CASE WHEN $2 THEN $3 ELSE notes END
+WHERE id = $1
+    AND workout_id IN (SELECT id FROM workouts WHERE visible_to(user_id, $4))
+RETURNING id, workout_id, exercise_id, reps, weight_kg, position, notes, rest_seconds
+            "#,
+            set_id,
+            !notes.is_undefined(),
+            notes.value(),
+            viewer_id(ctx)
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        set.ok_or_else(|| AppError::NotFound(format!("Set {} not found", set_id)))
+    }
+}
+
+async fn find_exercise(pool: &Pool<Postgres>, id: i32) -> Result<Exercise, AppError> {
+    sqlx::query_as!(
+        Exercise,
+        r#"SELECT id, name, main_muscle_worked_id, muscle_group AS "muscle_group: MuscleGroup", equipment AS "equipment: Equipment", created_at, updated_at FROM exercises WHERE id = $1"#,
+        id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| exercise_not_found(id))
+}
+
+/// Inserts exercises in a single statement, so either all of them are
+/// created or none are, and returns them in the order given. Names must
+/// already be validated and distinct.
+async fn insert_exercises(
+    pool: &Pool<Postgres>,
+    inputs: &[CreateExerciseInput],
+) -> Result<Vec<Exercise>, sqlx::Error> {
+    let names: Vec<&str> = inputs.iter().map(|input| input.name.as_str()).collect();
+    let main_muscle_worked_ids: Vec<i32> = inputs
+        .iter()
+        .map(|input| input.main_muscle_worked_id)
+        .collect();
+    let muscle_groups: Vec<&str> = inputs
+        .iter()
+        .map(|input| input.muscle_group.as_str())
+        .collect();
+    let equipment: Vec<&str> = inputs
+        .iter()
+        .map(|input| input.equipment.as_str())
+        .collect();
+
+    let mut exercises: HashMap<String, Exercise> = sqlx::query_as!(
+        Exercise,
+        r#"
+INSERT INTO exercises (name, main_muscle_worked_id, muscle_group, equipment)
+SELECT * FROM UNNEST($1::TEXT[], $2::INT[], $3::TEXT[]::muscle_group[], $4::TEXT[]::equipment[])
+RETURNING id, name, main_muscle_worked_id, muscle_group AS "muscle_group: MuscleGroup", equipment AS "equipment: Equipment", created_at, updated_at
+        "#,
+        &names as &[&str],
+        &main_muscle_worked_ids,
+        &muscle_groups as &[&str],
+        &equipment as &[&str]
+    )
+    .fetch(pool)
+    .map_ok(|exercise| (exercise.name.clone(), exercise))
+    .try_collect()
+    .await?;
+
+    // Exercise names are unique, so each name identifies its new row.
+    Ok(names
+        .iter()
+        .filter_map(|name| exercises.remove(*name))
+        .collect())
+}
+
+fn alias_conflict(alias: &str, exercise_id: i32, exercise_name: &str) -> AppError {
+    conflict(format!(
+        "Alias {:?} is already used by exercise {} ({})",
+        alias, exercise_id, exercise_name
+    ))
+}
+
+/// The longest suffix `copy_name` adds, `" (copy 2147483647)"`.
+const MAX_COPY_SUFFIX_LENGTH: usize = 18;
+
+/// The first of `"<name> (copy)"`, `"<name> (copy 2)"`, `"<name> (copy 3)"`
+/// and so on that isn't one of the `taken` routine names. `name` is cut short
+/// where the copy would otherwise be longer than `MAX_NAME_LENGTH`.
+fn copy_name(name: &str, taken: &HashSet<String>) -> String {
+    let mut number = 1;
+    loop {
+        let suffix = match number {
+            1 => " (copy)".to_owned(),
+            _ => format!(" (copy {})", number),
+        };
+        let copy = name
+            .chars()
+            .take(MAX_NAME_LENGTH - suffix.chars().count())
+            .chain(suffix.chars())
+            .collect();
+        if !taken.contains(&copy) {
+            return copy;
+        }
+        number += 1;
+    }
+}
+
+/// What every name `copy_name` can pick for `name` starts with.
+fn copy_name_prefix(name: &str) -> String {
+    name.chars()
+        .take(MAX_NAME_LENGTH - MAX_COPY_SUFFIX_LENGTH)
+        .collect()
+}
+
+/// The id of the signed-in user, or `None` for an anonymous request. Queries
+/// pass it to the `visible_to` SQL function to scope rows to the viewer.
+pub(crate) fn viewer_id(ctx: &Context<'_>) -> Option<i32> {
+    ctx.data_opt::<AuthenticatedUser>().map(|user| user.id)
+}
+
+/// Whether the viewer may see `routine`, as `visible_to` decides in SQL: a
+/// signed-in user only sees their own, and an anonymous request only sees
+/// routines without an owner.
+pub(crate) fn visible_to_viewer(ctx: &Context<'_>, routine: &Routine) -> bool {
+    routine.user_id == viewer_id(ctx)
+}
+
+/// Fails with NOT_FOUND unless routine `id` exists and is visible to the
+/// viewer, so that someone else's routine can't be told apart from a missing
+/// one. Mutations that change a routine call `require_user` first: anonymous
+/// requests may read routines without an owner, but not change them.
+async fn check_routine_visible(ctx: &Context<'_>, id: i32) -> Result<(), AppError> {
+    let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+
+    sqlx::query!(
+        "SELECT id FROM routines WHERE id = $1 AND visible_to(user_id, $2)",
+        id,
+        viewer_id(ctx)
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| routine_not_found(id))?;
+
+    Ok(())
+}
+
+/// Archives or unarchives a routine. Only routines whose state changes are
+/// written, so a repeated call leaves `archivedAt` and `updatedAt` alone.
+async fn set_routine_archived(
+    ctx: &Context<'_>,
+    id: i32,
+    archived: bool,
+) -> Result<Routine, AppError> {
+    let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+    require_user(ctx)?;
+    check_routine_visible(ctx, id).await?;
+
+    let routine = sqlx::query_as!(
+        Routine,
+        r#"
+UPDATE routines
+SET archived_at = CASE WHEN $2 THEN now() END
+WHERE id = $1 AND (archived_at IS NULL) = $2
+RETURNING id, name, user_id, created_at, updated_at, archived_at, deleted_at, version
+        "#,
+        id,
+        archived
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(routine) = routine {
+        return Ok(routine);
+    }
+
+    sqlx::query_as!(
+        Routine,
+        "SELECT id, name, user_id, created_at, updated_at, archived_at, deleted_at, version FROM routines WHERE id = $1",
+        id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| routine_not_found(id))
+}
+
+/// How many unread routines a subscriber may fall behind by before further
+/// routines are dropped for it.
+const SUBSCRIBER_BUFFER: usize = 16;
+
+/// Fans newly created routines out to every `routineCreated` subscriber.
+/// Publishing never waits: a subscriber whose buffer is full misses the
+/// routine, and disconnected subscribers are removed.
+#[derive(Clone, Default)]
+pub struct RoutineBroadcaster {
+    subscribers: Arc<Mutex<Vec<Sender<Routine>>>>,
+}
+
+impl RoutineBroadcaster {
+    fn subscribe(&self) -> Receiver<Routine> {
+        let (sender, receiver) = channel::bounded(SUBSCRIBER_BUFFER);
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    fn publish(&self, routine: Routine) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|subscriber| match subscriber.try_send(routine.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                tracing::warn!("routineCreated subscriber is lagging; dropping routine");
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        });
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// The viewer's routines as they are created.
+    async fn routine_created(&self, ctx: &Context<'_>) -> impl Stream<Item = Routine> {
+        let user_id = viewer_id(ctx);
+
+        ctx.data_unchecked::<RoutineBroadcaster>()
+            .subscribe()
+            .filter(move |routine| std::future::ready(routine.user_id == user_id))
+    }
+}
+
+const MAX_NAME_LENGTH: usize = 255;
+
+const MAX_NOTES_LENGTH: usize = 2000;
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// The token issuer the server adds when `JWT_SECRET` is set. Without one
+/// there is no way to sign users in.
+fn token_issuer<'a>(ctx: &Context<'a>) -> Result<&'a TokenIssuer, AppError> {
+    ctx.data_opt::<TokenIssuer>().ok_or_else(|| {
+        tracing::error!("cannot issue tokens because JWT_SECRET is not set");
+        AppError::Internal
+    })
+}
+
+/// Trims an email address, rejecting ones without a local part and a domain.
+fn validate_email(email: &str) -> Result<String, AppError> {
+    let email = email.trim();
+
+    match email.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && !domain.is_empty() => {}
+        _ => return Err(invalid_field("email", "email must be an email address")),
+    }
+
+    if email.chars().count() > MAX_NAME_LENGTH {
+        return Err(invalid_field(
+            "email",
+            format!("email must be at most {} characters long", MAX_NAME_LENGTH),
+        ));
+    }
+
+    Ok(email.to_owned())
+}
+
+fn validate_password(password: &str) -> Result<(), AppError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(invalid_field(
+            "password",
+            format!(
+                "password must be at least {} characters long",
+                MIN_PASSWORD_LENGTH
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The longest rest a set may record, a day.
+const MAX_REST_SECONDS: i64 = 24 * 60 * 60;
+
+/// Checks a set's rest against `MAX_REST_SECONDS`, returning it as stored.
+fn validate_rest(index: usize, rest: Option<Seconds>) -> Result<Option<i32>, AppError> {
+    match rest {
+        Some(Seconds(seconds)) if seconds > MAX_REST_SECONDS => Err(invalid_field(
+            format!("sets.{}.restSeconds", index),
+            format!(
+                "sets[{}]: restSeconds must be at most {}",
+                index, MAX_REST_SECONDS
+            ),
+        )),
+        Some(Seconds(seconds)) => Ok(Some(seconds as i32)),
+        None => Ok(None),
+    }
+}
+
+/// Rejects workout or set notes longer than `MAX_NOTES_LENGTH` characters.
+/// The error names the field but never echoes the notes.
+fn validate_notes(field: &str, notes: Option<&str>) -> Result<(), AppError> {
+    match notes {
+        Some(notes) if notes.chars().count() > MAX_NOTES_LENGTH => Err(invalid_field(
+            field,
+            format!(
+                "{} must be at most {} characters long",
+                field, MAX_NOTES_LENGTH
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Trims a routine or exercise name, rejecting names that are empty after
+/// trimming or longer than `MAX_NAME_LENGTH` characters.
+pub(crate) fn validate_name(field: &str, name: &str) -> Result<String, AppError> {
+    let name = name.trim();
+
+    if name.is_empty() {
+        return Err(invalid_field(field, format!("{} must not be blank", field)));
+    }
+
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(invalid_field(
+            field,
+            format!(
+                "{} must be at most {} characters long",
+                field, MAX_NAME_LENGTH
+            ),
+        ));
+    }
+
+    Ok(name.to_owned())
+}
+
+/// Validates an exercise to create, trimming its name. `index` is the
+/// input's position in a batch, which prefixes the field path.
+fn validate_exercise_input(
+    index: Option<usize>,
+    input: CreateExerciseInput,
+) -> Result<CreateExerciseInput, AppError> {
+    let field = match index {
+        Some(index) => format!("inputs.{}.name", index),
+        None => "name".to_owned(),
+    };
+
+    Ok(CreateExerciseInput {
+        name: validate_name(&field, &input.name)?,
+        ..input
+    })
+}
+
+/// Checks that `exercise_ids` is a permutation of `current`, naming any
+/// repeated, missing or unexpected ids.
+fn validate_exercise_order(current: &[i32], exercise_ids: &[i32]) -> Result<(), AppError> {
+    let current: BTreeSet<i32> = current.iter().copied().collect();
+    let mut given = BTreeSet::new();
+    let repeated: BTreeSet<i32> = exercise_ids
+        .iter()
+        .copied()
+        .filter(|id| !given.insert(*id))
+        .collect();
+
+    let problems: Vec<String> = [
+        ("repeated", repeated),
+        ("missing", current.difference(&given).copied().collect()),
+        (
+            "not in the routine",
+            given.difference(&current).copied().collect(),
+        ),
+    ]
+    .iter()
+    .filter(|(_, ids)| !ids.is_empty())
+    .map(|(problem, ids)| format!("{}: {}", problem, join_ids(ids)))
+    .collect();
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    Err(invalid_field(
+        "exerciseIds",
+        format!(
+            "exerciseIds must list each of the routine's exercises once ({})",
+            problems.join("; ")
+        ),
+    ))
+}
+
+/// Checks that `time_zone` is a time zone name Postgres knows.
+async fn validate_time_zone(pool: &Pool<Postgres>, time_zone: &str) -> Result<(), AppError> {
+    let known = sqlx::query!(
+        r#"SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1) AS "known!""#,
+        time_zone
+    )
+    .fetch_one(pool)
+    .await?
+    .known;
+
+    if !known {
+        return Err(invalid_field(
+            "timeZone",
+            format!("timeZone {:?} is not a known time zone", time_zone),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks that each exercise in a new routine is listed once and has at
+/// least one set of at least one rep.
+fn validate_routine_exercises(input: &CreateRoutineInput) -> Result<(), AppError> {
+    let mut seen = BTreeSet::new();
+
+    for (index, entry) in input.exercises.iter().enumerate() {
+        if !seen.insert(entry.exercise_id) {
+            return Err(invalid_field(
+                format!("input.exercises.{}.exerciseId", index),
+                format!(
+                    "exercises[{}]: exercise {} is listed more than once",
+                    index, entry.exercise_id
+                ),
+            ));
+        }
+
+        for (field, value) in &[
+            ("targetSets", entry.target_sets),
+            ("targetReps", entry.target_reps),
+        ] {
+            if *value < 1 {
+                return Err(invalid_field(
+                    format!("input.exercises.{}.{}", index, field),
+                    format!("exercises[{}]: {} must be at least 1", index, field),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn join_ids(ids: &BTreeSet<i32>) -> String {
+    ids.iter()
+        .map(i32::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn validate_set(index: usize, reps: i32, weight_kg: Option<f64>) -> Result<(), AppError> {
+    if reps < 1 {
+        return Err(invalid_field(
+            format!("sets.{}.reps", index),
+            format!("sets[{}]: reps must be at least 1", index),
+        ));
+    }
+
+    if let Some(weight_kg) = weight_kg {
+        if weight_kg < 0.0 {
+            return Err(invalid_field(
+                format!("sets.{}.weightKg", index),
+                format!("sets[{}]: weightKg must not be negative", index),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds the GraphQL schema with its data loaders and shared state and the
+/// default query limits.
+pub fn build_schema(postgres_pool: Pool<Postgres>) -> AppSchema {
+    build_schema_with_limits(postgres_pool, QueryLimits::default())
+}
+
+pub fn build_schema_with_limits(postgres_pool: Pool<Postgres>, limits: QueryLimits) -> AppSchema {
+    schema_builder(postgres_pool, limits).finish()
+}
+
+/// The schema as `build_schema_with_limits` configures it, for callers that
+/// adjust it further before finishing.
+pub(crate) fn schema_builder(
+    postgres_pool: Pool<Postgres>,
+    limits: QueryLimits,
+) -> SchemaBuilder<QueryRoot, MutationRoot, SubscriptionRoot> {
+    Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .limit_depth(limits.max_depth)
+        .limit_complexity(limits.max_complexity)
+        .extension(limits)
+        .data(DataLoader::new(ExerciseLoader::new(postgres_pool.clone())))
+        .data(DataLoader::new(ExerciseAliasesLoader::new(
+            postgres_pool.clone(),
+        )))
+        .data(DataLoader::new(MuscleLoader::new(postgres_pool.clone())))
+        .data(DataLoader::new(WorkoutSetsLoader::new(
+            postgres_pool.clone(),
+        )))
+        .data(DataLoader::new(RoutineLoader::new(postgres_pool.clone())))
+        .data(DataLoader::new(ExerciseRoutinesLoader::new(
+            postgres_pool.clone(),
+        )))
+        .data(DataLoader::new(RoutineExercisesLoader::new(
+            postgres_pool.clone(),
+        )))
+        .data(DataLoader::new(RoutineExerciseSetsLoader::new(
+            postgres_pool.clone(),
+        )))
+        .data(DataLoader::new(UserLoader::new(postgres_pool.clone())))
+        .data(RoutineBroadcaster::default())
+        .data(postgres_pool)
+        .extension(ErrorCorrelation)
+        .extension(ResolverTiming)
+}
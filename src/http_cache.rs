@@ -0,0 +1,70 @@
+use ring::digest::{digest, SHA256};
+use tide::http::headers::{CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+use tide::{Body, Middleware, Next, Request, Response, StatusCode};
+
+/// Lets clients cache the `200` responses of a route: sets `cache_control`
+/// and an `ETag` hashed from the body, and answers a request whose
+/// `If-None-Match` has that tag with `304 Not Modified` and no body.
+///
+/// The body is still built for every request, so this saves bandwidth
+/// rather than work.
+pub(crate) struct ETagCache {
+    cache_control: &'static str,
+}
+
+impl ETagCache {
+    pub(crate) fn new(cache_control: &'static str) -> Self {
+        Self { cache_control }
+    }
+}
+
+/// A strong `ETag` for `body`.
+fn etag(body: &[u8]) -> String {
+    format!(
+        "\"{}\"",
+        base64::encode_config(digest(&SHA256, body), base64::URL_SAFE_NO_PAD)
+    )
+}
+
+/// Whether an `If-None-Match` value matches `etag`. Tags are compared
+/// weakly, as the header requires, so `W/` prefixes are ignored.
+fn if_none_match(header: &str, etag: &str) -> bool {
+    header.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for ETagCache {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let if_none_match_header = req.header(IF_NONE_MATCH).map(|values| {
+            values
+                .iter()
+                .map(|value| value.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        });
+
+        let mut resp = next.run(req).await;
+        if resp.status() != StatusCode::Ok {
+            return Ok(resp);
+        }
+
+        let body = resp.take_body();
+        let mime = body.mime().clone();
+        let bytes = body.into_bytes().await?;
+        let etag = etag(&bytes);
+
+        if if_none_match_header.is_some_and(|header| if_none_match(&header, &etag)) {
+            resp = Response::new(StatusCode::NotModified);
+        } else {
+            let mut body = Body::from_bytes(bytes);
+            body.set_mime(mime);
+            resp.set_body(body);
+        }
+        resp.insert_header(CACHE_CONTROL, self.cache_control);
+        resp.insert_header(ETAG, etag);
+
+        Ok(resp)
+    }
+}
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use std::collections::HashMap;
+use tide::{Body, Request, Response, StatusCode};
+
+use crate::graphql::validate_name;
+use crate::server::read_body;
+
+#[derive(Deserialize)]
+struct ExerciseRow {
+    name: String,
+    main_muscle_worked: String,
+}
+
+/// A CSV row that was not imported, identified by its 1-based line number.
+#[derive(Serialize)]
+pub struct RowError {
+    line: u64,
+    message: String,
+}
+
+#[derive(Serialize, Default)]
+pub struct ImportSummary {
+    inserted: usize,
+    skipped: usize,
+    errors: Vec<RowError>,
+}
+
+/// Inserts the exercises in a CSV with `name` and `main_muscle_worked`
+/// (muscle name) columns in a single transaction. Names that already exist,
+/// in the database or earlier in the file, are skipped. Invalid rows are
+/// reported in `errors` without aborting the rest of the import.
+pub async fn import_exercises(
+    postgres_pool: &Pool<Postgres>,
+    csv: &str,
+) -> Result<ImportSummary, sqlx::Error> {
+    let mut tx = postgres_pool.begin().await?;
+    let mut summary = ImportSummary::default();
+
+    let muscles: HashMap<String, i32> = sqlx::query!("SELECT id, name FROM muscles")
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|muscle| (muscle.name.to_lowercase(), muscle.id))
+        .collect();
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(csv.as_bytes());
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(error) => {
+            summary.errors.push(RowError {
+                line: 1,
+                message: error.to_string(),
+            });
+            return Ok(summary);
+        }
+    };
+
+    for result in reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(error) => {
+                summary.errors.push(RowError {
+                    line: error.position().map_or(0, |position| position.line()),
+                    message: error.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |position| position.line());
+
+        let row: ExerciseRow = match record.deserialize(Some(&headers)) {
+            Ok(row) => row,
+            Err(error) => {
+                summary.errors.push(RowError {
+                    line,
+                    message: error.to_string(),
+                });
+                continue;
+            }
+        };
+
+        let name = match validate_name("name", &row.name) {
+            Ok(name) => name,
+            Err(error) => {
+                summary.errors.push(RowError {
+                    line,
+                    message: error.message().to_owned(),
+                });
+                continue;
+            }
+        };
+
+        let main_muscle_worked_id = match muscles.get(&row.main_muscle_worked.to_lowercase()) {
+            Some(id) => *id,
+            None => {
+                summary.errors.push(RowError {
+                    line,
+                    message: format!("Muscle {:?} not found", row.main_muscle_worked),
+                });
+                continue;
+            }
+        };
+
+        let inserted = sqlx::query!(
+            r#"
+INSERT INTO exercises (name, main_muscle_worked_id)
+VALUES ( $1, $2 )
+ON CONFLICT (name) DO NOTHING
+RETURNING id
+            "#,
+            name,
+            main_muscle_worked_id
+        )
+        .fetch_optional(&mut tx)
+        .await?;
+
+        if inserted.is_some() {
+            summary.inserted += 1;
+        } else {
+            summary.skipped += 1;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(summary)
+}
+
+/// `POST /import/exercises` with a `text/csv` body of at most
+/// `max_body_bytes`; larger ones get a `413`.
+pub(crate) async fn import_exercises_endpoint(
+    mut req: Request<()>,
+    postgres_pool: Pool<Postgres>,
+    max_body_bytes: usize,
+) -> tide::Result {
+    match req.content_type() {
+        Some(content_type) if content_type.essence() == "text/csv" => {}
+        _ => return Ok(Response::new(StatusCode::UnsupportedMediaType)),
+    }
+
+    let body = match read_body(&mut req, max_body_bytes).await? {
+        Some(body) => body,
+        None => {
+            tracing::warn!("CSV import is larger than {} bytes", max_body_bytes);
+            return Ok(Response::new(StatusCode::PayloadTooLarge));
+        }
+    };
+    let csv = String::from_utf8(body)
+        .map_err(|error| tide::Error::new(StatusCode::UnprocessableEntity, error))?;
+    let summary = import_exercises(&postgres_pool, &csv)
+        .await
+        .map_err(|error| {
+            tracing::error!("exercise import failed: {}", error);
+            tide::Error::from_str(StatusCode::InternalServerError, "Internal server error")
+        })?;
+
+    let mut resp = Response::new(StatusCode::Ok);
+    resp.set_body(Body::from_json(&summary)?);
+    Ok(resp)
+}
@@ -0,0 +1,71 @@
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextParseQuery};
+use async_graphql::parser::types::{ExecutableDocument, Selection, SelectionSet};
+use async_graphql::{ErrorExtensionValues, ServerError, ServerResult, Variables};
+use std::sync::Arc;
+
+const INTROSPECTION_FIELDS: [&str; 2] = ["__schema", "__type"];
+
+/// Rejects queries that select `__schema` or `__type`.
+///
+/// `disable_introspection` alone makes those fields resolve to `null`; this
+/// fails the whole query with an `INTROSPECTION_DISABLED` error instead.
+pub(crate) struct RejectIntrospection;
+
+impl ExtensionFactory for RejectIntrospection {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(RejectIntrospection)
+    }
+}
+
+/// Introspection fields only exist on the query root, so this looks through
+/// the root selection and the fragments spread into it. `depth` stops
+/// fragment cycles, which fail validation anyway.
+fn selects_introspection(
+    document: &ExecutableDocument,
+    selection_set: &SelectionSet,
+    depth: usize,
+) -> bool {
+    if depth > document.fragments.len() {
+        return false;
+    }
+
+    selection_set.items.iter().any(|item| match &item.node {
+        Selection::Field(field) => INTROSPECTION_FIELDS.contains(&field.node.name.node.as_str()),
+        Selection::FragmentSpread(spread) => document
+            .fragments
+            .get(&spread.node.fragment_name.node)
+            .is_some_and(|fragment| {
+                selects_introspection(document, &fragment.node.selection_set.node, depth + 1)
+            }),
+        Selection::InlineFragment(fragment) => {
+            selects_introspection(document, &fragment.node.selection_set.node, depth + 1)
+        }
+    })
+}
+
+#[async_trait::async_trait]
+impl Extension for RejectIntrospection {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+
+        let introspects = document.operations.iter().any(|(_, operation)| {
+            selects_introspection(&document, &operation.node.selection_set.node, 0)
+        });
+        if introspects {
+            let mut extensions = ErrorExtensionValues::default();
+            extensions.set("code", "INTROSPECTION_DISABLED");
+
+            let mut error = ServerError::new("Introspection is disabled", None);
+            error.extensions = Some(extensions);
+            return Err(error);
+        }
+
+        Ok(document)
+    }
+}
@@ -0,0 +1,39 @@
+mod auth;
+mod config;
+mod correlation;
+mod cors;
+mod duration;
+mod error;
+mod export;
+mod graphql;
+mod http_cache;
+mod import;
+mod introspection;
+mod limits;
+mod loaders;
+mod metrics;
+mod migrate;
+mod models;
+mod persisted_queries;
+mod rate_limit;
+mod routine_document;
+mod seed;
+mod server;
+mod sql_count;
+mod trace;
+mod units;
+mod upload;
+
+pub use auth::{promote_admin, AuthenticatedUser};
+pub use config::{AppEnv, Config, ConfigError};
+pub use error::AppError;
+pub use graphql::{
+    build_schema, build_schema_with_limits, AppSchema, MutationRoot, QueryRoot, SubscriptionRoot,
+};
+pub use import::{import_exercises, ImportSummary, RowError};
+pub use limits::QueryLimits;
+pub use migrate::{migrate, MigrationError};
+pub use models::{AuthPayload, Role, User};
+pub use seed::seed;
+pub use server::{app, health, run, run_migrations};
+pub use trace::init_tracing;
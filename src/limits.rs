@@ -0,0 +1,209 @@
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextValidation,
+};
+use async_graphql::parser::types::{ExecutableDocument, Field, Selection, SelectionSet};
+use async_graphql::{
+    ErrorExtensionValues, ServerError, ServerResult, ValidationResult, Value, Variables,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::graphql::{list_complexity, DEFAULT_PAGE_SIZE};
+
+/// The deepest and most expensive queries the schema will execute.
+/// Over-limit queries are rejected before any resolver runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryLimits {
+    pub max_depth: usize,
+    pub max_complexity: usize,
+}
+
+pub(crate) const DEFAULT_MAX_DEPTH: usize = 12;
+pub(crate) const DEFAULT_MAX_COMPLEXITY: usize = 5000;
+
+impl Default for QueryLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_complexity: DEFAULT_MAX_COMPLEXITY,
+        }
+    }
+}
+
+fn limit_error(code: &str, message: String) -> ServerError {
+    let mut extensions = ErrorExtensionValues::default();
+    extensions.set("code", code);
+
+    let mut error = ServerError::new(message, None);
+    error.extensions = Some(extensions);
+    error
+}
+
+/// Reports which limit a query exceeded and by how much. The schema's own
+/// `limit_depth` and `limit_complexity` checks only say that one was.
+impl ExtensionFactory for QueryLimits {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(QueryLimitsExtension {
+            limits: *self,
+            connection_complexity: AtomicUsize::new(0),
+        })
+    }
+}
+
+struct QueryLimitsExtension {
+    limits: QueryLimits,
+    /// Complexity the schema misses because connections aren't list fields,
+    /// measured when the query is parsed.
+    connection_complexity: AtomicUsize,
+}
+
+/// The page size a field asks for, `Some(None)` for a connection left at
+/// its default page size, or `None` when the field isn't a connection.
+/// Connections are recognised by their `first`/`last` arguments or by
+/// selecting `edges`.
+fn page_size(field: &Field, variables: &Variables) -> Option<Option<i32>> {
+    let argument = field
+        .get_argument("first")
+        .or_else(|| field.get_argument("last"));
+
+    match argument {
+        Some(value) => Some(
+            match value
+                .node
+                .clone()
+                .into_const_with(|name| variables.get(&name).cloned().ok_or(()))
+            {
+                Ok(Value::Number(number)) => number.as_i64().map(|size| size as i32),
+                _ => None,
+            },
+        ),
+        None if selects_edges(&field.selection_set.node) => Some(None),
+        None => None,
+    }
+}
+
+fn selects_edges(selection_set: &SelectionSet) -> bool {
+    selection_set.items.iter().any(|item| match &item.node {
+        Selection::Field(field) => field.node.name.node == "edges",
+        _ => false,
+    })
+}
+
+#[derive(Default)]
+struct Costs {
+    /// Counted the way the schema counts a connection: once.
+    schema: usize,
+    /// Counted once per item on the requested page.
+    paged: usize,
+}
+
+struct Walk<'a> {
+    document: &'a ExecutableDocument,
+    variables: &'a Variables,
+    max_depth: usize,
+}
+
+impl Walk<'_> {
+    /// Stops below `max_depth`, which also keeps fragment cycles from
+    /// recursing forever. Such queries fail validation regardless.
+    fn costs(&self, selection_set: &SelectionSet, depth: usize) -> Costs {
+        let mut costs = Costs::default();
+        if depth > self.max_depth {
+            return costs;
+        }
+
+        for item in &selection_set.items {
+            let nested = match &item.node {
+                Selection::Field(field) => {
+                    let children = self.costs(&field.node.selection_set.node, depth + 1);
+                    let paged = match page_size(&field.node, self.variables) {
+                        Some(size) => list_complexity(size, DEFAULT_PAGE_SIZE, children.paged),
+                        None => 1 + children.paged,
+                    };
+
+                    Costs {
+                        schema: 1 + children.schema,
+                        paged,
+                    }
+                }
+                Selection::FragmentSpread(spread) => {
+                    match self.document.fragments.get(&spread.node.fragment_name.node) {
+                        Some(fragment) => self.costs(&fragment.node.selection_set.node, depth + 1),
+                        None => Costs::default(),
+                    }
+                }
+                Selection::InlineFragment(fragment) => {
+                    self.costs(&fragment.node.selection_set.node, depth + 1)
+                }
+            };
+
+            costs.schema += nested.schema;
+            costs.paged += nested.paged;
+        }
+
+        costs
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for QueryLimitsExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+
+        let walk = Walk {
+            document: &document,
+            variables,
+            max_depth: self.limits.max_depth,
+        };
+        let extra = document
+            .operations
+            .iter()
+            .map(|(_, operation)| {
+                let costs = walk.costs(&operation.node.selection_set.node, 0);
+                costs.paged.saturating_sub(costs.schema)
+            })
+            .max()
+            .unwrap_or(0);
+        self.connection_complexity.store(extra, Ordering::Relaxed);
+
+        Ok(document)
+    }
+
+    async fn validation(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        next: NextValidation<'_>,
+    ) -> Result<ValidationResult, Vec<ServerError>> {
+        let mut result = next.run(ctx).await?;
+        result.complexity += self.connection_complexity.load(Ordering::Relaxed);
+        let limits = self.limits;
+
+        if result.depth > limits.max_depth {
+            return Err(vec![limit_error(
+                "QUERY_TOO_DEEP",
+                format!(
+                    "Query depth {} exceeds the maximum depth of {}",
+                    result.depth, limits.max_depth
+                ),
+            )]);
+        }
+
+        if result.complexity > limits.max_complexity {
+            return Err(vec![limit_error(
+                "QUERY_TOO_COMPLEX",
+                format!(
+                    "Query complexity {} exceeds the maximum complexity of {}",
+                    result.complexity, limits.max_complexity
+                ),
+            )]);
+        }
+
+        Ok(result)
+    }
+}
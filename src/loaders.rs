@@ -0,0 +1,454 @@
+use async_graphql::dataloader::Loader;
+use async_graphql::futures_util::TryStreamExt;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, Pool, Postgres, Row};
+use std::collections::HashMap;
+
+use crate::error::AppError;
+use crate::models::{
+    Equipment, Exercise, Muscle, MuscleGroup, Routine, RoutineExercise, Set, User, WorkoutSet,
+};
+use crate::trace::timed;
+
+#[derive(sqlx::FromRow)]
+struct RoutineExerciseRow {
+    routine_id: i32,
+    position: i32,
+    exercise_id: i32,
+    name: String,
+    main_muscle_worked_id: i32,
+    muscle_group: MuscleGroup,
+    equipment: Equipment,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+pub struct ExerciseLoader(Pool<Postgres>);
+
+impl ExerciseLoader {
+    pub(crate) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<i32> for ExerciseLoader {
+    type Value = Exercise;
+    type Error = AppError;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let query = "SELECT id, name, main_muscle_worked_id, muscle_group, equipment, created_at, updated_at FROM exercises WHERE id IN (SELECT * FROM UNNEST($1))";
+        let exercises = timed(
+            "ExerciseLoader",
+            sqlx::query_as(query)
+                .bind(keys)
+                .fetch(&self.0)
+                .map_ok(|exercise: Exercise| (exercise.id, exercise))
+                .try_collect(),
+        )
+        .await?;
+
+        Ok(exercises)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ExerciseAlias {
+    exercise_id: i32,
+    alias: String,
+}
+
+/// Loads each exercise's aliases in alphabetical order.
+pub struct ExerciseAliasesLoader(Pool<Postgres>);
+
+impl ExerciseAliasesLoader {
+    pub(crate) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<i32> for ExerciseAliasesLoader {
+    type Value = Vec<String>;
+    type Error = AppError;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let query = r#"
+SELECT exercise_id, alias
+FROM exercise_aliases
+WHERE exercise_id = ANY($1)
+ORDER BY lower(alias)
+        "#;
+        let rows: Vec<ExerciseAlias> = timed(
+            "ExerciseAliasesLoader",
+            sqlx::query_as(query)
+                .bind(keys)
+                .fetch(&self.0)
+                .try_collect(),
+        )
+        .await?;
+
+        let mut aliases: HashMap<i32, Self::Value> =
+            keys.iter().map(|key| (*key, Vec::new())).collect();
+
+        for row in rows {
+            aliases.entry(row.exercise_id).or_default().push(row.alias);
+        }
+
+        Ok(aliases)
+    }
+}
+
+pub struct RoutineLoader(Pool<Postgres>);
+
+impl RoutineLoader {
+    pub(crate) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+impl RoutineLoader {
+    async fn load_routines(
+        &self,
+        ids: Vec<i32>,
+        include_deleted: bool,
+    ) -> Result<Vec<Routine>, AppError> {
+        let query = r#"
+SELECT id, name, user_id, created_at, updated_at, archived_at, deleted_at, version
+FROM routines
+WHERE id IN (SELECT * FROM UNNEST($1)) AND ($2 OR deleted_at IS NULL)
+        "#;
+        let routines = timed(
+            "RoutineLoader",
+            sqlx::query_as(query)
+                .bind(ids)
+                .bind(include_deleted)
+                .fetch(&self.0)
+                .try_collect(),
+        )
+        .await?;
+
+        Ok(routines)
+    }
+}
+
+/// Loads routines by id, leaving out deleted ones.
+#[async_trait]
+impl Loader<i32> for RoutineLoader {
+    type Value = Routine;
+    type Error = AppError;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let routines = self.load_routines(keys.to_vec(), false).await?;
+
+        Ok(routines
+            .into_iter()
+            .map(|routine| (routine.id, routine))
+            .collect())
+    }
+}
+
+/// A routine id to load whether or not the routine has been deleted.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IncludingDeleted(pub i32);
+
+#[async_trait]
+impl Loader<IncludingDeleted> for RoutineLoader {
+    type Value = Routine;
+    type Error = AppError;
+
+    async fn load(
+        &self,
+        keys: &[IncludingDeleted],
+    ) -> Result<HashMap<IncludingDeleted, Self::Value>, Self::Error> {
+        let ids = keys.iter().map(|key| key.0).collect();
+        let routines = self.load_routines(ids, true).await?;
+
+        Ok(routines
+            .into_iter()
+            .map(|routine| (IncludingDeleted(routine.id), routine))
+            .collect())
+    }
+}
+
+/// Loads the routines each exercise is part of, by exercise id, leaving out
+/// deleted routines.
+pub struct ExerciseRoutinesLoader(Pool<Postgres>);
+
+impl ExerciseRoutinesLoader {
+    pub(crate) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+struct ExerciseRoutineRow {
+    exercise_id: i32,
+    routine: Routine,
+}
+
+impl<'r> FromRow<'r, PgRow> for ExerciseRoutineRow {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            exercise_id: row.try_get("exercise_id")?,
+            routine: Routine::from_row(row)?,
+        })
+    }
+}
+
+#[async_trait]
+impl Loader<i32> for ExerciseRoutinesLoader {
+    type Value = Vec<Routine>;
+    type Error = AppError;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let query = r#"
+SELECT routine_exercises.exercise_id, routines.id, routines.name, routines.user_id,
+    routines.created_at, routines.updated_at, routines.archived_at, routines.deleted_at,
+    routines.version
+FROM routine_exercises
+INNER JOIN routines ON routines.id = routine_exercises.routine_id
+WHERE routine_exercises.exercise_id = ANY($1) AND routines.deleted_at IS NULL
+ORDER BY routines.id
+        "#;
+        let rows: Vec<ExerciseRoutineRow> = timed(
+            "ExerciseRoutinesLoader",
+            sqlx::query_as(query)
+                .bind(keys)
+                .fetch(&self.0)
+                .try_collect(),
+        )
+        .await?;
+
+        let mut routines: HashMap<i32, Self::Value> =
+            keys.iter().map(|key| (*key, Vec::new())).collect();
+
+        for row in rows {
+            routines
+                .entry(row.exercise_id)
+                .or_default()
+                .push(row.routine);
+        }
+
+        Ok(routines)
+    }
+}
+
+pub struct RoutineExercisesLoader(Pool<Postgres>);
+
+impl RoutineExercisesLoader {
+    pub(crate) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<i32> for RoutineExercisesLoader {
+    type Value = Vec<RoutineExercise>;
+    type Error = AppError;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let query = r#"
+SELECT routine_exercises.routine_id, routine_exercises.position, exercises.id AS exercise_id, exercises.name,
+    exercises.main_muscle_worked_id, exercises.muscle_group, exercises.equipment,
+    exercises.created_at, exercises.updated_at
+FROM routine_exercises
+INNER JOIN exercises ON exercises.id = routine_exercises.exercise_id
+WHERE routine_exercises.routine_id = ANY($1)
+ORDER BY routine_exercises.position, routine_exercises.exercise_id
+        "#;
+        let rows: Vec<RoutineExerciseRow> = timed(
+            "RoutineExercisesLoader",
+            sqlx::query_as(query)
+                .bind(keys)
+                .fetch(&self.0)
+                .try_collect(),
+        )
+        .await?;
+
+        let mut exercises: HashMap<i32, Self::Value> =
+            keys.iter().map(|key| (*key, Vec::new())).collect();
+
+        for row in rows {
+            exercises
+                .entry(row.routine_id)
+                .or_default()
+                .push(RoutineExercise {
+                    routine_id: row.routine_id,
+                    position: row.position,
+                    exercise: Exercise {
+                        id: row.exercise_id,
+                        name: row.name,
+                        main_muscle_worked_id: row.main_muscle_worked_id,
+                        muscle_group: row.muscle_group,
+                        equipment: row.equipment,
+                        created_at: row.created_at,
+                        updated_at: row.updated_at,
+                    },
+                });
+        }
+
+        Ok(exercises)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct RoutineExerciseSetRow {
+    routine_id: i32,
+    exercise_id: i32,
+    set_number: i32,
+    reps: i32,
+    weight_kg: Option<f64>,
+}
+
+/// Loads the sets prescribed for `(routine_id, exercise_id)` pairs.
+pub struct RoutineExerciseSetsLoader(Pool<Postgres>);
+
+impl RoutineExerciseSetsLoader {
+    pub(crate) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<(i32, i32)> for RoutineExerciseSetsLoader {
+    type Value = Vec<WorkoutSet>;
+    type Error = AppError;
+
+    async fn load(
+        &self,
+        keys: &[(i32, i32)],
+    ) -> Result<HashMap<(i32, i32), Self::Value>, Self::Error> {
+        let (routine_ids, exercise_ids): (Vec<i32>, Vec<i32>) = keys.iter().copied().unzip();
+        let query = r#"
+SELECT routine_id, exercise_id, set_number, reps, weight_kg
+FROM routine_exercise_sets
+WHERE (routine_id, exercise_id) IN (SELECT * FROM UNNEST($1::INT[], $2::INT[]))
+ORDER BY set_number
+        "#;
+        let rows: Vec<RoutineExerciseSetRow> = timed(
+            "RoutineExerciseSetsLoader",
+            sqlx::query_as(query)
+                .bind(routine_ids)
+                .bind(exercise_ids)
+                .fetch(&self.0)
+                .try_collect(),
+        )
+        .await?;
+
+        let mut sets: HashMap<(i32, i32), Self::Value> =
+            keys.iter().map(|key| (*key, Vec::new())).collect();
+
+        for row in rows {
+            sets.entry((row.routine_id, row.exercise_id))
+                .or_default()
+                .push(WorkoutSet {
+                    set_number: row.set_number,
+                    reps: row.reps,
+                    weight_kg: row.weight_kg,
+                });
+        }
+
+        Ok(sets)
+    }
+}
+
+pub struct WorkoutSetsLoader(Pool<Postgres>);
+
+impl WorkoutSetsLoader {
+    pub(crate) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<i32> for WorkoutSetsLoader {
+    type Value = Vec<Set>;
+    type Error = AppError;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let query = r#"
+SELECT id, workout_id, exercise_id, reps, weight_kg, position, notes, rest_seconds
+FROM sets
+WHERE workout_id = ANY($1)
+ORDER BY position, id
+        "#;
+        let rows: Vec<Set> = timed(
+            "WorkoutSetsLoader",
+            sqlx::query_as(query)
+                .bind(keys)
+                .fetch(&self.0)
+                .try_collect(),
+        )
+        .await?;
+
+        let mut sets: HashMap<i32, Self::Value> =
+            keys.iter().map(|key| (*key, Vec::new())).collect();
+
+        for set in rows {
+            sets.entry(set.workout_id).or_default().push(set);
+        }
+
+        Ok(sets)
+    }
+}
+
+pub struct UserLoader(Pool<Postgres>);
+
+impl UserLoader {
+    pub(crate) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<i32> for UserLoader {
+    type Value = User;
+    type Error = AppError;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let query =
+            "SELECT id, email, created_at, role FROM users WHERE id IN (SELECT * FROM UNNEST($1))";
+        let users = timed(
+            "UserLoader",
+            sqlx::query_as(query)
+                .bind(keys)
+                .fetch(&self.0)
+                .map_ok(|user: User| (user.id, user))
+                .try_collect(),
+        )
+        .await?;
+
+        Ok(users)
+    }
+}
+
+pub struct MuscleLoader(Pool<Postgres>);
+
+impl MuscleLoader {
+    pub(crate) fn new(postgres_pool: Pool<Postgres>) -> Self {
+        Self(postgres_pool)
+    }
+}
+
+#[async_trait]
+impl Loader<i32> for MuscleLoader {
+    type Value = Muscle;
+    type Error = AppError;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
+        let query = "SELECT id, name FROM muscles WHERE id IN (SELECT * FROM UNNEST($1))";
+        let exercise = timed(
+            "MuscleLoader",
+            sqlx::query_as(query)
+                .bind(keys)
+                .fetch(&self.0)
+                .map_ok(|muscle: Muscle| (muscle.id, muscle))
+                .try_collect(),
+        )
+        .await?;
+
+        Ok(exercise)
+    }
+}
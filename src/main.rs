@@ -1,217 +1,38 @@
-use async_graphql::dataloader::{DataLoader, Loader};
-use async_graphql::futures_util::TryStreamExt;
-use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
-use async_graphql::{Context, EmptySubscription, FieldError, Object, Result, Schema, SimpleObject};
 use async_std::task;
-use async_trait::async_trait;
-use sqlx::{Pool, Postgres};
-use std::collections::HashMap;
-use std::env;
-use tide::{http::mime, Body, Response, StatusCode};
+use fit::Config;
+use std::{env, process};
 
-#[derive(sqlx::FromRow, Clone)]
-pub struct Exercise {
-    id: i32,
-    name: String,
-    main_muscle_worked_id: i32,
-}
-
-#[derive(sqlx::FromRow, Clone, SimpleObject)]
-pub struct Muscle {
-    id: i32,
-    name: String,
-}
-
-#[derive(sqlx::FromRow, Clone, SimpleObject)]
-pub struct Routine {
-    id: i32,
-    name: String,
-}
-
-pub struct RoutineLoader(Pool<Postgres>);
-
-impl RoutineLoader {
-    fn new(postgres_pool: Pool<Postgres>) -> Self {
-        Self(postgres_pool)
-    }
-}
-
-#[async_trait]
-impl Loader<i32> for RoutineLoader {
-    type Value = Routine;
-    type Error = FieldError;
-
-    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
-        let query = "SELECT id, name FROM routines WHERE id IN (SELECT * FROM UNNEST($1))";
-        let exercise = sqlx::query_as(&query)
-            .bind(keys)
-            .fetch(&self.0)
-            .map_ok(|routine: Routine| (routine.id, routine))
-            .try_collect()
-            .await?;
-
-        Ok(exercise)
-    }
-}
-
-pub struct MuscleLoader(Pool<Postgres>);
-
-impl MuscleLoader {
-    fn new(postgres_pool: Pool<Postgres>) -> Self {
-        Self(postgres_pool)
-    }
-}
-
-#[async_trait]
-impl Loader<i32> for MuscleLoader {
-    type Value = Muscle;
-    type Error = FieldError;
-
-    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error> {
-        let query = "SELECT id, name FROM muscles WHERE id IN (SELECT * FROM UNNEST($1))";
-        let exercise = sqlx::query_as(&query)
-            .bind(keys)
-            .fetch(&self.0)
-            .map_ok(|muscle: Muscle| (muscle.id, muscle))
-            .try_collect()
-            .await?;
-
-        Ok(exercise)
-    }
-}
-
-#[Object]
-impl Exercise {
-    async fn id(&self) -> i32 {
-        self.id
-    }
-
-    async fn name(&self) -> String {
-        self.name.to_owned()
-    }
-
-    async fn main_muscle_worked(&self, ctx: &Context<'_>) -> Result<Option<Muscle>> {
-        let muscle = ctx
-            .data_unchecked::<DataLoader<MuscleLoader>>()
-            .load_one(self.main_muscle_worked_id)
-            .await?;
-
-        Ok(muscle)
-    }
-}
+const USAGE: &str = "usage: fit [--migrate | migrate]";
 
-struct QueryRoot;
+fn main() {
+    fit::init_tracing();
 
-#[Object]
-impl QueryRoot {
-    async fn exercises(&self, ctx: &Context<'_>) -> Result<Vec<Exercise>> {
-        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
-
-        let exercises = sqlx::query_as!(
-            Exercise,
-            "SELECT id, name, main_muscle_worked_id FROM exercises"
-        )
-        .fetch(pool)
-        .try_collect()
-        .await?;
-
-        Ok(exercises)
-    }
-
-    async fn routine(&self, ctx: &Context<'_>, id: i32) -> Result<Option<Routine>> {
-        let routine = ctx
-            .data_unchecked::<DataLoader<RoutineLoader>>()
-            .load_one(id)
-            .await?;
-
-        Ok(routine)
-    }
-
-    async fn routines(&self, ctx: &Context<'_>) -> Result<Vec<Routine>> {
-        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
-
-        let routines = sqlx::query_as!(Routine, "SELECT id, name FROM routines")
-            .fetch(pool)
-            .try_collect()
-            .await?;
-
-        Ok(routines)
-    }
-}
-
-struct MutationRoot;
-
-#[Object]
-impl MutationRoot {
-    async fn create_exercise(
-        &self,
-        ctx: &Context<'_>,
-        name: String,
-        main_muscle_worked_id: i32,
-    ) -> Result<Exercise> {
-        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
-
-        let exercise = sqlx::query_as!(
-            Exercise,
-            r#"
-INSERT INTO exercises (name, main_muscle_worked_id)
-VALUES ( $1, $2 )
-RETURNING id, name, main_muscle_worked_id
-            "#,
-            name,
-            main_muscle_worked_id
-        )
-        .fetch_one(pool)
-        .await?;
-
-        Ok(exercise)
-    }
-
-    async fn create_routine(&self, ctx: &Context<'_>, name: String) -> Result<Routine> {
-        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
-
-        let routine = sqlx::query_as!(
-            Routine,
-            "INSERT INTO routines (name) VALUES ( $1 ) RETURNING id, name",
-            name
-        )
-        .fetch_one(pool)
-        .await?;
-
-        Ok(routine)
-    }
-}
-
-fn main() -> Result<()> {
-    task::block_on(run())
-}
-
-async fn run() -> Result<()> {
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
-    let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await?;
-
-    let schema = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
-        .data(DataLoader::new(MuscleLoader::new(postgres_pool.clone())))
-        .data(DataLoader::new(RoutineLoader::new(postgres_pool.clone())))
-        .data(postgres_pool.clone())
-        .finish();
-
-    let mut app = tide::new();
-
-    app.at("/graphql")
-        .post(async_graphql_tide::endpoint(schema));
-
-    app.at("/").get(|_| async move {
-        let mut resp = Response::new(StatusCode::Ok);
-        resp.set_body(Body::from_string(playground_source(
-            GraphQLPlaygroundConfig::new("/graphql"),
-        )));
-        resp.set_content_type(mime::HTML);
-        Ok(resp)
+    let mut config = Config::from_env().unwrap_or_else(|error| {
+        eprintln!("Invalid configuration: {}", error);
+        process::exit(1);
     });
 
-    println!("Playground: http://127.0.0.1:8000");
-    app.listen("127.0.0.1:8000").await?;
-
-    Ok(())
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] => task::block_on(fit::run(config)),
+        ["--migrate"] => {
+            config.run_migrations = true;
+            task::block_on(fit::run(config))
+        }
+        ["migrate"] => task::block_on(fit::run_migrations(&config)),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(error) = result {
+        eprintln!("{}", error.message);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,149 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::{Pool, Postgres};
+use std::time::{Duration, Instant};
+use tide::{Middleware, Next, Request, Response, StatusCode};
+
+/// The Prometheus metrics served at `/metrics`. Each app gets its own
+/// registry; clones share it.
+#[derive(Clone)]
+pub(crate) struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    graphql_requests: IntCounter,
+    resolver_duration: HistogramVec,
+    resolver_errors: IntCounterVec,
+    pool_size: IntGauge,
+    pool_idle: IntGauge,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Self {
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to handle an HTTP request",
+            ),
+            &["method"],
+        )
+        .unwrap();
+        let graphql_requests = IntCounter::new(
+            "graphql_requests_total",
+            "GraphQL operations received, counting each operation in a batch",
+        )
+        .unwrap();
+        let resolver_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "graphql_resolver_duration_seconds",
+                "Time taken to resolve a root query or mutation field",
+            ),
+            &["operation"],
+        )
+        .unwrap();
+        let resolver_errors = IntCounterVec::new(
+            Opts::new(
+                "graphql_resolver_errors_total",
+                "Root query or mutation fields that resolved to an error",
+            ),
+            &["operation"],
+        )
+        .unwrap();
+        let pool_size = IntGauge::new(
+            "db_pool_connections",
+            "Connections currently open in the Postgres pool",
+        )
+        .unwrap();
+        let pool_idle = IntGauge::new(
+            "db_pool_idle_connections",
+            "Idle connections in the Postgres pool",
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(graphql_requests.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(resolver_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(resolver_errors.clone()))
+            .unwrap();
+        registry.register(Box::new(pool_size.clone())).unwrap();
+        registry.register(Box::new(pool_idle.clone())).unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            graphql_requests,
+            resolver_duration,
+            resolver_errors,
+            pool_size,
+            pool_idle,
+        }
+    }
+
+    pub(crate) fn record_graphql_requests(&self, operations: usize) {
+        self.graphql_requests.inc_by(operations as u64);
+    }
+
+    pub(crate) fn record_resolver(&self, operation: &str, elapsed: Duration, ok: bool) {
+        self.resolver_duration
+            .with_label_values(&[operation])
+            .observe(elapsed.as_secs_f64());
+        if !ok {
+            self.resolver_errors.with_label_values(&[operation]).inc();
+        }
+    }
+
+    /// Renders every metric in the Prometheus text format, reading the pool
+    /// gauges at scrape time.
+    fn render(&self, postgres_pool: &Pool<Postgres>) -> String {
+        self.pool_size.set(postgres_pool.size() as i64);
+        self.pool_idle.set(postgres_pool.num_idle() as i64);
+
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("encoding metrics failed");
+        String::from_utf8(buffer).expect("metrics are not valid UTF-8")
+    }
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for Metrics {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let method = req.method().to_string();
+        let started = Instant::now();
+        let response = next.run(req).await;
+
+        self.http_request_duration
+            .with_label_values(&[&method])
+            .observe(started.elapsed().as_secs_f64());
+        self.http_requests
+            .with_label_values(&[&method, response.status().to_string().as_str()])
+            .inc();
+
+        Ok(response)
+    }
+}
+
+pub(crate) fn metrics_endpoint(metrics: &Metrics, postgres_pool: &Pool<Postgres>) -> Response {
+    let mut resp = Response::new(StatusCode::Ok);
+    resp.set_body(metrics.render(postgres_pool));
+    resp.set_content_type(TextEncoder::new().format_type());
+    resp
+}
@@ -0,0 +1,89 @@
+use sqlx::migrate::{Migrate, MigrateError, Migration, Migrator};
+use sqlx::{Pool, Postgres};
+use std::fmt;
+
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// A migration failed to apply, or an applied migration no longer matches
+/// the embedded one.
+#[derive(Debug)]
+pub struct MigrationError {
+    /// `<version> <description>` of the migration that failed, when the
+    /// failure can be attributed to one.
+    pub migration: Option<String>,
+    pub error: MigrateError,
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.migration {
+            Some(migration) => write!(f, "migration {} failed: {}", migration, self.error),
+            None => write!(f, "migrations failed: {}", self.error),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<MigrateError> for MigrationError {
+    fn from(error: MigrateError) -> Self {
+        Self {
+            migration: None,
+            error,
+        }
+    }
+}
+
+impl From<sqlx::Error> for MigrationError {
+    fn from(error: sqlx::Error) -> Self {
+        MigrateError::from(error).into()
+    }
+}
+
+/// Applies every pending migration embedded from `migrations/`, in version
+/// order, and checks that previously applied ones are unchanged.
+///
+/// This mirrors `Migrator::run`, which in sqlx 0.4 would also run the
+/// `.down.sql` half of each reversible migration, and reports which migration
+/// failed.
+pub async fn migrate(postgres_pool: &Pool<Postgres>) -> Result<(), MigrationError> {
+    let mut conn = postgres_pool.acquire().await?;
+
+    conn.lock().await?;
+    conn.ensure_migrations_table().await?;
+
+    let (version, dirty) = conn.version().await?.unwrap_or((0, false));
+    if dirty {
+        return Err(MigrateError::Dirty(version).into());
+    }
+
+    let migrations = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration());
+
+    for migration in migrations {
+        let result = if migration.version > version {
+            tracing::info!(
+                "applying migration {} {}",
+                migration.version,
+                migration.description
+            );
+            conn.apply(migration).await.map(|_| ())
+        } else {
+            conn.validate(migration).await
+        };
+
+        result.map_err(|error| MigrationError {
+            migration: Some(describe(migration)),
+            error,
+        })?;
+    }
+
+    conn.unlock().await?;
+
+    Ok(())
+}
+
+fn describe(migration: &Migration) -> String {
+    format!("{} {}", migration.version, migration.description)
+}
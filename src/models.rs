@@ -0,0 +1,760 @@
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{
+    ComplexObject, Context, Enum, InputObject, Object, Result, SimpleObject, Union,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::duration::Seconds;
+use crate::error::{invalid_field, AppError};
+use crate::graphql::{
+    list_complexity, list_routines, list_workouts, routines_page_size, viewer_id,
+    visible_to_viewer, RoutineOrderBy, MAX_RECENT_WORKOUTS, UNPAGINATED_LIST_COMPLEXITY,
+};
+use crate::loaders::{
+    ExerciseAliasesLoader, ExerciseLoader, ExerciseRoutinesLoader, IncludingDeleted, MuscleLoader,
+    RoutineExerciseSetsLoader, RoutineExercisesLoader, RoutineLoader, UserLoader,
+    WorkoutSetsLoader,
+};
+use crate::units::WeightUnit;
+
+/// The broad area of the body an exercise trains.
+#[derive(Enum, sqlx::Type, Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[sqlx(rename = "muscle_group", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum MuscleGroup {
+    Chest,
+    Back,
+    Legs,
+    Shoulders,
+    Arms,
+    Core,
+    FullBody,
+    Other,
+}
+
+impl MuscleGroup {
+    /// The label of the Postgres `muscle_group` enum value.
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            MuscleGroup::Chest => "chest",
+            MuscleGroup::Back => "back",
+            MuscleGroup::Legs => "legs",
+            MuscleGroup::Shoulders => "shoulders",
+            MuscleGroup::Arms => "arms",
+            MuscleGroup::Core => "core",
+            MuscleGroup::FullBody => "full_body",
+            MuscleGroup::Other => "other",
+        }
+    }
+}
+
+/// What a user is allowed to do. Admins can also manage the shared exercise
+/// library and see every account.
+#[derive(Enum, sqlx::Type, Copy, Clone, Debug, Eq, PartialEq)]
+#[sqlx(rename = "user_role", rename_all = "snake_case")]
+pub enum Role {
+    User,
+    Admin,
+}
+
+impl Role {
+    /// Whether this role may do what `required` may.
+    pub(crate) fn grants(self, required: Role) -> bool {
+        self == Role::Admin || self == required
+    }
+}
+
+/// What an exercise is performed with.
+#[derive(Enum, sqlx::Type, Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[sqlx(rename = "equipment", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Equipment {
+    Barbell,
+    Dumbbell,
+    Machine,
+    Cable,
+    Bodyweight,
+    Kettlebell,
+    Band,
+    Other,
+}
+
+impl Equipment {
+    /// The label of the Postgres `equipment` enum value.
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Equipment::Barbell => "barbell",
+            Equipment::Dumbbell => "dumbbell",
+            Equipment::Machine => "machine",
+            Equipment::Cable => "cable",
+            Equipment::Bodyweight => "bodyweight",
+            Equipment::Kettlebell => "kettlebell",
+            Equipment::Band => "band",
+            Equipment::Other => "other",
+        }
+    }
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct Exercise {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    pub(crate) main_muscle_worked_id: i32,
+    pub(crate) muscle_group: MuscleGroup,
+    pub(crate) equipment: Equipment,
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) updated_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow, Clone, SimpleObject)]
+pub struct Muscle {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct Routine {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    pub(crate) user_id: Option<i32>,
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) updated_at: DateTime<Utc>,
+    pub(crate) archived_at: Option<DateTime<Utc>>,
+    pub(crate) deleted_at: Option<DateTime<Utc>>,
+    pub(crate) version: i32,
+}
+
+/// A `search` hit.
+#[derive(Clone, Union)]
+pub enum SearchResult {
+    Exercise(Exercise),
+    Routine(Routine),
+}
+
+/// An exercise as it appears in a routine, with the sets prescribed for it.
+#[derive(Clone)]
+pub struct RoutineExercise {
+    pub(crate) routine_id: i32,
+    pub(crate) position: i32,
+    pub(crate) exercise: Exercise,
+}
+
+/// A set prescribed for an exercise in a routine. `weight_kg` is null for
+/// bodyweight exercises.
+#[derive(Clone, SimpleObject)]
+pub struct WorkoutSet {
+    pub(crate) set_number: i32,
+    pub(crate) reps: i32,
+    pub(crate) weight_kg: Option<f64>,
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct User {
+    pub(crate) id: i32,
+    pub(crate) email: String,
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) role: Role,
+}
+
+/// A signed-in user and the token to send as `Authorization: Bearer <token>`
+/// on later requests.
+#[derive(Clone, SimpleObject)]
+pub struct AuthPayload {
+    pub(crate) token: String,
+    pub(crate) user: User,
+}
+
+/// A key that signs requests in as its owner without a JWT, for scripts and
+/// integrations. The key itself is only shown when it is created.
+#[derive(sqlx::FromRow, Clone, SimpleObject)]
+pub struct ApiKey {
+    pub(crate) id: i32,
+    pub(crate) name: String,
+    pub(crate) created_at: DateTime<Utc>,
+    /// When the key was last used, to within a few minutes.
+    pub(crate) last_used_at: Option<DateTime<Utc>>,
+    pub(crate) revoked_at: Option<DateTime<Utc>>,
+}
+
+/// A new API key. `key` is sent as `Authorization: ApiKey <key>` or
+/// `X-Api-Key: <key>`, and can't be read again.
+#[derive(Clone, SimpleObject)]
+pub struct CreateApiKeyPayload {
+    pub(crate) key: String,
+    pub(crate) api_key: ApiKey,
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct Workout {
+    pub(crate) id: i32,
+    pub(crate) routine_id: Option<i32>,
+    pub(crate) performed_at: DateTime<Utc>,
+    pub(crate) notes: Option<String>,
+    pub(crate) distance_m: Option<f64>,
+    pub(crate) duration_s: Option<f64>,
+    pub(crate) avg_heart_rate: Option<i32>,
+    pub(crate) started_at: Option<DateTime<Utc>>,
+    pub(crate) finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(sqlx::FromRow, Clone)]
+pub struct Set {
+    pub(crate) id: i32,
+    pub(crate) workout_id: i32,
+    pub(crate) exercise_id: i32,
+    pub(crate) reps: i32,
+    pub(crate) weight_kg: Option<f64>,
+    pub(crate) position: i32,
+    pub(crate) notes: Option<String>,
+    pub(crate) rest_seconds: Option<i32>,
+}
+
+/// Where an `exerciseHistory` set was performed.
+#[derive(Clone, SimpleObject)]
+pub struct SetHistoryFields {
+    pub(crate) workout_id: i32,
+    pub(crate) performed_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow, Clone, SimpleObject)]
+#[graphql(complex)]
+pub struct BodyWeightEntry {
+    pub(crate) id: i32,
+    pub(crate) measured_at: DateTime<Utc>,
+    pub(crate) weight_kg: f64,
+    pub(crate) notes: Option<String>,
+}
+
+/// A day with at least one workout.
+#[derive(Clone, SimpleObject)]
+pub struct TrainingDay {
+    pub(crate) date: NaiveDate,
+    pub(crate) workout_count: i64,
+    pub(crate) set_count: i64,
+}
+
+/// Runs of consecutive days with at least one workout.
+#[derive(Clone, SimpleObject)]
+pub struct TrainingStreaks {
+    /// The length of the run ending on the day the streak is measured as of,
+    /// or the day before: a streak isn't broken until a whole day passes
+    /// without training. 0 otherwise.
+    pub(crate) current_streak: i64,
+    pub(crate) longest_streak: i64,
+}
+
+/// How `volumeStats` buckets sets.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum VolumeGroupBy {
+    Week,
+    MuscleGroupPerWeek,
+}
+
+/// The training volume of the sets performed in a week, for one muscle group
+/// when grouped by muscle group.
+#[derive(Clone, SimpleObject)]
+pub struct VolumeBucket {
+    /// Midnight UTC on the Monday the week starts on.
+    pub(crate) week_start: DateTime<Utc>,
+    pub(crate) muscle_group: Option<MuscleGroup>,
+    /// The sum of reps × weight. Sets without a weight count as 0.
+    pub(crate) total_volume_kg: f64,
+    pub(crate) set_count: i64,
+}
+
+/// The heaviest set logged for an exercise.
+#[derive(Clone)]
+pub struct PersonalRecord {
+    pub(crate) exercise_id: i32,
+    pub(crate) max_weight_kg: f64,
+    pub(crate) reps_at_max_weight: i32,
+    pub(crate) workout: Workout,
+}
+
+#[derive(InputObject)]
+pub struct CreateExerciseInput {
+    pub(crate) name: String,
+    pub(crate) main_muscle_worked_id: i32,
+    #[graphql(default_with = "MuscleGroup::Other")]
+    pub(crate) muscle_group: MuscleGroup,
+    #[graphql(default_with = "Equipment::Other")]
+    pub(crate) equipment: Equipment,
+}
+
+#[derive(InputObject)]
+pub struct CreateRoutineInput {
+    pub(crate) name: String,
+    /// The routine's exercises, in order.
+    pub(crate) exercises: Vec<RoutineExerciseInput>,
+}
+
+/// An exercise in a new routine, prescribed as `targetSets` sets of
+/// `targetReps` reps each.
+#[derive(InputObject)]
+pub struct RoutineExerciseInput {
+    pub(crate) exercise_id: i32,
+    pub(crate) target_sets: i32,
+    pub(crate) target_reps: i32,
+}
+
+#[derive(InputObject)]
+pub struct SetInput {
+    pub(crate) exercise_id: i32,
+    pub(crate) reps: i32,
+    pub(crate) weight_kg: Option<f64>,
+    pub(crate) notes: Option<String>,
+    /// The rest taken before this set.
+    pub(crate) rest_seconds: Option<Seconds>,
+}
+
+#[derive(InputObject)]
+pub struct WorkoutSetInput {
+    pub(crate) reps: i32,
+    pub(crate) weight_kg: Option<f64>,
+}
+
+#[Object]
+impl Exercise {
+    async fn id(&self) -> i32 {
+        self.id
+    }
+
+    async fn name(&self) -> String {
+        self.name.to_owned()
+    }
+
+    async fn muscle_group(&self) -> MuscleGroup {
+        self.muscle_group
+    }
+
+    async fn equipment(&self) -> Equipment {
+        self.equipment
+    }
+
+    /// Alternate names the exercise is also known by, which `search`
+    /// matches too.
+    async fn aliases(&self, ctx: &Context<'_>) -> Result<Vec<String>, AppError> {
+        let aliases = ctx
+            .data_unchecked::<DataLoader<ExerciseAliasesLoader>>()
+            .load_one(self.id)
+            .await?;
+
+        Ok(aliases.unwrap_or_default())
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// When the row was last changed; equal to `createdAt` until then.
+    async fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    async fn main_muscle_worked(&self, ctx: &Context<'_>) -> Result<Option<Muscle>> {
+        let muscle = ctx
+            .data_unchecked::<DataLoader<MuscleLoader>>()
+            .load_one(self.main_muscle_worked_id)
+            .await?;
+
+        Ok(muscle)
+    }
+
+    /// The routines that include this exercise, archived ones too. Deleted
+    /// routines and other users' routines are left out.
+    #[graphql(complexity = "list_complexity(None, UNPAGINATED_LIST_COMPLEXITY, child_complexity)")]
+    async fn routines(&self, ctx: &Context<'_>) -> Result<Vec<Routine>, AppError> {
+        let routines = ctx
+            .data_unchecked::<DataLoader<ExerciseRoutinesLoader>>()
+            .load_one(self.id)
+            .await?;
+
+        Ok(routines
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|routine| visible_to_viewer(ctx, routine))
+            .collect())
+    }
+}
+
+#[Object]
+impl User {
+    async fn id(&self) -> i32 {
+        self.id
+    }
+
+    async fn email(&self) -> &str {
+        &self.email
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    async fn role(&self) -> Role {
+        self.role
+    }
+
+    /// The user's routines, filtered and ordered as `Query.routines` does.
+    /// Empty unless the user is the viewer.
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "list_complexity(Some(routines_page_size(limit)), 0, child_complexity)")]
+    async fn routines(
+        &self,
+        ctx: &Context<'_>,
+        name_contains: Option<String>,
+        #[graphql(default_with = "RoutineOrderBy::IdAsc")] order_by: RoutineOrderBy,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        #[graphql(default)] include_archived: bool,
+        #[graphql(default)] include_deleted: bool,
+    ) -> Result<Vec<Routine>, AppError> {
+        if !self.visible_to_viewer(ctx) {
+            return Ok(Vec::new());
+        }
+
+        list_routines(
+            ctx,
+            Some(self.id),
+            name_contains,
+            order_by,
+            limit,
+            offset,
+            include_archived,
+            include_deleted,
+        )
+        .await
+    }
+
+    /// The user's `limit` most recent workouts, newest first. Empty unless
+    /// the user is the viewer.
+    #[graphql(
+        complexity = "list_complexity(Some(limit), MAX_RECENT_WORKOUTS as usize, child_complexity)"
+    )]
+    async fn recent_workouts(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 10)] limit: i32,
+    ) -> Result<Vec<Workout>, AppError> {
+        if !(0..=MAX_RECENT_WORKOUTS).contains(&limit) {
+            return Err(invalid_field(
+                "limit",
+                format!("limit must be between 0 and {}", MAX_RECENT_WORKOUTS),
+            ));
+        }
+        if !self.visible_to_viewer(ctx) {
+            return Ok(Vec::new());
+        }
+
+        list_workouts(ctx, Some(self.id), None, Some(limit)).await
+    }
+
+    /// The user's API keys, revoked ones included, oldest first. Empty unless
+    /// the user is the signed-in viewer.
+    #[graphql(complexity = "list_complexity(None, UNPAGINATED_LIST_COMPLEXITY, child_complexity)")]
+    async fn api_keys(&self, ctx: &Context<'_>) -> Result<Vec<ApiKey>, AppError> {
+        if !self.visible_to_viewer(ctx) {
+            return Ok(Vec::new());
+        }
+
+        let pool = ctx.data_unchecked::<sqlx::Pool<sqlx::Postgres>>();
+        let api_keys = sqlx::query_as!(
+            ApiKey,
+            "SELECT id, name, created_at, last_used_at, revoked_at FROM api_keys WHERE user_id = $1 ORDER BY id",
+            self.id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(api_keys)
+    }
+}
+
+impl User {
+    fn visible_to_viewer(&self, ctx: &Context<'_>) -> bool {
+        viewer_id(ctx) == Some(self.id)
+    }
+}
+
+#[Object]
+impl Routine {
+    async fn id(&self) -> i32 {
+        self.id
+    }
+
+    async fn name(&self) -> String {
+        self.name.to_owned()
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// When the row was last changed; equal to `createdAt` until then.
+    async fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    /// Goes up by one each time the routine is changed. Pass it to
+    /// `updateRoutine` as `expectedVersion`.
+    async fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Archived routines are left out of `routines` unless
+    /// `includeArchived` is set, but can still be looked up by id.
+    async fn archived(&self) -> bool {
+        self.archived_at.is_some()
+    }
+
+    /// When the routine was deleted. Deleted routines are left out of
+    /// `routines` and `routine` unless `includeDeleted` is set, and can be
+    /// brought back with `restoreRoutine`.
+    async fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at
+    }
+
+    #[graphql(complexity = "list_complexity(None, UNPAGINATED_LIST_COMPLEXITY, child_complexity)")]
+    async fn exercises(&self, ctx: &Context<'_>) -> Result<Vec<Exercise>, AppError> {
+        let exercises = ctx
+            .data_unchecked::<DataLoader<RoutineExercisesLoader>>()
+            .load_one(self.id)
+            .await?;
+
+        Ok(exercises
+            .unwrap_or_default()
+            .into_iter()
+            .map(|routine_exercise| routine_exercise.exercise)
+            .collect())
+    }
+
+    /// The routine's exercises with their positions and prescribed sets.
+    #[graphql(complexity = "list_complexity(None, UNPAGINATED_LIST_COMPLEXITY, child_complexity)")]
+    async fn routine_exercises(&self, ctx: &Context<'_>) -> Result<Vec<RoutineExercise>, AppError> {
+        let routine_exercises = ctx
+            .data_unchecked::<DataLoader<RoutineExercisesLoader>>()
+            .load_one(self.id)
+            .await?;
+
+        Ok(routine_exercises.unwrap_or_default())
+    }
+
+    async fn owner(&self, ctx: &Context<'_>) -> Result<Option<User>, AppError> {
+        let owner = match self.user_id {
+            Some(user_id) => {
+                ctx.data_unchecked::<DataLoader<UserLoader>>()
+                    .load_one(user_id)
+                    .await?
+            }
+            None => None,
+        };
+
+        Ok(owner)
+    }
+}
+
+#[Object]
+impl RoutineExercise {
+    async fn position(&self) -> i32 {
+        self.position
+    }
+
+    async fn exercise(&self) -> Exercise {
+        self.exercise.clone()
+    }
+
+    #[graphql(complexity = "list_complexity(None, UNPAGINATED_LIST_COMPLEXITY, child_complexity)")]
+    async fn sets(&self, ctx: &Context<'_>) -> Result<Vec<WorkoutSet>, AppError> {
+        let sets = ctx
+            .data_unchecked::<DataLoader<RoutineExerciseSetsLoader>>()
+            .load_one((self.routine_id, self.exercise.id))
+            .await?;
+
+        Ok(sets.unwrap_or_default())
+    }
+}
+
+#[Object]
+impl Workout {
+    async fn id(&self) -> i32 {
+        self.id
+    }
+
+    async fn performed_at(&self) -> DateTime<Utc> {
+        self.performed_at
+    }
+
+    async fn notes(&self) -> Option<String> {
+        self.notes.to_owned()
+    }
+
+    async fn distance_meters(&self) -> Option<f64> {
+        self.distance_m
+    }
+
+    async fn duration_seconds(&self) -> Option<f64> {
+        self.duration_s
+    }
+
+    async fn average_heart_rate(&self) -> Option<i32> {
+        self.avg_heart_rate
+    }
+
+    async fn started_at(&self) -> Option<DateTime<Utc>> {
+        self.started_at
+    }
+
+    async fn finished_at(&self) -> Option<DateTime<Utc>> {
+        self.finished_at
+    }
+
+    /// The time from `startedAt` to `finishedAt`. Null unless both are set.
+    async fn duration(&self) -> Option<Seconds> {
+        let elapsed = self.finished_at? - self.started_at?;
+
+        Some(Seconds(elapsed.num_seconds()))
+    }
+
+    /// The routine that was followed, even if it has since been deleted.
+    /// Activities uploaded from `.fit` files have none, and someone else's
+    /// routine is null.
+    async fn routine(&self, ctx: &Context<'_>) -> Result<Option<Routine>, AppError> {
+        let routine = match self.routine_id {
+            Some(routine_id) => {
+                ctx.data_unchecked::<DataLoader<RoutineLoader>>()
+                    .load_one(IncludingDeleted(routine_id))
+                    .await?
+            }
+            None => None,
+        };
+
+        Ok(routine.filter(|routine| visible_to_viewer(ctx, routine)))
+    }
+
+    #[graphql(complexity = "list_complexity(None, UNPAGINATED_LIST_COMPLEXITY, child_complexity)")]
+    async fn sets(&self, ctx: &Context<'_>) -> Result<Vec<Set>, AppError> {
+        let sets = ctx
+            .data_unchecked::<DataLoader<WorkoutSetsLoader>>()
+            .load_one(self.id)
+            .await?;
+
+        Ok(sets.unwrap_or_default())
+    }
+}
+
+/// Sets of more reps than this say too little about a one-rep max to
+/// estimate it.
+pub(crate) const MAX_ONE_REP_MAX_REPS: i32 = 12;
+
+/// Estimates the weight that could be lifted for a single rep with the Epley
+/// formula, `weight × (1 + reps / 30)`. A single rep is its own max. Returns
+/// `None` without a weight or with fewer than 1 or more than
+/// `MAX_ONE_REP_MAX_REPS` reps.
+pub(crate) fn estimated_one_rep_max(reps: i32, weight_kg: Option<f64>) -> Option<f64> {
+    let weight_kg = weight_kg?;
+
+    match reps {
+        1 => Some(weight_kg),
+        2..=MAX_ONE_REP_MAX_REPS => Some(weight_kg * (1.0 + f64::from(reps) / 30.0)),
+        _ => None,
+    }
+}
+
+#[Object]
+impl Set {
+    async fn id(&self) -> i32 {
+        self.id
+    }
+
+    async fn reps(&self) -> i32 {
+        self.reps
+    }
+
+    async fn weight_kg(&self) -> Option<f64> {
+        self.weight_kg
+    }
+
+    /// `weightKg` in `unit`, rounded to two decimal places.
+    async fn weight(
+        &self,
+        #[graphql(default_with = "WeightUnit::Kg")] unit: WeightUnit,
+    ) -> Option<f64> {
+        self.weight_kg.map(|weight_kg| unit.convert_kg(weight_kg))
+    }
+
+    /// The Epley estimate of this set's one-rep max. Null for sets without a
+    /// weight or of more than 12 reps.
+    async fn estimated_one_rep_max_kg(&self) -> Option<f64> {
+        estimated_one_rep_max(self.reps, self.weight_kg)
+    }
+
+    async fn position(&self) -> i32 {
+        self.position
+    }
+
+    async fn notes(&self) -> Option<String> {
+        self.notes.to_owned()
+    }
+
+    /// The rest taken before this set.
+    async fn rest_seconds(&self) -> Option<Seconds> {
+        self.rest_seconds.map(|seconds| Seconds(i64::from(seconds)))
+    }
+
+    async fn exercise(&self, ctx: &Context<'_>) -> Result<Option<Exercise>, AppError> {
+        let exercise = ctx
+            .data_unchecked::<DataLoader<ExerciseLoader>>()
+            .load_one(self.exercise_id)
+            .await?;
+
+        Ok(exercise)
+    }
+}
+
+#[Object]
+impl PersonalRecord {
+    async fn exercise(&self, ctx: &Context<'_>) -> Result<Option<Exercise>, AppError> {
+        let exercise = ctx
+            .data_unchecked::<DataLoader<ExerciseLoader>>()
+            .load_one(self.exercise_id)
+            .await?;
+
+        Ok(exercise)
+    }
+
+    async fn max_weight_kg(&self) -> f64 {
+        self.max_weight_kg
+    }
+
+    /// `maxWeightKg` in `unit`, rounded to two decimal places.
+    async fn max_weight(
+        &self,
+        #[graphql(default_with = "WeightUnit::Kg")] unit: WeightUnit,
+    ) -> f64 {
+        unit.convert_kg(self.max_weight_kg)
+    }
+
+    /// The reps done in the record set. When the record weight was lifted
+    /// more than once in the same workout, the most reps.
+    async fn reps_at_max_weight(&self) -> i32 {
+        self.reps_at_max_weight
+    }
+
+    /// The workout the record was set in.
+    async fn workout(&self) -> Workout {
+        self.workout.clone()
+    }
+
+    async fn performed_at(&self) -> DateTime<Utc> {
+        self.workout.performed_at
+    }
+}
+
+#[ComplexObject]
+impl BodyWeightEntry {
+    /// `weightKg` in `unit`, rounded to two decimal places.
+    async fn weight(&self, #[graphql(default_with = "WeightUnit::Kg")] unit: WeightUnit) -> f64 {
+        unit.convert_kg(self.weight_kg)
+    }
+}
@@ -0,0 +1,112 @@
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextPrepareRequest,
+};
+use async_graphql::parser::types::{DocumentOperations, ExecutableDocument, OperationType};
+use async_graphql::{ErrorExtensionValues, Request, ServerError, ServerResult, Variables};
+use std::sync::Arc;
+
+/// The message `ApolloPersistedQueries` fails a request with when it only
+/// has the hash of a query that isn't in the cache.
+const NOT_FOUND_MESSAGE: &str = "PersistedQueryNotFound";
+
+/// The type of the operation named `operation_name` in `document`, or `None`
+/// when it names no operation the document contains; the executor reports
+/// those.
+pub(crate) fn operation_type(
+    document: &ExecutableDocument,
+    operation_name: Option<&str>,
+) -> Option<OperationType> {
+    let operation = match &document.operations {
+        DocumentOperations::Single(operation) => operation,
+        DocumentOperations::Multiple(operations) => match operation_name {
+            Some(name) => operations.get(name)?,
+            None if operations.len() == 1 => operations.values().next()?,
+            None => return None,
+        },
+    };
+
+    Some(operation.node.ty)
+}
+
+/// Adds the `PERSISTED_QUERY_NOT_FOUND` code Apollo clients look for to the
+/// error `ApolloPersistedQueries` returns on a cache miss, so they retry with
+/// the full query. Must be registered before `ApolloPersistedQueries`.
+pub(crate) struct PersistedQueryErrors;
+
+impl ExtensionFactory for PersistedQueryErrors {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PersistedQueryErrors)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for PersistedQueryErrors {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        next.run(ctx, request).await.map_err(|mut error| {
+            if error.message == NOT_FOUND_MESSAGE {
+                error
+                    .extensions
+                    .get_or_insert_with(ErrorExtensionValues::default)
+                    .set("code", "PERSISTED_QUERY_NOT_FOUND");
+            }
+            error
+        })
+    }
+}
+
+/// Marks an operation received with `GET /graphql`.
+#[derive(Clone)]
+pub(crate) struct GetRequest {
+    pub(crate) operation_name: Option<String>,
+}
+
+/// Fails operations received with `GET /graphql` that aren't queries.
+///
+/// The HTTP handler refuses those with a `405` when the query string has the
+/// query, but a persisted query sent by hash is only known once
+/// `ApolloPersistedQueries` has looked it up, so it is checked here.
+pub(crate) struct QueriesOnlyOverGet;
+
+impl ExtensionFactory for QueriesOnlyOverGet {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(QueriesOnlyOverGet)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for QueriesOnlyOverGet {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+
+        let get_request = match ctx.data_opt::<GetRequest>() {
+            Some(get_request) => get_request,
+            None => return Ok(document),
+        };
+        if let Some(OperationType::Mutation) | Some(OperationType::Subscription) =
+            operation_type(&document, get_request.operation_name.as_deref())
+        {
+            let mut extensions = ErrorExtensionValues::default();
+            extensions.set("code", "METHOD_NOT_ALLOWED");
+
+            let mut error = ServerError::new(
+                "Only queries can be sent with GET; use POST for mutations",
+                None,
+            );
+            error.extensions = Some(extensions);
+            return Err(error);
+        }
+
+        Ok(document)
+    }
+}
@@ -0,0 +1,130 @@
+use dashmap::DashMap;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tide::{Middleware, Next, Request, Response, StatusCode};
+
+use crate::auth::AuthenticatedUser;
+
+/// How long an empty bucket takes to refill completely.
+const REFILL_PERIOD: Duration = Duration::from_secs(60);
+
+/// How often buckets that have refilled completely are dropped.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Paths that are never limited. Load balancers and metrics scrapers poll
+/// them from a single IP, and a `429` would take the instance out of
+/// rotation.
+const UNLIMITED_PATHS: [&str; 2] = ["/health", "/metrics"];
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// A token-bucket rate limiter keyed by the authenticated user, or by the
+/// client IP for anonymous requests. Each client may burst up to
+/// `per_minute` requests, and the bucket refills at `per_minute` tokens a
+/// minute. Requests over the limit get `429 Too Many Requests` with a
+/// `Retry-After` header. `UNLIMITED_PATHS` are let through without taking a
+/// token.
+///
+/// Must be added after `AuthMiddleware` so the user is known. Clones share
+/// their buckets.
+#[derive(Clone)]
+pub(crate) struct RateLimit {
+    capacity: f64,
+    buckets: Arc<DashMap<String, Bucket>>,
+    swept_at: Arc<Mutex<Instant>>,
+}
+
+impl RateLimit {
+    pub(crate) fn per_minute(per_minute: NonZeroU32) -> Self {
+        Self {
+            capacity: f64::from(per_minute.get()),
+            buckets: Arc::new(DashMap::new()),
+            swept_at: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    fn refill_rate(&self) -> f64 {
+        self.capacity / REFILL_PERIOD.as_secs_f64()
+    }
+
+    /// Takes a token from `key`'s bucket, or returns how long until one is
+    /// available.
+    fn take(&self, key: String, now: Instant) -> Result<(), Duration> {
+        let mut bucket = self.buckets.entry(key).or_insert(Bucket {
+            tokens: self.capacity,
+            refilled_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_rate()).min(self.capacity);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.refill_rate(),
+            ))
+        }
+    }
+
+    /// Drops buckets that would be full by now, at most once per
+    /// `SWEEP_INTERVAL`. A full bucket is the same as no bucket.
+    fn sweep(&self, now: Instant) {
+        {
+            let mut swept_at = self.swept_at.lock().unwrap();
+            if now.duration_since(*swept_at) < SWEEP_INTERVAL {
+                return;
+            }
+            *swept_at = now;
+        }
+
+        self.buckets
+            .retain(|_, bucket| now.duration_since(bucket.refilled_at) < REFILL_PERIOD);
+    }
+}
+
+/// The key a request is limited by: the user id when authenticated, the
+/// peer IP otherwise.
+fn client_key<State>(req: &Request<State>) -> String {
+    if let Some(user) = req.ext::<AuthenticatedUser>() {
+        return format!("user:{}", user.id);
+    }
+
+    let ip = req
+        .peer_addr()
+        .map(|addr| match addr.rsplit_once(':') {
+            Some((ip, port)) if port.parse::<u16>().is_ok() => ip,
+            _ => addr,
+        })
+        .unwrap_or("unknown");
+    format!("ip:{}", ip)
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for RateLimit {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        if UNLIMITED_PATHS.contains(&req.url().path()) {
+            return Ok(next.run(req).await);
+        }
+
+        let now = Instant::now();
+        self.sweep(now);
+
+        let key = client_key(&req);
+        if let Err(retry_after) = self.take(key.clone(), now) {
+            tracing::warn!("rate limit exceeded for {}", key);
+
+            let mut resp = Response::new(StatusCode::TooManyRequests);
+            resp.insert_header("Retry-After", retry_after.as_secs_f64().ceil().to_string());
+            return Ok(resp);
+        }
+
+        Ok(next.run(req).await)
+    }
+}
@@ -0,0 +1,278 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres, Transaction};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::error::{invalid_field, pg_error_code, AppError};
+use crate::graphql::validate_name;
+use crate::models::{Equipment, MuscleGroup, Routine};
+
+/// The document version `routineExport` writes and `importRoutine` reads.
+const DOCUMENT_VERSION: u32 = 1;
+
+/// A routine with its exercises and prescribed sets, as exported by
+/// `routineExport`. Exercises are identified by name rather than id, and
+/// carry what is needed to create them, so that a document can be imported
+/// into another server.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RoutineDocument {
+    version: u32,
+    name: String,
+    exercises: Vec<ExerciseDocument>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExerciseDocument {
+    name: String,
+    main_muscle_worked: String,
+    muscle_group: MuscleGroup,
+    equipment: Equipment,
+    #[serde(default)]
+    sets: Vec<SetDocument>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetDocument {
+    reps: i32,
+    weight_kg: Option<f64>,
+}
+
+impl RoutineDocument {
+    /// Reads routine `id`, or returns `None` when there is no such routine,
+    /// it is deleted, or it isn't visible to `user_id`.
+    pub(crate) async fn load(
+        pool: &Pool<Postgres>,
+        id: i32,
+        user_id: Option<i32>,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let routine = sqlx::query!(
+            r#"
+SELECT name FROM routines
+WHERE id = $1 AND deleted_at IS NULL AND visible_to(user_id, $2)
+            "#,
+            id,
+            user_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        let routine = match routine {
+            Some(routine) => routine,
+            None => return Ok(None),
+        };
+
+        let mut sets: HashMap<i32, Vec<SetDocument>> = HashMap::new();
+        for set in sqlx::query!(
+            r#"
+SELECT exercise_id, reps, weight_kg
+FROM routine_exercise_sets
+WHERE routine_id = $1
+ORDER BY exercise_id, set_number
+            "#,
+            id
+        )
+        .fetch_all(pool)
+        .await?
+        {
+            sets.entry(set.exercise_id).or_default().push(SetDocument {
+                reps: set.reps,
+                weight_kg: set.weight_kg,
+            });
+        }
+
+        let exercises = sqlx::query!(
+            r#"
+SELECT
+    exercises.id,
+    exercises.name,
+    muscles.name AS main_muscle_worked,
+    exercises.muscle_group AS "muscle_group: MuscleGroup",
+    exercises.equipment AS "equipment: Equipment"
+FROM routine_exercises
+JOIN exercises ON exercises.id = routine_exercises.exercise_id
+JOIN muscles ON muscles.id = exercises.main_muscle_worked_id
+WHERE routine_exercises.routine_id = $1
+ORDER BY routine_exercises.position
+            "#,
+            id
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|exercise| ExerciseDocument {
+            sets: sets.remove(&exercise.id).unwrap_or_default(),
+            name: exercise.name,
+            main_muscle_worked: exercise.main_muscle_worked,
+            muscle_group: exercise.muscle_group,
+            equipment: exercise.equipment,
+        })
+        .collect();
+
+        Ok(Some(RoutineDocument {
+            version: DOCUMENT_VERSION,
+            name: routine.name,
+            exercises,
+        }))
+    }
+
+    /// Parses and validates a document passed as the `field` argument.
+    pub(crate) fn parse(field: &str, data: &str) -> Result<Self, AppError> {
+        let document: Self = serde_json::from_str(data).map_err(|error| {
+            invalid_field(
+                field,
+                format!("{} is not a routine export: {}", field, error),
+            )
+        })?;
+
+        if document.version != DOCUMENT_VERSION {
+            return Err(invalid_field(
+                format!("{}.version", field),
+                format!(
+                    "{} has version {}, but only version {} can be imported",
+                    field, document.version, DOCUMENT_VERSION
+                ),
+            ));
+        }
+
+        validate_name(&format!("{}.name", field), &document.name)?;
+
+        let mut seen = BTreeSet::new();
+        for (index, exercise) in document.exercises.iter().enumerate() {
+            let path = format!("{}.exercises.{}", field, index);
+            validate_name(&format!("{}.name", path), &exercise.name)?;
+            validate_name(
+                &format!("{}.mainMuscleWorked", path),
+                &exercise.main_muscle_worked,
+            )?;
+            if !seen.insert(exercise.name.trim()) {
+                return Err(invalid_field(
+                    format!("{}.name", path),
+                    format!(
+                        "exercises[{}]: {:?} is listed more than once",
+                        index, exercise.name
+                    ),
+                ));
+            }
+
+            for (set_index, set) in exercise.sets.iter().enumerate() {
+                if set.reps < 1 {
+                    return Err(invalid_field(
+                        format!("{}.sets.{}.reps", path, set_index),
+                        format!(
+                            "exercises[{}].sets[{}]: reps must be at least 1",
+                            index, set_index
+                        ),
+                    ));
+                }
+                if set.weight_kg.is_some_and(|weight_kg| weight_kg < 0.0) {
+                    return Err(invalid_field(
+                        format!("{}.sets.{}.weightKg", path, set_index),
+                        format!(
+                            "exercises[{}].sets[{}]: weightKg must not be negative",
+                            index, set_index
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Ok(document)
+    }
+
+    /// Creates the routine for `user_id`. Exercises are matched to existing
+    /// ones by name; those that don't exist yet are created, along with their
+    /// main muscle when that doesn't exist either.
+    pub(crate) async fn save(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: i32,
+    ) -> Result<Routine, AppError> {
+        let routine = sqlx::query_as!(
+            Routine,
+            "INSERT INTO routines (name, user_id) VALUES ( $1, $2 ) RETURNING id, name, user_id, created_at, updated_at, archived_at, deleted_at, version",
+            self.name.trim(),
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|error| match pg_error_code(&error) {
+            Some("23503") => AppError::NotFound(format!("User {} not found", user_id)),
+            _ => error.into(),
+        })?;
+
+        for (position, exercise) in self.exercises.iter().enumerate() {
+            let exercise_id = exercise.find_or_create(tx).await?;
+
+            sqlx::query!(
+                "INSERT INTO routine_exercises (routine_id, exercise_id, position) VALUES ( $1, $2, $3 )",
+                routine.id,
+                exercise_id,
+                position as i32
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            for (index, set) in exercise.sets.iter().enumerate() {
+                sqlx::query!(
+                    r#"
+INSERT INTO routine_exercise_sets (routine_id, exercise_id, set_number, reps, weight_kg)
+VALUES ( $1, $2, $3, $4, $5 )
+                    "#,
+                    routine.id,
+                    exercise_id,
+                    index as i32 + 1,
+                    set.reps,
+                    set.weight_kg
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        Ok(routine)
+    }
+}
+
+impl ExerciseDocument {
+    /// The id of the exercise with this name, creating it if there is none.
+    async fn find_or_create(&self, tx: &mut Transaction<'_, Postgres>) -> Result<i32, AppError> {
+        let name = self.name.trim();
+
+        let existing = sqlx::query!("SELECT id FROM exercises WHERE name = $1", name)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if let Some(existing) = existing {
+            return Ok(existing.id);
+        }
+
+        // The no-op update makes `RETURNING` yield the id of an existing muscle.
+        let muscle = sqlx::query!(
+            r#"
+INSERT INTO muscles (name) VALUES ( $1 )
+ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+RETURNING id
+            "#,
+            self.main_muscle_worked.trim()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let exercise = sqlx::query!(
+            r#"
+INSERT INTO exercises (name, main_muscle_worked_id, muscle_group, equipment)
+VALUES ( $1, $2, $3::TEXT::muscle_group, $4::TEXT::equipment )
+RETURNING id
+            "#,
+            name,
+            muscle.id,
+            self.muscle_group.as_str(),
+            self.equipment.as_str()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Ok(exercise.id)
+    }
+}
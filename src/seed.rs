@@ -0,0 +1,67 @@
+use sqlx::{Pool, Postgres};
+
+/// The exercises `seed` inserts, with the muscle each mainly works.
+const CATALOG: &[(&str, &str)] = &[
+    ("Back Squat", "Quadriceps"),
+    ("Front Squat", "Quadriceps"),
+    ("Deadlift", "Hamstrings"),
+    ("Romanian Deadlift", "Hamstrings"),
+    ("Bench Press", "Chest"),
+    ("Push Up", "Chest"),
+    ("Overhead Press", "Shoulders"),
+    ("Lateral Raise", "Shoulders"),
+    ("Pull Up", "Lats"),
+    ("Barbell Row", "Upper Back"),
+    ("Biceps Curl", "Biceps"),
+    ("Triceps Extension", "Triceps"),
+    ("Hip Thrust", "Glutes"),
+    ("Calf Raise", "Calves"),
+    ("Plank", "Abdominals"),
+];
+
+/// Inserts a default exercise catalog, and the muscles it refers to, when
+/// there are no exercises yet. Returns how many exercises were inserted,
+/// which is 0 on every run after the first.
+pub async fn seed(postgres_pool: &Pool<Postgres>) -> Result<u64, sqlx::Error> {
+    let (names, muscles): (Vec<&str>, Vec<&str>) = CATALOG.iter().copied().unzip();
+
+    let mut tx = postgres_pool.begin().await?;
+
+    // Keeps servers that start together from both seeding the empty table.
+    sqlx::query("LOCK TABLE exercises IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut tx)
+        .await?;
+
+    let (empty,): (bool,) = sqlx::query_as("SELECT NOT EXISTS (SELECT 1 FROM exercises)")
+        .fetch_one(&mut tx)
+        .await?;
+    if !empty {
+        return Ok(0);
+    }
+
+    sqlx::query("INSERT INTO muscles (name) SELECT DISTINCT UNNEST($1::TEXT[]) ON CONFLICT (name) DO NOTHING")
+        .bind(&muscles)
+        .execute(&mut tx)
+        .await?;
+
+    let (inserted,): (i64,) = sqlx::query_as(
+        r#"
+WITH inserted AS (
+    INSERT INTO exercises (name, main_muscle_worked_id)
+    SELECT catalog.name, muscles.id
+    FROM UNNEST($1::TEXT[], $2::TEXT[]) AS catalog (name, muscle)
+    INNER JOIN muscles ON muscles.name = catalog.muscle
+    RETURNING id
+)
+SELECT COUNT(*) FROM inserted
+        "#,
+    )
+    .bind(&names)
+    .bind(&muscles)
+    .fetch_one(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(inserted as u64)
+}
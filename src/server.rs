@@ -0,0 +1,616 @@
+use async_graphql::extensions::apollo_persisted_queries::{
+    ApolloPersistedQueries, LruCacheStorage,
+};
+use async_graphql::futures_util::future::{self, Either};
+use async_graphql::futures_util::io::{AsyncReadExt, Cursor};
+use async_graphql::futures_util::StreamExt;
+use async_graphql::http::{
+    playground_source, receive_batch_body, GraphQLPlaygroundConfig, MultipartOptions,
+};
+use async_graphql::parser::parse_query;
+use async_graphql::parser::types::OperationType;
+use async_graphql::{
+    BatchRequest, BatchResponse, ErrorExtensions, Request as GraphQLRequest,
+    Response as GraphQLResponse, Result, ServerError, Value, Variables,
+};
+use event_listener::Event;
+use log::LevelFilter;
+use serde::Deserialize;
+use serde_json::json;
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook_async_std::Signals;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{ConnectOptions, Pool, Postgres};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tide::http::{mime, Method};
+use tide::{Body, Middleware, Next, Request, Response, StatusCode};
+
+use crate::auth::{promote_admin, AuthMiddleware, AuthenticatedUser, TokenError, TokenIssuer};
+use crate::config::Config;
+use crate::cors::Cors;
+use crate::error::AppError;
+use crate::export::export_routines_endpoint;
+use crate::graphql::{schema_builder, AppSchema};
+use crate::http_cache::ETagCache;
+use crate::import::import_exercises_endpoint;
+use crate::introspection::RejectIntrospection;
+use crate::metrics::{metrics_endpoint, Metrics};
+use crate::migrate::migrate;
+use crate::persisted_queries::{
+    operation_type, GetRequest, PersistedQueryErrors, QueriesOnlyOverGet,
+};
+use crate::rate_limit::RateLimit;
+use crate::seed::seed;
+use crate::sql_count::SqlStatementCount;
+use crate::trace::{RequestId, RequestTracing, SlowQueryThreshold};
+use crate::upload::upload_fit_endpoint;
+
+/// Where GraphQL subscriptions are served over WebSockets.
+const SUBSCRIPTIONS_PATH: &str = "/graphql/ws";
+
+/// Counts the requests currently being handled so shutdown can wait for
+/// them to finish. WebSocket subscriptions are left out: they stay open
+/// for as long as the client likes, so waiting for them would hold up
+/// every shutdown until `Config::shutdown_timeout`.
+#[derive(Clone, Default)]
+struct InFlightRequests(Arc<InFlight>);
+
+#[derive(Default)]
+struct InFlight {
+    count: AtomicUsize,
+    /// Notified whenever the count drops to zero.
+    drained: Event,
+}
+
+impl InFlightRequests {
+    fn count(&self) -> usize {
+        self.0.count.load(Ordering::SeqCst)
+    }
+
+    /// Waits until every in-flight request has finished.
+    async fn drained(&self) {
+        while self.count() > 0 {
+            // Listening before checking again means a request finishing in
+            // between still wakes us.
+            let drained = self.0.drained.listen();
+            if self.count() == 0 {
+                break;
+            }
+            drained.await;
+        }
+    }
+}
+
+/// Decrements the in-flight count when a request finishes or is dropped.
+struct InFlightGuard(Arc<InFlight>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.drained.notify(usize::MAX);
+        }
+    }
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for InFlightRequests {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        if req.url().path() == SUBSCRIPTIONS_PATH {
+            return Ok(next.run(req).await);
+        }
+
+        self.0.count.fetch_add(1, Ordering::SeqCst);
+        let _guard = InFlightGuard(self.0.clone());
+
+        Ok(next.run(req).await)
+    }
+}
+
+/// Resolves with the signal number once the process receives SIGINT or
+/// SIGTERM.
+async fn shutdown_signal() -> std::io::Result<i32> {
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    let signal = signals.next().await.unwrap_or(SIGTERM);
+
+    Ok(signal)
+}
+
+/// The playground only changes when the server is upgraded, which changes
+/// its `ETag`, so browsers may reuse it briefly and revalidate after.
+const PLAYGROUND_CACHE_CONTROL: &str = "public, max-age=300";
+
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Reports whether the database is reachable: 200 when `SELECT 1` succeeds
+/// within `HEALTH_CHECK_TIMEOUT`, 503 otherwise.
+pub async fn health(postgres_pool: &Pool<Postgres>) -> Response {
+    let ping = sqlx::query("SELECT 1").execute(postgres_pool);
+    let database_ok = matches!(
+        async_std::future::timeout(HEALTH_CHECK_TIMEOUT, ping).await,
+        Ok(Ok(_))
+    );
+
+    let (status, body) = if database_ok {
+        (StatusCode::Ok, json!({ "status": "ok", "database": "ok" }))
+    } else {
+        (
+            StatusCode::ServiceUnavailable,
+            json!({ "status": "degraded", "database": "unavailable" }),
+        )
+    };
+
+    let mut resp = Response::new(status);
+    resp.set_body(body);
+    resp
+}
+
+fn enabled(enabled: bool) -> &'static str {
+    if enabled {
+        "enabled"
+    } else {
+        "disabled"
+    }
+}
+
+/// Attaches `data` to every operation in a (possibly batched) request.
+fn with_data<D: Clone + Send + Sync + 'static>(request: BatchRequest, data: D) -> BatchRequest {
+    match request {
+        BatchRequest::Single(request) => BatchRequest::Single(request.data(data)),
+        BatchRequest::Batch(requests) => BatchRequest::Batch(
+            requests
+                .into_iter()
+                .map(|request| request.data(data.clone()))
+                .collect(),
+        ),
+    }
+}
+
+/// The query string of `GET /graphql`. `variables` and `extensions` are
+/// URL-encoded JSON; `query` may be left out when `extensions` names a
+/// persisted query.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetRequestParams {
+    #[serde(default)]
+    query: String,
+    operation_name: Option<String>,
+    variables: Option<String>,
+    extensions: Option<String>,
+}
+
+/// A `GET /graphql` error response with the `errors` shape clients expect.
+/// A `405` says which method to use instead.
+fn get_request_error(status: StatusCode, message: String) -> Response {
+    let mut resp = Response::new(status);
+    if status == StatusCode::MethodNotAllowed {
+        resp.insert_header("Allow", "POST");
+    }
+    resp.set_body(json!({ "errors": [{ "message": message }] }));
+    resp
+}
+
+/// Reads a GraphQL request from the query string of `GET /graphql`. Only
+/// queries may be sent this way: mutations and subscriptions are refused
+/// with a `405`, and a missing query or malformed `variables` or
+/// `extensions` with a `400`.
+fn receive_get_request(
+    req: &Request<()>,
+) -> std::result::Result<GraphQLRequest, (StatusCode, String)> {
+    let missing_query = || {
+        (
+            StatusCode::BadRequest,
+            "GET /graphql needs a `query` parameter".to_owned(),
+        )
+    };
+    let params: GetRequestParams = req.query().map_err(|_| missing_query())?;
+    if params.query.is_empty() && params.extensions.is_none() {
+        return Err(missing_query());
+    }
+
+    let mut request = GraphQLRequest::new(params.query);
+    if let Some(operation_name) = params.operation_name {
+        request = request.operation_name(operation_name);
+    }
+    if let Some(variables) = params.variables {
+        let variables = match serde_json::from_str(&variables) {
+            Ok(variables @ serde_json::Value::Object(_)) => variables,
+            Ok(_) => {
+                return Err((
+                    StatusCode::BadRequest,
+                    "`variables` must be a JSON object".to_owned(),
+                ))
+            }
+            Err(error) => {
+                return Err((
+                    StatusCode::BadRequest,
+                    format!("`variables` is not valid JSON: {}", error),
+                ))
+            }
+        };
+        request = request.variables(Variables::from_json(variables));
+    }
+    if let Some(extensions) = params.extensions {
+        request.extensions =
+            serde_json::from_str::<HashMap<String, Value>>(&extensions).map_err(|error| {
+                (
+                    StatusCode::BadRequest,
+                    format!("`extensions` is not a JSON object: {}", error),
+                )
+            })?;
+    }
+
+    let document = parse_query(&request.query).ok();
+    let operation_name = request.operation_name.as_deref();
+    match document.and_then(|document| operation_type(&document, operation_name)) {
+        Some(OperationType::Mutation) | Some(OperationType::Subscription) => Err((
+            StatusCode::MethodNotAllowed,
+            "Only queries can be sent with GET; use POST for mutations".to_owned(),
+        )),
+        _ => Ok(request),
+    }
+}
+
+/// Bounds on the work a single `/graphql` request can cause.
+#[derive(Clone, Copy)]
+struct GraphQLRequestLimits {
+    /// The largest `POST` body read; larger ones get a `413`.
+    max_body_bytes: usize,
+    /// How long the operations may run before they are dropped and the
+    /// response is a `TIMEOUT` error.
+    timeout: Duration,
+}
+
+/// Reads a request body of at most `max_body_bytes`, or returns `None` when
+/// it is larger. A `Content-Length` over the limit is refused without
+/// reading anything.
+pub(crate) async fn read_body(
+    req: &mut Request<()>,
+    max_body_bytes: usize,
+) -> tide::Result<Option<Vec<u8>>> {
+    if req.len().is_some_and(|len| len > max_body_bytes) {
+        return Ok(None);
+    }
+
+    let mut body = Vec::new();
+    req.take_body()
+        .take(max_body_bytes as u64 + 1)
+        .read_to_end(&mut body)
+        .await?;
+
+    Ok(if body.len() > max_body_bytes {
+        None
+    } else {
+        Some(body)
+    })
+}
+
+/// Serves `/graphql`: batches of operations in the body of a `POST`, or a
+/// single query in the query string of a `GET`. The signed-in user, any
+/// rejected token and the request id are passed to the operations, which
+/// are cancelled after `GraphQLRequestLimits::timeout`.
+async fn graphql_endpoint(
+    mut req: Request<()>,
+    schema: AppSchema,
+    metrics: Metrics,
+    limits: GraphQLRequestLimits,
+) -> tide::Result {
+    let request_timeout = limits.timeout;
+    let user = req.ext::<AuthenticatedUser>().cloned();
+    let token_error = req.ext::<TokenError>().copied();
+    let request_id = req.ext::<RequestId>().cloned();
+    let mut request = if req.method() == Method::Get {
+        match receive_get_request(&req) {
+            Ok(request) => {
+                let get_request = GetRequest {
+                    operation_name: request.operation_name.clone(),
+                };
+                BatchRequest::Single(request.data(get_request))
+            }
+            Err((status, message)) => return Ok(get_request_error(status, message)),
+        }
+    } else {
+        let body = match read_body(&mut req, limits.max_body_bytes).await? {
+            Some(body) => body,
+            None => {
+                tracing::warn!(
+                    "GraphQL request body is larger than {} bytes",
+                    limits.max_body_bytes
+                );
+                return Ok(Response::new(StatusCode::PayloadTooLarge));
+            }
+        };
+        let content_type = req.content_type().map(|mime| mime.to_string());
+        receive_batch_body(content_type, Cursor::new(body), MultipartOptions::default())
+            .await
+            .map_err(|error| tide::Error::new(StatusCode::BadRequest, error))?
+    };
+    if let Some(user) = user {
+        request = with_data(request, user);
+    }
+    if let Some(token_error) = token_error {
+        request = with_data(request, token_error);
+    }
+    if let Some(request_id) = request_id {
+        request = with_data(request, request_id);
+    }
+    let (operations, batched) = match &request {
+        BatchRequest::Single(_) => (1, false),
+        BatchRequest::Batch(requests) => (requests.len(), true),
+    };
+    metrics.record_graphql_requests(operations);
+
+    let response = async_std::future::timeout(request_timeout, schema.execute_batch(request))
+        .await
+        .unwrap_or_else(|_| {
+            tracing::warn!(
+                "GraphQL request timed out after {}s",
+                request_timeout.as_secs()
+            );
+            timed_out(operations, batched)
+        });
+    async_graphql_tide::respond(response)
+}
+
+/// The response to a request that ran past `Config::request_timeout`: a
+/// `TIMEOUT` error for each of its `operations`.
+fn timed_out(operations: usize, batched: bool) -> BatchResponse {
+    let error = AppError::Timeout.extend();
+    let response = || {
+        let mut server_error = ServerError::new(error.message.clone(), None);
+        server_error.extensions = error.extensions.clone();
+        GraphQLResponse::from_errors(vec![server_error])
+    };
+
+    if batched {
+        BatchResponse::Batch((0..operations).map(|_| response()).collect())
+    } else {
+        BatchResponse::Single(response())
+    }
+}
+
+async fn connect(config: &Config) -> Result<Pool<Postgres>> {
+    tracing::info!(
+        "database pool: max_connections={} min_connections={} connect_timeout={}s idle_timeout={}s",
+        config.database_max_connections,
+        config.database_min_connections,
+        config.database_connect_timeout.as_secs(),
+        config.database_idle_timeout.as_secs()
+    );
+
+    let mut options: PgConnectOptions = config.database_url.parse()?;
+    options.log_slow_statements(LevelFilter::Warn, config.slow_query_threshold);
+
+    let postgres_pool = PgPoolOptions::new()
+        .max_connections(config.database_max_connections)
+        .min_connections(config.database_min_connections)
+        .connect_timeout(config.database_connect_timeout)
+        .idle_timeout(config.database_idle_timeout)
+        .connect_with(options)
+        .await?;
+
+    Ok(postgres_pool)
+}
+
+/// Seeds the exercise catalog when `Config::seed` is set.
+async fn seed_if_enabled(config: &Config, postgres_pool: &Pool<Postgres>) -> Result<()> {
+    if config.seed {
+        let inserted = seed(postgres_pool).await?;
+        tracing::info!("seeded {} exercise(s)", inserted);
+    }
+
+    Ok(())
+}
+
+/// Promotes `Config::admin_email` to admin when it is set.
+async fn promote_admin_if_set(config: &Config, postgres_pool: &Pool<Postgres>) -> Result<()> {
+    if let Some(email) = &config.admin_email {
+        if promote_admin(postgres_pool, email).await? {
+            tracing::info!("{} is an admin", email);
+        } else {
+            tracing::warn!("ADMIN_EMAIL {} does not match any user", email);
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies pending migrations, seeds the database when `Config::seed` is
+/// set, and returns without starting the server.
+pub async fn run_migrations(config: &Config) -> Result<()> {
+    let postgres_pool = connect(config).await?;
+    migrate(&postgres_pool).await?;
+    seed_if_enabled(config, &postgres_pool).await?;
+    postgres_pool.close().await;
+
+    Ok(())
+}
+
+/// Builds the HTTP app: the GraphQL endpoint (`POST`, or `GET` for queries) and
+/// WebSocket subscriptions, the CSV and `.fit` endpoints, the health check,
+/// Prometheus metrics at `/metrics` and, when enabled, the playground at `/`,
+/// which is served with an `ETag` so browsers can revalidate it. Every route
+/// but the health check and metrics is rate limited when
+/// `Config::rate_limit_per_minute` is set. GraphQL requests may use automatic
+/// persisted queries unless `Config::apq_cache_size` is unset, report their SQL
+/// statement count when `Config::debug_sql_count` is set, and are cancelled
+/// after `Config::request_timeout`; bodies over
+/// `Config::graphql_max_body_bytes` get a `413`, as do CSV imports and `.fit`
+/// uploads over `Config::upload_max_body_bytes`.
+pub fn app(config: &Config, postgres_pool: Pool<Postgres>) -> tide::Server<()> {
+    build_app(tide::new(), config, postgres_pool)
+}
+
+/// Adds the middleware and routes described on `app()` to `app`, after any
+/// middleware it already has.
+fn build_app(
+    mut app: tide::Server<()>,
+    config: &Config,
+    postgres_pool: Pool<Postgres>,
+) -> tide::Server<()> {
+    let metrics = Metrics::new();
+    let mut schema = schema_builder(postgres_pool.clone(), config.query_limits)
+        .data(metrics.clone())
+        .data(SlowQueryThreshold(config.slow_query_threshold));
+    if !config.enable_introspection {
+        schema = schema
+            .disable_introspection()
+            .extension(RejectIntrospection);
+    }
+    if let Some(secret) = &config.jwt_secret {
+        schema = schema.data(TokenIssuer::new(secret));
+    }
+    if let Some(threshold) = config.debug_sql_count {
+        schema = schema.extension(SqlStatementCount::new(threshold));
+    }
+    if let Some(size) = config.apq_cache_size {
+        schema = schema
+            .extension(PersistedQueryErrors)
+            .extension(ApolloPersistedQueries::new(LruCacheStorage::new(
+                size.get(),
+            )))
+            .extension(QueriesOnlyOverGet);
+    }
+    let schema = schema.finish();
+
+    app.with(RequestTracing);
+    app.with(metrics.clone());
+    app.with(Cors::new(
+        config.allowed_origins.clone(),
+        config.cors_max_age,
+    ));
+    app.with(AuthMiddleware::new(
+        config.jwt_secret.clone(),
+        postgres_pool.clone(),
+    ));
+    if let Some(per_minute) = config.rate_limit_per_minute {
+        app.with(RateLimit::per_minute(per_minute));
+    }
+
+    let graphql_schema = schema.clone();
+    let graphql_metrics = metrics.clone();
+    let limits = GraphQLRequestLimits {
+        max_body_bytes: config.graphql_max_body_bytes,
+        timeout: config.request_timeout,
+    };
+    let graphql = move |req: Request<()>| {
+        graphql_endpoint(req, graphql_schema.clone(), graphql_metrics.clone(), limits)
+    };
+    app.at("/graphql").get(graphql.clone()).post(graphql);
+
+    let import_pool = postgres_pool.clone();
+    let upload_max_body_bytes = config.upload_max_body_bytes;
+    app.at("/import/exercises").post(move |req: Request<()>| {
+        import_exercises_endpoint(req, import_pool.clone(), upload_max_body_bytes)
+    });
+
+    let export_pool = postgres_pool.clone();
+    app.at("/export/routines.csv")
+        .get(move |req: Request<()>| export_routines_endpoint(req, export_pool.clone()));
+
+    let upload_pool = postgres_pool.clone();
+    app.at("/upload/fit").post(move |req: Request<()>| {
+        upload_fit_endpoint(req, upload_pool.clone(), upload_max_body_bytes)
+    });
+
+    let health_pool = postgres_pool.clone();
+    app.at("/health").get(move |_| {
+        let postgres_pool = health_pool.clone();
+        async move { Ok(health(&postgres_pool).await) }
+    });
+
+    app.at("/metrics").get(move |_| {
+        let response = metrics_endpoint(&metrics, &postgres_pool);
+        async move { Ok(response) }
+    });
+
+    app.at(SUBSCRIPTIONS_PATH)
+        .get(async_graphql_tide::Subscription::new(schema));
+
+    if config.enable_playground {
+        app.at("/")
+            .with(ETagCache::new(PLAYGROUND_CACHE_CONTROL))
+            .get(|_| async move {
+                let mut resp = Response::new(StatusCode::Ok);
+                resp.set_body(Body::from_string(playground_source(
+                    GraphQLPlaygroundConfig::new("/graphql")
+                        .subscription_endpoint(SUBSCRIPTIONS_PATH),
+                )));
+                resp.set_content_type(mime::HTML);
+                Ok(resp)
+            });
+    }
+
+    app
+}
+
+/// Connects to Postgres and serves `app` until SIGINT or SIGTERM. On a
+/// signal the server stops accepting connections, gives in-flight requests
+/// up to `Config::shutdown_timeout` to finish, and closes the pool.
+///
+/// Pending migrations are applied first when `Config::run_migrations` is set;
+/// if one fails the server is not started. The exercise catalog is then
+/// seeded when `Config::seed` is set.
+pub async fn run(config: Config) -> Result<()> {
+    let postgres_pool = connect(&config).await?;
+
+    if config.run_migrations {
+        migrate(&postgres_pool).await?;
+    }
+    seed_if_enabled(&config, &postgres_pool).await?;
+    promote_admin_if_set(&config, &postgres_pool).await?;
+
+    tracing::info!(
+        "Running in {} mode: playground {}, introspection {}",
+        config.app_env,
+        enabled(config.enable_playground),
+        enabled(config.enable_introspection)
+    );
+    if config.jwt_secret.is_none() {
+        tracing::warn!("JWT_SECRET is not set; all requests will be unauthenticated");
+    }
+
+    // Registered first, so that it is the outermost middleware and counts a
+    // request before `AuthMiddleware` can use the pool for it.
+    let in_flight = InFlightRequests::default();
+    let mut server = tide::new();
+    server.with(in_flight.clone());
+    let app = build_app(server, &config, postgres_pool.clone());
+
+    let listen_addr = config.listen_addr();
+    tracing::info!("Listening on http://{}", listen_addr);
+    if config.enable_playground {
+        tracing::info!("Playground: http://{}/", listen_addr);
+    }
+    let listener = Box::pin(app.listen(listen_addr));
+    let signal = Box::pin(shutdown_signal());
+
+    let signal = match future::select(listener, signal).await {
+        Either::Left((result, _)) => {
+            result?;
+            return Ok(());
+        }
+        Either::Right((result, _)) => result?,
+    };
+
+    tracing::info!(
+        "Received signal {}; shutting down once in-flight requests finish",
+        signal
+    );
+    if async_std::future::timeout(config.shutdown_timeout, in_flight.drained())
+        .await
+        .is_err()
+    {
+        tracing::warn!(
+            "Shutdown timeout elapsed with {} request(s) still in flight",
+            in_flight.count()
+        );
+    }
+
+    postgres_pool.close().await;
+    tracing::info!("Shutdown complete");
+
+    Ok(())
+}
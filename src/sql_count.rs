@@ -0,0 +1,124 @@
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextExecute};
+use async_graphql::{Response, Value};
+use async_std::task::{self, TaskId};
+use dashmap::DashMap;
+use log::{Log, Metadata, Record};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use tracing_log::LogTracer;
+
+/// The `log` target sqlx reports each statement it executes under.
+const STATEMENT_TARGET: &str = "sqlx::query";
+
+/// The ping sqlx sends when a connection is taken from the pool, which is
+/// logged like a statement but isn't one an operation asked for.
+const PING: &str = "/* SQLx ping */";
+
+/// Statement counts of the GraphQL operations running right now, keyed by
+/// the task running each. DataLoader batches run in the task of the request
+/// that triggered them, so they count towards that operation.
+fn counts() -> &'static DashMap<TaskId, Arc<AtomicUsize>> {
+    static COUNTS: OnceLock<DashMap<TaskId, Arc<AtomicUsize>>> = OnceLock::new();
+    COUNTS.get_or_init(DashMap::new)
+}
+
+fn is_statement(record: &Record) -> bool {
+    record.target() == STATEMENT_TARGET && !record.args().to_string().starts_with(PING)
+}
+
+/// The global `log` logger when `DEBUG_SQL_COUNT` is set. Counts the
+/// statements sqlx logs towards the operation running in the current task,
+/// and passes every record on to `tracing` as `LogTracer` would.
+pub(crate) struct SqlStatementLogger {
+    tracer: LogTracer,
+}
+
+impl SqlStatementLogger {
+    pub(crate) fn new() -> Self {
+        Self {
+            tracer: LogTracer::new(),
+        }
+    }
+}
+
+impl Log for SqlStatementLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target() == STATEMENT_TARGET || self.tracer.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if is_statement(record) {
+            if let Some(count) = task::try_current().and_then(|task| counts().get(&task.id())) {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        if self.tracer.enabled(record.metadata()) {
+            self.tracer.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.tracer.flush();
+    }
+}
+
+/// Counts the SQL statements each GraphQL operation runs, reports the count
+/// as `extensions.sqlQueryCount` and logs a warning when it exceeds
+/// `threshold`, to catch resolvers that query once per parent instead of
+/// going through a DataLoader. The catalog lookups sqlx makes the first time
+/// a connection sees a custom type count too. Only counts when `SqlStatementLogger` is the
+/// `log` logger, which `init_tracing` installs when `DEBUG_SQL_COUNT` is set.
+pub(crate) struct SqlStatementCount {
+    threshold: NonZeroUsize,
+}
+
+impl SqlStatementCount {
+    pub(crate) fn new(threshold: NonZeroUsize) -> Self {
+        Self { threshold }
+    }
+}
+
+impl ExtensionFactory for SqlStatementCount {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(SqlStatementCount {
+            threshold: self.threshold,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for SqlStatementCount {
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let task_id = match task::try_current() {
+            Some(task) => task.id(),
+            None => return next.run(ctx, operation_name).await,
+        };
+
+        let count = Arc::new(AtomicUsize::new(0));
+        counts().insert(task_id, count.clone());
+        let mut response = next.run(ctx, operation_name).await;
+        counts().remove(&task_id);
+
+        let statements = count.load(Ordering::Relaxed);
+        if statements > self.threshold.get() {
+            tracing::warn!(
+                operation = operation_name.unwrap_or("anonymous"),
+                statements,
+                threshold = self.threshold.get(),
+                "GraphQL operation ran more SQL statements than DEBUG_SQL_COUNT"
+            );
+        }
+        response
+            .extensions
+            .insert("sqlQueryCount".to_owned(), Value::from(statements as u64));
+
+        response
+    }
+}
@@ -0,0 +1,174 @@
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo,
+};
+use async_graphql::{ServerResult, Value};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::env;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tide::{Middleware, Next, Request};
+use tracing::level_filters::LevelFilter;
+use tracing::Instrument;
+use tracing_log::AsLog;
+use tracing_subscriber::EnvFilter;
+
+use crate::metrics::Metrics;
+use crate::sql_count::SqlStatementLogger;
+
+const DEFAULT_LOG_FILTER: &str = "info";
+
+/// Installs the global `tracing` subscriber, which also receives records
+/// from the `log` crate. Filtering follows `RUST_LOG`, then `LOG_LEVEL`,
+/// and defaults to `info`. When `DEBUG_SQL_COUNT` is set, `log` records go
+/// through `SqlStatementLogger` so that SQL statements can be counted
+/// whatever the filter.
+pub fn init_tracing() {
+    let (name, directives) = match (env::var("RUST_LOG"), env::var("LOG_LEVEL")) {
+        (Ok(directives), _) => ("RUST_LOG", directives),
+        (_, Ok(directives)) => ("LOG_LEVEL", directives),
+        _ => ("", DEFAULT_LOG_FILTER.to_string()),
+    };
+
+    let filter = EnvFilter::try_new(&directives);
+    let invalid = filter.is_err();
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter.unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER)));
+    if counts_sql_statements() {
+        tracing::subscriber::set_global_default(subscriber.finish())
+            .expect("a tracing subscriber is already installed");
+        // sqlx logs statements at `info`, which must reach the logger even
+        // when the filter drops them.
+        log::set_boxed_logger(Box::new(SqlStatementLogger::new()))
+            .expect("a logger is already installed");
+        log::set_max_level(LevelFilter::current().as_log().max(log::LevelFilter::Info));
+    } else {
+        subscriber.init();
+    }
+
+    if invalid {
+        tracing::warn!(
+            "{} has an invalid value: {:?}; using {:?}",
+            name,
+            directives,
+            DEFAULT_LOG_FILTER
+        );
+    }
+}
+
+/// Whether `DEBUG_SQL_COUNT` is set to a threshold, as `Config` reads it.
+fn counts_sql_statements() -> bool {
+    env::var("DEBUG_SQL_COUNT")
+        .ok()
+        .and_then(|threshold| threshold.trim().parse::<usize>().ok())
+        .is_some_and(|threshold| threshold > 0)
+}
+
+/// Identifies one HTTP request in the logs and in `requestId` error
+/// extensions.
+#[derive(Clone, Debug)]
+pub(crate) struct RequestId(pub(crate) String);
+
+pub(crate) fn new_request_id() -> String {
+    let mut bytes = [0; 8];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system random number generator failed");
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Opens a span per request carrying its method, path and a generated
+/// request id, and logs the response status and duration when it finishes.
+pub(crate) struct RequestTracing;
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for RequestTracing {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let request_id = new_request_id();
+        let span = tracing::info_span!(
+            "request",
+            method = %req.method(),
+            path = %req.url().path(),
+            request_id = %request_id
+        );
+        req.set_ext(RequestId(request_id));
+
+        async move {
+            let started = Instant::now();
+            let response = next.run(req).await;
+            tracing::info!(
+                status = %response.status(),
+                elapsed = ?started.elapsed(),
+                "request finished"
+            );
+
+            Ok(response)
+        }
+        .instrument(span)
+        .await
+    }
+}
+
+/// Runs a database operation and logs how long it took at `debug`.
+pub(crate) async fn timed<F: Future>(operation: &str, future: F) -> F::Output {
+    let started = Instant::now();
+    let output = future.await;
+    tracing::debug!(operation, elapsed = ?started.elapsed(), "database operation finished");
+
+    output
+}
+
+/// The duration at which `ResolverTiming` logs a root field as slow.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SlowQueryThreshold(pub(crate) Duration);
+
+/// Logs how long each root query or mutation field took to resolve at
+/// `debug`, or at `warn` when it reached the schema's `SlowQueryThreshold`,
+/// and records it in the schema's `Metrics` when it has them. Nested fields
+/// are served by the loaders, which time their own batches.
+pub(crate) struct ResolverTiming;
+
+impl ExtensionFactory for ResolverTiming {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(ResolverTiming)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for ResolverTiming {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        if info.path_node.parent.is_some() {
+            return next.run(ctx, info).await;
+        }
+
+        let operation = format!("{}.{}", info.parent_type, info.name);
+        let started = Instant::now();
+        let result = next.run(ctx, info).await;
+        let elapsed = started.elapsed();
+        match ctx.data_opt::<SlowQueryThreshold>() {
+            Some(SlowQueryThreshold(threshold)) if elapsed >= *threshold => tracing::warn!(
+                operation = operation.as_str(),
+                elapsed_ms = elapsed.as_millis() as u64,
+                ok = result.is_ok(),
+                "slow resolver"
+            ),
+            _ => tracing::debug!(
+                operation = operation.as_str(),
+                elapsed = ?elapsed,
+                ok = result.is_ok(),
+                "resolver finished"
+            ),
+        }
+
+        if let Some(metrics) = ctx.data_opt::<Metrics>() {
+            metrics.record_resolver(&operation, elapsed, result.is_ok());
+        }
+
+        result
+    }
+}
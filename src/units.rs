@@ -0,0 +1,34 @@
+use async_graphql::Enum;
+
+/// Kilograms in an international pound, exactly.
+const KG_PER_LB: f64 = 0.453_592_37;
+
+/// A unit weights can be given in or asked for. Weights are always stored in
+/// kilograms; other units only exist at the API boundary.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WeightUnit {
+    Kg,
+    Lb,
+}
+
+impl WeightUnit {
+    /// Converts a weight given in this unit to kilograms for storage. The
+    /// result isn't rounded, so converting it back with `convert_kg` gives the
+    /// weight that was entered.
+    pub(crate) fn to_kg(self, weight: f64) -> f64 {
+        match self {
+            WeightUnit::Kg => weight,
+            WeightUnit::Lb => weight * KG_PER_LB,
+        }
+    }
+
+    /// Converts a stored weight to this unit, rounded to two decimal places.
+    pub(crate) fn convert_kg(self, weight_kg: f64) -> f64 {
+        let weight = match self {
+            WeightUnit::Kg => weight_kg,
+            WeightUnit::Lb => weight_kg / KG_PER_LB,
+        };
+
+        (weight * 100.0).round() / 100.0
+    }
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use fitparser::profile::MesgNum;
+use fitparser::{FitDataRecord, Value};
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+use std::convert::TryInto;
+use tide::{Body, Request, Response, StatusCode};
+
+use crate::auth::AuthenticatedUser;
+use crate::server::read_body;
+
+/// The summary of an activity read from a `.fit` file.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Activity {
+    performed_at: DateTime<Utc>,
+    distance_m: Option<f64>,
+    duration_s: Option<f64>,
+    avg_heart_rate: Option<i32>,
+}
+
+fn field<'a>(message: &'a FitDataRecord, name: &str) -> Option<&'a Value> {
+    message
+        .fields()
+        .iter()
+        .find(|field| field.name() == name)
+        .map(|field| field.value())
+}
+
+fn timestamp(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::Timestamp(timestamp) => Some(timestamp.with_timezone(&Utc)),
+        _ => None,
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    value.clone().try_into().ok()
+}
+
+/// Summarises the first session in a `.fit` file. Files without a session
+/// message, which some devices write, are summarised from their records.
+pub(crate) fn parse_activity(bytes: &[u8]) -> Result<Activity, String> {
+    let messages = fitparser::from_bytes(bytes).map_err(|error| error.to_string())?;
+
+    let session = messages
+        .iter()
+        .find(|message| message.kind() == MesgNum::Session);
+    if let Some(session) = session {
+        if let Some(performed_at) = field(session, "start_time").and_then(timestamp) {
+            return Ok(Activity {
+                performed_at,
+                distance_m: field(session, "total_distance").and_then(number),
+                duration_s: field(session, "total_elapsed_time").and_then(number),
+                avg_heart_rate: field(session, "avg_heart_rate")
+                    .and_then(number)
+                    .map(|heart_rate| heart_rate.round() as i32),
+            });
+        }
+    }
+
+    let records: Vec<&FitDataRecord> = messages
+        .iter()
+        .filter(|message| message.kind() == MesgNum::Record)
+        .collect();
+    let timestamps: Vec<DateTime<Utc>> = records
+        .iter()
+        .filter_map(|record| field(record, "timestamp").and_then(timestamp))
+        .collect();
+    let heart_rates: Vec<f64> = records
+        .iter()
+        .filter_map(|record| field(record, "heart_rate").and_then(number))
+        .collect();
+
+    let (first, last) = match (timestamps.iter().min(), timestamps.iter().max()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return Err("The file contains no activity session or records".to_owned()),
+    };
+
+    Ok(Activity {
+        performed_at: first,
+        distance_m: records
+            .iter()
+            .filter_map(|record| field(record, "distance").and_then(number))
+            .fold(None, |max: Option<f64>, distance| {
+                Some(max.map_or(distance, |max| max.max(distance)))
+            }),
+        duration_s: Some((last - first).num_milliseconds() as f64 / 1000.0),
+        avg_heart_rate: if heart_rates.is_empty() {
+            None
+        } else {
+            Some((heart_rates.iter().sum::<f64>() / heart_rates.len() as f64).round() as i32)
+        },
+    })
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response {
+    let mut resp = Response::new(status);
+    resp.set_body(Body::from_json(&body).unwrap_or_else(|_| Body::empty()));
+    resp
+}
+
+/// `POST /upload/fit` with the raw bytes of a `.fit` activity file of at
+/// most `max_body_bytes`; larger ones get a `413`. Stores the activity as a
+/// workout without a routine, owned by the signed-in user if any, and
+/// responds with its id.
+pub(crate) async fn upload_fit_endpoint(
+    mut req: Request<()>,
+    postgres_pool: Pool<Postgres>,
+    max_body_bytes: usize,
+) -> tide::Result {
+    let bytes = match read_body(&mut req, max_body_bytes).await? {
+        Some(bytes) => bytes,
+        None => {
+            tracing::warn!(".fit upload is larger than {} bytes", max_body_bytes);
+            return Ok(Response::new(StatusCode::PayloadTooLarge));
+        }
+    };
+
+    let activity = match parse_activity(&bytes) {
+        Ok(activity) => activity,
+        Err(message) => {
+            return Ok(json_response(
+                StatusCode::BadRequest,
+                json!({ "error": format!("Could not read .fit file: {}", message) }),
+            ));
+        }
+    };
+
+    let workout = sqlx::query!(
+        r#"
+INSERT INTO workouts (performed_at, distance_m, duration_s, avg_heart_rate, user_id)
+VALUES ( $1, $2, $3, $4, $5 )
+RETURNING id
+        "#,
+        activity.performed_at,
+        activity.distance_m,
+        activity.duration_s,
+        activity.avg_heart_rate,
+        req.ext::<AuthenticatedUser>().map(|user| user.id)
+    )
+    .fetch_one(&postgres_pool)
+    .await
+    .map_err(|error| {
+        tracing::error!("storing uploaded activity failed: {}", error);
+        tide::Error::from_str(StatusCode::InternalServerError, "Internal server error")
+    })?;
+
+    Ok(json_response(
+        StatusCode::Created,
+        json!({ "id": workout.id }),
+    ))
+}
@@ -0,0 +1,187 @@
+use async_std::task;
+use serde_json::Value;
+
+mod common;
+
+use common::TestDb;
+
+fn error_code(response: &async_graphql::Response) -> Value {
+    let errors = serde_json::to_value(&response.errors).unwrap();
+    errors[0]["extensions"]["code"].clone()
+}
+
+async fn insert_exercise(db: &TestDb, name: &str) -> i32 {
+    let (muscle_id,): (i32,) =
+        sqlx::query_as("INSERT INTO muscles (name) VALUES ($1) RETURNING id")
+            .bind(format!("Muscle for {}", name))
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+    let (exercise_id,): (i32,) = sqlx::query_as(
+        "INSERT INTO exercises (name, main_muscle_worked_id) VALUES ($1, $2) RETURNING id",
+    )
+    .bind(name)
+    .bind(muscle_id)
+    .fetch_one(&db.pool)
+    .await
+    .unwrap();
+
+    exercise_id
+}
+
+#[test]
+fn users_requires_a_signed_in_user() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+
+        let response = db.execute("{ users { id } }").await;
+
+        assert_eq!(error_code(&response), "UNAUTHENTICATED");
+    });
+}
+
+#[test]
+fn users_is_forbidden_to_non_admins() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("user@example.com").await;
+
+        let response = db.execute_as(user_id, "{ users { id } }").await;
+
+        assert_eq!(error_code(&response), "FORBIDDEN");
+    });
+}
+
+#[test]
+fn admins_can_list_users() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let admin_id = db.insert_user("admin@example.com").await;
+        db.insert_user("user@example.com").await;
+        assert!(fit::promote_admin(&db.pool, "ADMIN@example.com")
+            .await
+            .unwrap());
+        assert!(!fit::promote_admin(&db.pool, "nobody@example.com")
+            .await
+            .unwrap());
+
+        let response = db.execute_as(admin_id, "{ users { email role } }").await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            r#"{users: [{email: "admin@example.com",role: ADMIN},{email: "user@example.com",role: USER}]}"#
+        );
+    });
+}
+
+#[test]
+fn delete_exercise_is_restricted_to_admins() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("user@example.com").await;
+        let admin_id = db.insert_user("admin@example.com").await;
+        fit::promote_admin(&db.pool, "admin@example.com")
+            .await
+            .unwrap();
+        let exercise_id = insert_exercise(&db, "Squat").await;
+        let mutation = format!(
+            "mutation {{ deleteExercise(id: {}) {{ name }} }}",
+            exercise_id
+        );
+
+        let response = db.execute(mutation.clone()).await;
+        assert_eq!(error_code(&response), "UNAUTHENTICATED");
+
+        let response = db.execute_as(user_id, mutation.clone()).await;
+        assert_eq!(error_code(&response), "FORBIDDEN");
+
+        let (remaining,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM exercises")
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, 1);
+
+        let response = db.execute_as(admin_id, mutation).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            r#"{deleteExercise: {name: "Squat"}}"#
+        );
+    });
+}
+
+#[test]
+fn exercises_in_routines_are_only_deleted_when_forced() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let admin_id = db.insert_user("admin@example.com").await;
+        fit::promote_admin(&db.pool, "admin@example.com")
+            .await
+            .unwrap();
+        let exercise_id = insert_exercise(&db, "Squat").await;
+        let other_exercise_id = insert_exercise(&db, "Lunge").await;
+        let mut routine_ids = Vec::new();
+        for name in &["Legs", "Full body"] {
+            let (routine_id,): (i32,) =
+                sqlx::query_as("INSERT INTO routines (name) VALUES ($1) RETURNING id")
+                    .bind(name)
+                    .fetch_one(&db.pool)
+                    .await
+                    .unwrap();
+            for (position, exercise_id) in [exercise_id, other_exercise_id].iter().enumerate() {
+                sqlx::query(
+                    "INSERT INTO routine_exercises (routine_id, exercise_id, position) VALUES ($1, $2, $3)",
+                )
+                .bind(routine_id)
+                .bind(exercise_id)
+                .bind(position as i32)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            }
+            routine_ids.push(routine_id);
+        }
+
+        let response = db
+            .execute_as(
+                admin_id,
+                format!(
+                    "mutation {{ deleteExercise(id: {}) {{ name }} }}",
+                    exercise_id
+                ),
+            )
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "IN_USE");
+        assert_eq!(
+            errors[0]["extensions"]["routineIds"],
+            serde_json::json!(routine_ids)
+        );
+
+        let response = db
+            .execute_as(
+                admin_id,
+                format!(
+                    "mutation {{ deleteExercise(id: {}, force: true) {{ name }} }}",
+                    exercise_id
+                ),
+            )
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let remaining: Vec<(i32, i32)> = sqlx::query_as(
+            "SELECT routine_id, exercise_id FROM routine_exercises ORDER BY routine_id",
+        )
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            remaining,
+            vec![
+                (routine_ids[0], other_exercise_id),
+                (routine_ids[1], other_exercise_id)
+            ]
+        );
+    });
+}
@@ -0,0 +1,341 @@
+use async_std::task;
+use ring::hmac;
+use serde_json::{json, Value};
+use std::env;
+use tide::http::{Method, Request, Response, StatusCode, Url};
+
+mod common;
+
+use common::TestDb;
+
+fn app(db: &TestDb) -> tide::Server<()> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    let config = fit::Config::from_vars(|name| match name {
+        "DATABASE_URL" => Some(database_url.clone()),
+        "JWT_SECRET" => Some("test secret".to_owned()),
+        _ => None,
+    })
+    .unwrap();
+
+    fit::app(&config, db.pool.clone())
+}
+
+async fn post_graphql(app: &tide::Server<()>, token: Option<&str>, query: &str) -> Value {
+    let header = token.map(|token| ("Authorization", format!("Bearer {}", token)));
+    post_graphql_with_header(app, header, query).await
+}
+
+async fn post_graphql_with_header(
+    app: &tide::Server<()>,
+    header: Option<(&str, String)>,
+    query: &str,
+) -> Value {
+    let mut req = Request::new(
+        Method::Post,
+        Url::parse("http://localhost/graphql").unwrap(),
+    );
+    if let Some((name, value)) = header {
+        req.insert_header(name, value);
+    }
+    req.set_body(json!({ "query": query }));
+
+    let mut res: Response = app.respond(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::Ok);
+    res.body_json().await.unwrap()
+}
+
+/// Signs a token for user 1 with the test secret, expiring `exp` seconds
+/// after the Unix epoch.
+fn token_expiring_at(exp: u64) -> String {
+    let encode = |json: String| base64::encode_config(json, base64::URL_SAFE_NO_PAD);
+    let signed = format!(
+        "{}.{}",
+        encode(r#"{"alg":"HS256","typ":"JWT"}"#.to_owned()),
+        encode(format!(r#"{{"sub":1,"exp":{}}}"#, exp))
+    );
+    let key = hmac::Key::new(hmac::HMAC_SHA256, b"test secret");
+    let signature = hmac::sign(&key, signed.as_bytes());
+
+    format!(
+        "{}.{}",
+        signed,
+        base64::encode_config(signature.as_ref(), base64::URL_SAFE_NO_PAD)
+    )
+}
+
+#[test]
+fn registered_users_can_log_in_and_use_their_token() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let app = app(&db);
+        let email = "Lifter@Example.com".to_owned();
+
+        let response = post_graphql(
+            &app,
+            None,
+            &format!(
+                r#"mutation {{ register(email: "{}", password: "correct horse") {{ token user {{ id email }} }} }}"#,
+                email
+            ),
+        )
+        .await;
+        assert!(response.get("errors").is_none(), "{}", response);
+        let user_id = response["data"]["register"]["user"]["id"].clone();
+
+        let response = post_graphql(
+            &app,
+            None,
+            &format!(
+                r#"mutation {{ login(email: "{}", password: "correct horse") {{ token user {{ id }} }} }}"#,
+                email.to_lowercase()
+            ),
+        )
+        .await;
+        assert!(response.get("errors").is_none(), "{}", response);
+        assert_eq!(response["data"]["login"]["user"]["id"], user_id);
+        let token = response["data"]["login"]["token"].as_str().unwrap();
+
+        let response = post_graphql(
+            &app,
+            Some(token),
+            &format!(
+                r#"mutation {{ createRoutine(name: "{}") {{ id }} }}"#,
+                email
+            ),
+        )
+        .await;
+        assert!(response.get("errors").is_none(), "{}", response);
+    });
+}
+
+#[test]
+fn register_rejects_short_passwords_and_taken_emails() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let app = app(&db);
+        let email = "Lifter@Example.com".to_owned();
+        let register = |email: String, password: &'static str| {
+            let app = &app;
+            async move {
+                post_graphql(
+                    app,
+                    None,
+                    &format!(
+                        r#"mutation {{ register(email: "{}", password: "{}") {{ token }} }}"#,
+                        email, password
+                    ),
+                )
+                .await
+            }
+        };
+
+        let response = register(email.clone(), "short").await;
+        assert_eq!(response["errors"][0]["extensions"]["code"], "VALIDATION");
+        assert_eq!(response["errors"][0]["extensions"]["field"], "password");
+
+        let response = register(email.clone(), "long enough").await;
+        assert!(response.get("errors").is_none(), "{}", response);
+
+        let response = register(email.to_uppercase(), "long enough").await;
+        assert_eq!(response["errors"][0]["extensions"]["code"], "CONFLICT");
+        assert_eq!(response["errors"][0]["extensions"]["field"], "email");
+    });
+}
+
+#[test]
+fn login_rejects_wrong_passwords_and_unknown_emails_alike() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let app = app(&db);
+        let email = "Lifter@Example.com".to_owned();
+        post_graphql(
+            &app,
+            None,
+            &format!(
+                r#"mutation {{ register(email: "{}", password: "long enough") {{ token }} }}"#,
+                email
+            ),
+        )
+        .await;
+
+        for (email, password) in &[
+            (email.as_str(), "wrong password"),
+            ("nobody@example.com", "long enough"),
+        ] {
+            let response = post_graphql(
+                &app,
+                None,
+                &format!(
+                    r#"mutation {{ login(email: "{}", password: "{}") {{ token }} }}"#,
+                    email, password
+                ),
+            )
+            .await;
+
+            assert_eq!(
+                response["errors"][0]["extensions"]["code"],
+                "INVALID_CREDENTIALS"
+            );
+        }
+    });
+}
+
+#[test]
+fn rejected_tokens_get_distinct_codes_but_do_not_block_public_queries() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let app = app(&db);
+        let expired = token_expiring_at(1);
+        let mut tampered = token_expiring_at(u32::MAX as u64);
+        tampered.push('x');
+
+        for (token, code) in &[
+            (expired.as_str(), "TOKEN_EXPIRED"),
+            (tampered.as_str(), "INVALID_TOKEN"),
+        ] {
+            let response = post_graphql(
+                &app,
+                Some(token),
+                r#"mutation { createRoutine(name: "Never created") { id } }"#,
+            )
+            .await;
+            assert_eq!(response["errors"][0]["extensions"]["code"], *code);
+
+            let response = post_graphql(&app, Some(token), "{ exerciseCount }").await;
+            assert!(response.get("errors").is_none(), "{}", response);
+        }
+
+        let response = post_graphql(
+            &app,
+            None,
+            r#"mutation { createRoutine(name: "Never created") { id } }"#,
+        )
+        .await;
+        assert_eq!(
+            response["errors"][0]["extensions"]["code"],
+            "UNAUTHENTICATED"
+        );
+    });
+}
+
+#[test]
+fn api_keys_sign_requests_in_until_revoked() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let app = app(&db);
+        let response = post_graphql(
+            &app,
+            None,
+            &format!(
+                r#"mutation {{ register(email: "{}", password: "long enough") {{ token user {{ id }} }} }}"#,
+                "lifter@example.com"
+            ),
+        )
+        .await;
+        let user_id = response["data"]["register"]["user"]["id"].clone();
+        let token = response["data"]["register"]["token"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+
+        let response = post_graphql(
+            &app,
+            Some(&token),
+            r#"mutation { createApiKey(name: "Laptop sync") { key apiKey { id name lastUsedAt } } }"#,
+        )
+        .await;
+        assert!(response.get("errors").is_none(), "{}", response);
+        let created = &response["data"]["createApiKey"];
+        let key = created["key"].as_str().unwrap().to_owned();
+        let key_id = created["apiKey"]["id"].clone();
+        assert!(key.starts_with("fit_") && key.len() >= 4 + 43, "{}", key);
+        assert_eq!(created["apiKey"]["name"], "Laptop sync");
+        assert_eq!(created["apiKey"]["lastUsedAt"], Value::Null);
+
+        for header in &[
+            ("Authorization", format!("ApiKey {}", key)),
+            ("X-Api-Key", key.clone()),
+        ] {
+            let response = post_graphql_with_header(
+                &app,
+                Some(header.clone()),
+                "{ viewer { id apiKeys { id name lastUsedAt revokedAt } } }",
+            )
+            .await;
+            assert!(response.get("errors").is_none(), "{}", response);
+            let viewer = &response["data"]["viewer"];
+            assert_eq!(viewer["id"], user_id);
+            assert_eq!(viewer["apiKeys"][0]["id"], key_id);
+            assert!(viewer["apiKeys"][0]["lastUsedAt"].is_string(), "{}", viewer);
+            assert_eq!(viewer["apiKeys"][0]["revokedAt"], Value::Null);
+        }
+
+        let response = post_graphql(
+            &app,
+            Some(&token),
+            &format!(
+                "mutation {{ revokeApiKey(id: {}) {{ revokedAt }} }}",
+                key_id
+            ),
+        )
+        .await;
+        assert!(
+            response["data"]["revokeApiKey"]["revokedAt"].is_string(),
+            "{}",
+            response
+        );
+
+        let response = post_graphql_with_header(
+            &app,
+            Some(("X-Api-Key", key.clone())),
+            r#"mutation { createRoutine(name: "Never created") { id } }"#,
+        )
+        .await;
+        assert_eq!(response["errors"][0]["extensions"]["code"], "INVALID_TOKEN");
+    });
+}
+
+#[test]
+fn api_keys_can_only_be_revoked_by_their_owner() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let app = app(&db);
+        let mut tokens = Vec::new();
+        for email in &["owner@example.com", "other@example.com"] {
+            let response = post_graphql(
+                &app,
+                None,
+                &format!(
+                    r#"mutation {{ register(email: "{}", password: "long enough") {{ token }} }}"#,
+                    email
+                ),
+            )
+            .await;
+            tokens.push(
+                response["data"]["register"]["token"]
+                    .as_str()
+                    .unwrap()
+                    .to_owned(),
+            );
+        }
+
+        let response = post_graphql(
+            &app,
+            Some(&tokens[0]),
+            r#"mutation { createApiKey(name: "Cron") { apiKey { id } } }"#,
+        )
+        .await;
+        let key_id = response["data"]["createApiKey"]["apiKey"]["id"].clone();
+
+        let response = post_graphql(
+            &app,
+            Some(&tokens[1]),
+            &format!("mutation {{ revokeApiKey(id: {}) {{ id }} }}", key_id),
+        )
+        .await;
+        assert_eq!(response["errors"][0]["extensions"]["code"], "NOT_FOUND");
+
+        let response = post_graphql(&app, Some(&tokens[1]), "{ viewer { apiKeys { id } } }").await;
+        assert_eq!(response["data"]["viewer"]["apiKeys"], json!([]));
+    });
+}
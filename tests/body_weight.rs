@@ -0,0 +1,145 @@
+use async_std::task;
+use sqlx::{Pool, Postgres};
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+async fn connect() -> Pool<Postgres> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    Pool::connect(&database_url).await.unwrap()
+}
+
+async fn insert_user(postgres_pool: &Pool<Postgres>) -> i32 {
+    let suffix = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let (user_id,): (i32,) = sqlx::query_as("INSERT INTO users (email) VALUES ($1) RETURNING id")
+        .bind(format!("scale{}@example.com", suffix))
+        .fetch_one(postgres_pool)
+        .await
+        .unwrap();
+
+    user_id
+}
+
+#[test]
+fn body_weight_entries_are_listed_newest_first() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let user_id = insert_user(&postgres_pool).await;
+        let schema = fit::build_schema(postgres_pool);
+        let execute = |query: &str| {
+            schema.execute(
+                async_graphql::Request::new(query).data(fit::AuthenticatedUser { id: user_id }),
+            )
+        };
+
+        for (weight_kg, measured_at) in &[
+            (81.5, "2021-12-01T07:00:00Z"),
+            (81.0, "2021-12-02T07:00:00Z"),
+            (80.8, "2021-12-02T07:00:00Z"),
+            (80.5, "2021-12-03T07:00:00Z"),
+        ] {
+            let response = execute(&format!(
+                r#"mutation {{ logBodyWeight(weight: {}, measuredAt: "{}", notes: "morning") {{ id }} }}"#,
+                weight_kg, measured_at
+            ))
+            .await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+        }
+
+        let response = execute(
+            r#"{ bodyWeightEntries(fromDate: "2021-12-02T00:00:00Z", toDate: "2021-12-02T23:59:59Z") { weightKg notes }
+                 latestBodyWeight { weightKg } }"#,
+        )
+        .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            r#"{bodyWeightEntries: [{weightKg: 80.8,notes: "morning"},{weightKg: 81,notes: "morning"}],latestBodyWeight: {weightKg: 80.5}}"#
+        );
+    });
+}
+
+#[test]
+fn log_body_weight_rejects_implausible_weights() {
+    task::block_on(async {
+        let response = fit::build_schema(connect().await)
+            .execute("mutation { logBodyWeight(weight: 800) { id } }")
+            .await;
+
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "VALIDATION");
+        assert_eq!(errors[0]["extensions"]["field"], "weight");
+    });
+}
+
+#[test]
+fn delete_body_weight_entry_removes_only_the_users_own_entries() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let owner = insert_user(&postgres_pool).await;
+        let other = insert_user(&postgres_pool).await;
+        let schema = fit::build_schema(postgres_pool);
+        let execute = |user_id: i32, query: String| {
+            schema.execute(
+                async_graphql::Request::new(query).data(fit::AuthenticatedUser { id: user_id }),
+            )
+        };
+
+        let response = execute(
+            owner,
+            "mutation { logBodyWeight(weight: 70) { id } }".to_owned(),
+        )
+        .await;
+        let id = response.data.into_json().unwrap()["logBodyWeight"]["id"].clone();
+        let delete = format!(
+            "mutation {{ deleteBodyWeightEntry(id: {}) {{ weightKg }} }}",
+            id
+        );
+
+        let response = execute(other, delete.clone()).await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "NOT_FOUND");
+
+        let response = execute(owner, delete).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            "{deleteBodyWeightEntry: {weightKg: 70}}"
+        );
+    });
+}
+
+#[test]
+fn body_weights_round_trip_through_pounds() {
+    task::block_on(async {
+        let response = fit::build_schema(connect().await)
+            .execute(
+                r#"mutation { logBodyWeight(weight: 500, unit: LB) {
+                    pounds: weight(unit: LB) kilograms: weight weightKg } }"#,
+            )
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let entry = &response.data.into_json().unwrap()["logBodyWeight"];
+        assert_eq!(entry["pounds"], 500.0);
+        assert_eq!(entry["kilograms"], 226.8);
+        assert!((entry["weightKg"].as_f64().unwrap() - 226.796_185).abs() < 1e-9);
+    });
+}
+
+#[test]
+fn log_body_weight_checks_the_range_after_converting_to_kilograms() {
+    task::block_on(async {
+        // 1,000 lb is about 454 kg, inside the range even though 1,000 kg
+        // isn't.
+        let response = fit::build_schema(connect().await)
+            .execute("mutation { logBodyWeight(weight: 1000, unit: LB) { weight(unit: LB) } }")
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.to_string(), "{logBodyWeight: {weight: 1000}}");
+    });
+}
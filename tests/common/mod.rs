@@ -0,0 +1,116 @@
+//! Shared setup for integration tests that want a database of their own.
+//!
+//! Each `TestDb` creates and migrates a fresh database on the server
+//! `DATABASE_URL` points at, so a test starts from empty tables and can't see
+//! rows written by tests running alongside it. The database is dropped when
+//! the `TestDb` is.
+//!
+//! A database rather than a schema keeps the Postgres enum types unambiguous:
+//! sqlx looks custom types up by name alone.
+
+// Each test binary compiles this module and uses a different part of it.
+#![allow(dead_code)]
+
+use async_std::task;
+use sqlx::{Connection, Executor, PgConnection, Pool, Postgres};
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tide::http::Url;
+
+static DATABASE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+pub struct TestDb {
+    pub pool: Pool<Postgres>,
+    pub schema: fit::AppSchema,
+    server_url: String,
+    name: String,
+}
+
+impl TestDb {
+    pub async fn new() -> Self {
+        let server_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let name = format!(
+            "test_{}_{}_{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos(),
+            DATABASE_COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let mut conn = PgConnection::connect(&server_url).await.unwrap();
+        conn.execute(format!("CREATE DATABASE {}", name).as_str())
+            .await
+            .unwrap();
+        conn.close().await.unwrap();
+
+        let mut database_url = Url::parse(&server_url).unwrap();
+        database_url.set_path(&name);
+        let pool = Pool::connect(database_url.as_str()).await.unwrap();
+        fit::migrate(&pool).await.unwrap();
+
+        Self {
+            schema: fit::build_schema(pool.clone()),
+            pool,
+            server_url,
+            name,
+        }
+    }
+
+    /// Executes a GraphQL request as an anonymous client.
+    pub async fn execute(&self, query: impl Into<String>) -> async_graphql::Response {
+        self.schema
+            .execute(async_graphql::Request::new(query.into()))
+            .await
+    }
+
+    /// Executes a GraphQL request signed in as `user_id`.
+    pub async fn execute_as(
+        &self,
+        user_id: i32,
+        query: impl Into<String>,
+    ) -> async_graphql::Response {
+        self.schema
+            .execute(
+                async_graphql::Request::new(query.into())
+                    .data(fit::AuthenticatedUser { id: user_id }),
+            )
+            .await
+    }
+
+    /// Inserts a user with `email`, returning their id.
+    pub async fn insert_user(&self, email: &str) -> i32 {
+        let (user_id,): (i32,) =
+            sqlx::query_as("INSERT INTO users (email) VALUES ($1) RETURNING id")
+                .bind(email)
+                .fetch_one(&self.pool)
+                .await
+                .unwrap();
+
+        user_id
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let pool = self.pool.clone();
+        let server_url = self.server_url.clone();
+        let drop_database = format!("DROP DATABASE {} WITH (FORCE)", self.name);
+
+        // Tests already run inside `task::block_on`, so the database is
+        // dropped from a thread of its own.
+        std::thread::spawn(move || {
+            task::block_on(async {
+                pool.close().await;
+                let mut conn = PgConnection::connect(&server_url).await?;
+                conn.execute(drop_database.as_str()).await?;
+                conn.close().await
+            })
+        })
+        .join()
+        .unwrap()
+        .unwrap();
+    }
+}
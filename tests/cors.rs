@@ -0,0 +1,129 @@
+use async_std::task;
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+use std::env;
+use tide::http::{Method, Request, Response, StatusCode, Url};
+
+async fn app(allowed_origins: &str) -> tide::Server<()> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    let config = fit::Config::from_vars(|name| match name {
+        "DATABASE_URL" => Some(database_url.clone()),
+        "CORS_ALLOWED_ORIGINS" => Some(allowed_origins.to_owned()),
+        "CORS_MAX_AGE_SECS" => Some("3600".to_owned()),
+        _ => None,
+    })
+    .unwrap();
+    let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+
+    fit::app(&config, postgres_pool)
+}
+
+async fn preflight(app: &tide::Server<()>, origin: &str) -> Response {
+    let mut req = Request::new(
+        Method::Options,
+        Url::parse("http://localhost/graphql").unwrap(),
+    );
+    req.insert_header("Origin", origin);
+    req.insert_header("Access-Control-Request-Method", "POST");
+    req.insert_header(
+        "Access-Control-Request-Headers",
+        "authorization, content-type",
+    );
+
+    app.respond(req).await.unwrap()
+}
+
+async fn post_graphql(app: &tide::Server<()>, origin: &str) -> Response {
+    let mut req = Request::new(
+        Method::Post,
+        Url::parse("http://localhost/graphql").unwrap(),
+    );
+    req.insert_header("Origin", origin);
+    req.set_body(json!({ "query": "{ exerciseCount }" }));
+
+    app.respond(req).await.unwrap()
+}
+
+fn header(res: &Response, name: &str) -> Option<String> {
+    res.header(name).map(|values| values.as_str().to_owned())
+}
+
+#[test]
+fn allowed_origins_get_cors_headers() {
+    task::block_on(async {
+        let app = app("http://localhost:3000, https://fit.example").await;
+
+        let res = preflight(&app, "https://fit.example").await;
+        assert_eq!(res.status(), StatusCode::NoContent);
+        assert_eq!(
+            header(&res, "Access-Control-Allow-Origin").as_deref(),
+            Some("https://fit.example")
+        );
+        assert_eq!(
+            header(&res, "Access-Control-Allow-Methods").as_deref(),
+            Some("GET, POST, OPTIONS")
+        );
+        let allowed_headers = header(&res, "Access-Control-Allow-Headers").unwrap();
+        assert!(
+            allowed_headers.contains("Authorization"),
+            "{}",
+            allowed_headers
+        );
+        assert!(
+            allowed_headers.contains("Content-Type"),
+            "{}",
+            allowed_headers
+        );
+        assert_eq!(
+            header(&res, "Access-Control-Max-Age").as_deref(),
+            Some("3600")
+        );
+        assert_eq!(header(&res, "Vary").as_deref(), Some("Origin"));
+
+        let res = post_graphql(&app, "http://localhost:3000").await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(
+            header(&res, "Access-Control-Allow-Origin").as_deref(),
+            Some("http://localhost:3000")
+        );
+        assert_eq!(header(&res, "Vary").as_deref(), Some("Origin"));
+    });
+}
+
+#[test]
+fn other_origins_get_no_allow_origin_header() {
+    task::block_on(async {
+        let app = app("https://fit.example").await;
+
+        let res = preflight(&app, "https://evil.example").await;
+        assert_eq!(res.status(), StatusCode::NoContent);
+        assert_eq!(header(&res, "Access-Control-Allow-Origin"), None);
+        assert_eq!(header(&res, "Access-Control-Allow-Methods"), None);
+
+        let res = post_graphql(&app, "https://evil.example").await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(header(&res, "Access-Control-Allow-Origin"), None);
+    });
+}
+
+#[test]
+fn a_wildcard_allows_any_origin() {
+    task::block_on(async {
+        let app = app("*").await;
+
+        let res = preflight(&app, "https://anywhere.example").await;
+        assert_eq!(res.status(), StatusCode::NoContent);
+        assert_eq!(
+            header(&res, "Access-Control-Allow-Origin").as_deref(),
+            Some("*")
+        );
+        assert_eq!(header(&res, "Vary"), None);
+
+        let res = post_graphql(&app, "https://anywhere.example").await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(
+            header(&res, "Access-Control-Allow-Origin").as_deref(),
+            Some("*")
+        );
+    });
+}
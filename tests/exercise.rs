@@ -0,0 +1,389 @@
+use async_std::task;
+use sqlx::{Pool, Postgres};
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+async fn connect() -> Pool<Postgres> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    Pool::connect(&database_url).await.unwrap()
+}
+
+fn unique_suffix() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos()
+}
+
+#[test]
+fn exercises_can_be_filtered_by_muscle_group() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = unique_suffix();
+        let (muscle_id,): (i32,) =
+            sqlx::query_as("INSERT INTO muscles (name) VALUES ($1) RETURNING id")
+                .bind(format!("Muscle {}", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+        let schema = fit::build_schema(postgres_pool);
+
+        for (name, muscle_group) in &[("Dip", "CHEST"), ("Squat", "LEGS")] {
+            let response = schema
+                .execute(format!(
+                    r#"mutation {{ createExercise(name: "{} {}", mainMuscleWorkedId: {}, muscleGroup: {}) {{ muscleGroup }} }}"#,
+                    name, suffix, muscle_id, muscle_group
+                ))
+                .await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+        }
+
+        let response = schema
+            .execute(format!(
+                r#"{{ exercises(nameContains: "{}", muscleGroup: LEGS) {{ edges {{ node {{ name muscleGroup }} }} }} }}"#,
+                suffix
+            ))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                r#"{{exercises: {{edges: [{{node: {{name: "Squat {}",muscleGroup: LEGS}}}}]}}}}"#,
+                suffix
+            )
+        );
+    });
+}
+
+#[test]
+fn rejects_an_unknown_muscle_group() {
+    task::block_on(async {
+        let response = fit::build_schema(connect().await)
+            .execute("{ exercises(muscleGroup: NECK) { edges { node { id } } } }")
+            .await;
+
+        assert_eq!(response.errors.len(), 1);
+        assert!(
+            response.errors[0].message.contains("NECK"),
+            "{}",
+            response.errors[0].message
+        );
+    });
+}
+
+#[test]
+fn exercises_can_be_filtered_by_any_of_several_equipment() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = unique_suffix();
+        let (muscle_id,): (i32,) =
+            sqlx::query_as("INSERT INTO muscles (name) VALUES ($1) RETURNING id")
+                .bind(format!("Muscle {}", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+        let schema = fit::build_schema(postgres_pool);
+
+        for (name, muscle_group, equipment) in &[
+            ("Barbell Squat", "LEGS", "BARBELL"),
+            ("Goblet Squat", "LEGS", "KETTLEBELL"),
+            ("Leg Press", "LEGS", "MACHINE"),
+            ("Floor Press", "CHEST", "BARBELL"),
+        ] {
+            let response = schema
+                .execute(format!(
+                    r#"mutation {{ createExercise(name: "{} {}", mainMuscleWorkedId: {}, muscleGroup: {}, equipment: {}) {{ id }} }}"#,
+                    name, suffix, muscle_id, muscle_group, equipment
+                ))
+                .await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+        }
+
+        let names = |filters: &str| {
+            let query = format!(
+                r#"{{ exercises(nameContains: "{}", orderBy: NAME_ASC, {}) {{ edges {{ node {{ name }} }} }} }}"#,
+                suffix, filters
+            );
+            let schema = schema.clone();
+            async move {
+                let response = schema.execute(query).await;
+                assert!(response.errors.is_empty(), "{:?}", response.errors);
+                response.data.into_json().unwrap()["exercises"]["edges"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|edge| {
+                        let name = edge["node"]["name"].as_str().unwrap();
+                        name.trim_end_matches(&format!(" {}", suffix)).to_owned()
+                    })
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(
+            names("equipment: [BARBELL, KETTLEBELL]").await,
+            vec!["Barbell Squat", "Floor Press", "Goblet Squat"]
+        );
+        assert_eq!(
+            names("equipment: [BARBELL, KETTLEBELL], muscleGroup: LEGS").await,
+            vec!["Barbell Squat", "Goblet Squat"]
+        );
+        assert_eq!(names("equipment: []").await.len(), 4);
+    });
+}
+
+#[test]
+fn search_matches_exercise_aliases_and_aliases_must_be_unique() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = unique_suffix();
+        let (muscle_id,): (i32,) =
+            sqlx::query_as("INSERT INTO muscles (name) VALUES ($1) RETURNING id")
+                .bind(format!("Muscle {}", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+        let mut ids = Vec::new();
+        for name in &["Romanian Deadlift", "Conventional Deadlift"] {
+            let (id,): (i32,) = sqlx::query_as(
+                "INSERT INTO exercises (name, main_muscle_worked_id) VALUES ($1, $2) RETURNING id",
+            )
+            .bind(format!("{} {}", name, suffix))
+            .bind(muscle_id)
+            .fetch_one(&postgres_pool)
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+        let schema = fit::build_schema(postgres_pool);
+
+        for alias in &["RDL", "Stiff-Leg"] {
+            let response = schema
+                .execute(format!(
+                    r#"mutation {{ addExerciseAlias(exerciseId: {}, alias: "{} {}") {{ id }} }}"#,
+                    ids[0], alias, suffix
+                ))
+                .await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+        }
+
+        let response = schema
+            .execute(format!(
+                r#"{{ exercises(search: "rdl {}") {{ edges {{ node {{ id aliases }} }} }} }}"#,
+                suffix
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let edges = response.data.into_json().unwrap()["exercises"]["edges"].clone();
+        assert_eq!(
+            edges[0]["node"],
+            serde_json::json!({
+                "id": ids[0],
+                "aliases": [format!("RDL {}", suffix), format!("Stiff-Leg {}", suffix)],
+            })
+        );
+        let matches = edges
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|edge| edge["node"]["id"] == ids[0])
+            .count();
+        assert_eq!(matches, 1);
+
+        let response = schema
+            .execute(format!(
+                r#"mutation {{ addExerciseAlias(exerciseId: {}, alias: "romanian deadlift {}") {{ id }} }}"#,
+                ids[1], suffix
+            ))
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "CONFLICT");
+        assert_eq!(
+            errors[0]["message"],
+            format!(
+                r#"Alias "romanian deadlift {0}" is already used by exercise {1} (Romanian Deadlift {0})"#,
+                suffix, ids[0]
+            )
+        );
+    });
+}
+
+#[test]
+fn update_exercise_reports_a_taken_name_as_a_conflict() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = unique_suffix();
+        let (muscle_id,): (i32,) =
+            sqlx::query_as("INSERT INTO muscles (name) VALUES ($1) RETURNING id")
+                .bind(format!("Muscle {}", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+        let mut ids = Vec::new();
+        for name in &["Chin Up", "Pull Up"] {
+            let (id,): (i32,) = sqlx::query_as(
+                "INSERT INTO exercises (name, main_muscle_worked_id) VALUES ($1, $2) RETURNING id",
+            )
+            .bind(format!("{} {}", name, suffix))
+            .bind(muscle_id)
+            .fetch_one(&postgres_pool)
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+
+        let response = fit::build_schema(postgres_pool)
+            .execute(format!(
+                r#"mutation {{ updateExercise(id: {}, name: "Pull Up {}") {{ id }} }}"#,
+                ids[0], suffix
+            ))
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+
+        assert_eq!(errors[0]["extensions"]["code"], "CONFLICT");
+        assert_eq!(
+            errors[0]["message"],
+            format!(r#"An exercise named "Pull Up {}" already exists"#, suffix)
+        );
+    });
+}
+
+#[test]
+fn create_exercises_returns_the_exercises_in_input_order() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = unique_suffix();
+        let (muscle_id,): (i32,) =
+            sqlx::query_as("INSERT INTO muscles (name) VALUES ($1) RETURNING id")
+                .bind(format!("Muscle {}", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+
+        let response = fit::build_schema(postgres_pool)
+            .execute(format!(
+                r#"mutation {{ createExercises(inputs: [
+                    {{ name: "Squat {0}", mainMuscleWorkedId: {1}, muscleGroup: LEGS, equipment: BARBELL }},
+                    {{ name: " Dip {0} ", mainMuscleWorkedId: {1} }}
+                ]) {{ name muscleGroup equipment }} }}"#,
+                suffix, muscle_id
+            ))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                r#"{{createExercises: [{{name: "Squat {0}",muscleGroup: LEGS,equipment: BARBELL}},{{name: "Dip {0}",muscleGroup: OTHER,equipment: OTHER}}]}}"#,
+                suffix
+            )
+        );
+    });
+}
+
+#[test]
+fn create_exercises_creates_nothing_when_a_name_is_taken() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = unique_suffix();
+        let (muscle_id,): (i32,) =
+            sqlx::query_as("INSERT INTO muscles (name) VALUES ($1) RETURNING id")
+                .bind(format!("Muscle {}", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+        sqlx::query("INSERT INTO exercises (name, main_muscle_worked_id) VALUES ($1, $2)")
+            .bind(format!("Taken {}", suffix))
+            .bind(muscle_id)
+            .execute(&postgres_pool)
+            .await
+            .unwrap();
+        let schema = fit::build_schema(postgres_pool.clone());
+
+        let response = schema
+            .execute(format!(
+                r#"mutation {{ createExercises(inputs: [
+                    {{ name: "New {0}", mainMuscleWorkedId: {1} }},
+                    {{ name: "Taken {0}", mainMuscleWorkedId: {1} }}
+                ]) {{ id }} }}"#,
+                suffix, muscle_id
+            ))
+            .await;
+
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "CONFLICT");
+        assert_eq!(errors[0]["extensions"]["field"], "inputs.1.name");
+        assert_eq!(
+            errors[0]["message"],
+            format!(
+                r#"inputs[1]: An exercise named "Taken {}" already exists"#,
+                suffix
+            )
+        );
+
+        let response = schema
+            .execute(format!(
+                r#"mutation {{ createExercises(inputs: [
+                    {{ name: "Twice {0}", mainMuscleWorkedId: {1} }},
+                    {{ name: "Twice {0}", mainMuscleWorkedId: {1} }}
+                ]) {{ id }} }}"#,
+                suffix, muscle_id
+            ))
+            .await;
+
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["field"], "inputs.1.name");
+
+        let (created,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM exercises WHERE name IN ($1, $2)")
+                .bind(format!("New {}", suffix))
+                .bind(format!("Twice {}", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+        assert_eq!(created, 0);
+    });
+}
+
+#[test]
+fn exercises_by_ids_returns_the_existing_exercises_in_order() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = unique_suffix();
+        let (muscle_id,): (i32,) =
+            sqlx::query_as("INSERT INTO muscles (name) VALUES ($1) RETURNING id")
+                .bind(format!("Muscle {}", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+        let mut ids = Vec::new();
+        for name in &["Row", "Curl"] {
+            let (id,): (i32,) = sqlx::query_as(
+                "INSERT INTO exercises (name, main_muscle_worked_id) VALUES ($1, $2) RETURNING id",
+            )
+            .bind(format!("{} {}", name, suffix))
+            .bind(muscle_id)
+            .fetch_one(&postgres_pool)
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+
+        let response = fit::build_schema(postgres_pool)
+            .execute(format!(
+                "{{ exercisesByIds(ids: [{}, -1, {}]) {{ id }} }}",
+                ids[1], ids[0]
+            ))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                "{{exercisesByIds: [{{id: {}}},{{id: {}}}]}}",
+                ids[1], ids[0]
+            )
+        );
+    });
+}
@@ -0,0 +1,194 @@
+use async_std::task;
+use log::{Log, Metadata, Record};
+use sqlx::{Pool, Postgres};
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, Once};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod common;
+
+use common::TestDb;
+
+/// Counts the statements sqlx logs that read from `exercises`, and those
+/// that look up the routines exercises are part of.
+struct ExerciseQueryCounter;
+
+static EXERCISE_QUERIES: AtomicUsize = AtomicUsize::new(0);
+static EXERCISE_ROUTINE_QUERIES: AtomicUsize = AtomicUsize::new(0);
+static COUNTER: ExerciseQueryCounter = ExerciseQueryCounter;
+static INSTALL_COUNTER: Once = Once::new();
+
+/// Held by each test so that one test's statements aren't counted by another.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+impl Log for ExerciseQueryCounter {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if record.target() != "sqlx::query" {
+            return;
+        }
+
+        let statement = record.args().to_string();
+        if statement.contains("exercises") {
+            EXERCISE_QUERIES.fetch_add(1, Ordering::SeqCst);
+        }
+        if statement.contains("routine_exercises.exercise_id = ANY") {
+            EXERCISE_ROUTINE_QUERIES.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn install_counter() {
+    INSTALL_COUNTER.call_once(|| {
+        log::set_logger(&COUNTER).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+    });
+}
+
+#[test]
+fn aliased_exercise_fields_are_loaded_in_one_statement() {
+    install_counter();
+    let _serial = SERIAL.lock().unwrap_or_else(|error| error.into_inner());
+
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+        let schema = fit::build_schema(postgres_pool.clone());
+
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let (muscle_id,): (i32,) =
+            sqlx::query_as("INSERT INTO muscles (name) VALUES ($1) RETURNING id")
+                .bind(format!("Muscle {}", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+
+        let mut ids = Vec::new();
+        for name in &["Squat", "Bench Press", "Deadlift"] {
+            let (id,): (i32,) = sqlx::query_as(
+                "INSERT INTO exercises (name, main_muscle_worked_id) VALUES ($1, $2) RETURNING id",
+            )
+            .bind(format!("{} {}", name, suffix))
+            .bind(muscle_id)
+            .fetch_one(&postgres_pool)
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+
+        let query = format!(
+            "{{ a: exercise(id: {}) {{ id }} b: exercise(id: {}) {{ id }} c: exercise(id: {}) {{ id }} }}",
+            ids[0], ids[1], ids[2]
+        );
+
+        let before = EXERCISE_QUERIES.load(Ordering::SeqCst);
+        let response = schema.execute(query).await;
+        let statements = EXERCISE_QUERIES.load(Ordering::SeqCst) - before;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                "{{a: {{id: {}}},b: {{id: {}}},c: {{id: {}}}}}",
+                ids[0], ids[1], ids[2]
+            )
+        );
+        assert_eq!(statements, 1);
+    });
+}
+
+#[test]
+fn exercise_routines_are_loaded_in_one_statement() {
+    install_counter();
+    let _serial = SERIAL.lock().unwrap_or_else(|error| error.into_inner());
+
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let (muscle_id,): (i32,) =
+            sqlx::query_as("INSERT INTO muscles (name) VALUES ('Legs') RETURNING id")
+                .fetch_one(&db.pool)
+                .await
+                .unwrap();
+        let mut exercise_ids = Vec::new();
+        for name in &["Squat", "Lunge", "Calf Raise"] {
+            let (id,): (i32,) = sqlx::query_as(
+                "INSERT INTO exercises (name, main_muscle_worked_id) VALUES ($1, $2) RETURNING id",
+            )
+            .bind(name)
+            .bind(muscle_id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+            exercise_ids.push(id);
+        }
+        // Leg Day has every exercise, Quads only the first two, and the
+        // deleted routine is left out.
+        for (name, count, deleted) in
+            &[("Leg Day", 3, false), ("Quads", 2, false), ("Old", 3, true)]
+        {
+            let (routine_id,): (i32,) = sqlx::query_as(
+                "INSERT INTO routines (name, deleted_at) VALUES ($1, CASE WHEN $2 THEN now() END) RETURNING id",
+            )
+            .bind(name)
+            .bind(deleted)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+            for (position, exercise_id) in exercise_ids.iter().take(*count).enumerate() {
+                sqlx::query(
+                    "INSERT INTO routine_exercises (routine_id, exercise_id, position) VALUES ($1, $2, $3)",
+                )
+                .bind(routine_id)
+                .bind(exercise_id)
+                .bind(position as i32)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+            }
+        }
+
+        let before = EXERCISE_ROUTINE_QUERIES.load(Ordering::SeqCst);
+        let response = db
+            .execute(
+                "{ exercises(orderBy: NAME_ASC) { edges { node { name routines { name } } } } }",
+            )
+            .await;
+        let statements = EXERCISE_ROUTINE_QUERIES.load(Ordering::SeqCst) - before;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            concat!(
+                r#"{exercises: {edges: ["#,
+                r#"{node: {name: "Calf Raise",routines: [{name: "Leg Day"}]}},"#,
+                r#"{node: {name: "Lunge",routines: [{name: "Leg Day"},{name: "Quads"}]}},"#,
+                r#"{node: {name: "Squat",routines: [{name: "Leg Day"},{name: "Quads"}]}}"#,
+                r#"]}}"#,
+            )
+        );
+        assert_eq!(statements, 1);
+
+        // The routines have no owner, so a signed-in user sees none of them.
+        let user_id = db.insert_user("lifter@example.com").await;
+        let response = db
+            .execute_as(
+                user_id,
+                format!(
+                    "{{ exercise(id: {}) {{ routines {{ name }} }} }}",
+                    exercise_ids[0]
+                ),
+            )
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.to_string(), "{exercise: {routines: []}}");
+    });
+}
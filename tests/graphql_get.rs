@@ -0,0 +1,96 @@
+use async_std::task;
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+use std::env;
+use tide::http::{Method, Request, Response, StatusCode, Url};
+
+async fn app() -> tide::Server<()> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    let config = fit::Config::from_vars(|name| match name {
+        "DATABASE_URL" => Some(database_url.clone()),
+        _ => None,
+    })
+    .unwrap();
+    let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+
+    fit::app(&config, postgres_pool)
+}
+
+async fn get_graphql(app: &tide::Server<()>, params: &[(&str, &str)]) -> (StatusCode, Value) {
+    let mut url = Url::parse("http://localhost/graphql").unwrap();
+    url.query_pairs_mut().extend_pairs(params);
+
+    let mut res: Response = app.respond(Request::new(Method::Get, url)).await.unwrap();
+    (res.status(), res.body_json().await.unwrap())
+}
+
+#[test]
+fn queries_can_be_sent_in_the_query_string() {
+    task::block_on(async {
+        let app = app().await;
+
+        let (status, body) = get_graphql(
+            &app,
+            &[
+                (
+                    "query",
+                    "query Count { exerciseCount } query Search($term: String!) { search(query: $term) { __typename } }",
+                ),
+                ("operationName", "Search"),
+                ("variables", r#"{"term": "no such exercise or routine"}"#),
+            ],
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::Ok);
+        assert_eq!(body, json!({ "data": { "search": [] } }));
+    });
+}
+
+#[test]
+fn mutations_are_refused_over_get() {
+    task::block_on(async {
+        let app = app().await;
+
+        for params in &[
+            vec![(
+                "query",
+                r#"mutation { createRoutine(name: "Over GET") { id } }"#,
+            )],
+            vec![
+                (
+                    "query",
+                    r#"query Count { exerciseCount } mutation Create { createRoutine(name: "Over GET") { id } }"#,
+                ),
+                ("operationName", "Create"),
+            ],
+        ] {
+            let (status, body) = get_graphql(&app, params).await;
+
+            assert_eq!(status, StatusCode::MethodNotAllowed);
+            assert!(body["errors"][0]["message"].is_string(), "{}", body);
+        }
+    });
+}
+
+#[test]
+fn malformed_variables_are_a_bad_request() {
+    task::block_on(async {
+        let app = app().await;
+
+        for variables in &["{not json", "[1, 2]"] {
+            let (status, body) = get_graphql(
+                &app,
+                &[("query", "{ exerciseCount }"), ("variables", variables)],
+            )
+            .await;
+
+            assert_eq!(status, StatusCode::BadRequest);
+            let message = body["errors"][0]["message"].as_str().unwrap();
+            assert!(message.contains("`variables`"), "{}", message);
+        }
+
+        let (status, _) = get_graphql(&app, &[]).await;
+        assert_eq!(status, StatusCode::BadRequest);
+    });
+}
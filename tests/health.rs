@@ -0,0 +1,19 @@
+use async_std::task;
+use sqlx::{Pool, Postgres};
+use std::env;
+use tide::StatusCode;
+
+#[test]
+fn health_reports_unavailable_database_when_the_pool_is_closed() {
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+        postgres_pool.close().await;
+
+        let mut response = fit::health(&postgres_pool).await;
+
+        assert_eq!(response.status(), StatusCode::ServiceUnavailable);
+        let body: serde_json::Value = response.take_body().into_json().await.unwrap();
+        assert_eq!(body["database"], "unavailable");
+    });
+}
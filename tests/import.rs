@@ -0,0 +1,71 @@
+use async_std::task;
+use sqlx::{Pool, Postgres};
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tide::http::{Method, Request, Response, StatusCode, Url};
+
+#[test]
+fn import_exercises_skips_duplicates_and_reports_bad_rows() {
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let muscle = format!("Hamstrings {}", suffix);
+        sqlx::query("INSERT INTO muscles (name) VALUES ($1)")
+            .bind(&muscle)
+            .execute(&postgres_pool)
+            .await
+            .unwrap();
+
+        let csv = format!(
+            "name,main_muscle_worked\nRDL {s},{m}\nRDL {s},{m}\n,{m}\nNordic {s},Unknown\n",
+            s = suffix,
+            m = muscle
+        );
+        let summary = fit::import_exercises(&postgres_pool, &csv).await.unwrap();
+
+        let summary = serde_json::to_value(&summary).unwrap();
+        assert_eq!(summary["inserted"], 1);
+        assert_eq!(summary["skipped"], 1);
+        assert_eq!(summary["errors"][0]["line"], 4);
+        assert_eq!(summary["errors"][1]["line"], 5);
+    });
+}
+
+#[test]
+fn oversized_imports_are_refused() {
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let config = fit::Config::from_vars(|name| match name {
+            "DATABASE_URL" => Some(database_url.clone()),
+            "UPLOAD_MAX_BODY_BYTES" => Some("64".to_owned()),
+            _ => None,
+        })
+        .unwrap();
+        let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+        let app = fit::app(&config, postgres_pool);
+
+        // A header with no rows imports nothing, so only the size matters.
+        for (csv, status) in &[
+            ("name,main_muscle_worked\n".to_owned(), StatusCode::Ok),
+            (
+                format!("name,main_muscle_worked\n{}", "\n".repeat(64)),
+                StatusCode::PayloadTooLarge,
+            ),
+        ] {
+            let mut req = Request::new(
+                Method::Post,
+                Url::parse("http://localhost/import/exercises").unwrap(),
+            );
+            req.set_body(csv.as_str());
+            req.set_content_type("text/csv".into());
+            let res: Response = app.respond(req).await.unwrap();
+
+            assert_eq!(res.status(), *status);
+        }
+    });
+}
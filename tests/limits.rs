@@ -0,0 +1,52 @@
+use async_std::task;
+use sqlx::{Pool, Postgres};
+use std::env;
+
+async fn execute(query: &str) -> serde_json::Value {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+    let response = fit::build_schema(postgres_pool).execute(query).await;
+
+    serde_json::to_value(&response).unwrap()
+}
+
+#[test]
+fn rejects_queries_nested_deeper_than_the_limit() {
+    task::block_on(async {
+        let response = execute(
+            "{ __schema { types { fields { type { ofType { ofType { ofType { ofType { ofType { ofType { ofType { ofType { name } } } } } } } } } } } } }",
+        )
+        .await;
+
+        assert_eq!(
+            response["errors"][0]["extensions"]["code"],
+            "QUERY_TOO_DEEP"
+        );
+        assert_eq!(
+            response["errors"][0]["message"],
+            "Query depth 13 exceeds the maximum depth of 12"
+        );
+    });
+}
+
+#[test]
+fn rejects_pages_too_large_for_the_complexity_limit() {
+    task::block_on(async {
+        let response = execute("{ exercises(first: 10000) { edges { node { id name } } } }").await;
+
+        assert_eq!(
+            response["errors"][0]["extensions"]["code"],
+            "QUERY_TOO_COMPLEX"
+        );
+        assert!(response["data"].is_null());
+    });
+}
+
+#[test]
+fn allows_default_pages() {
+    task::block_on(async {
+        let response = execute("{ exercises { edges { node { id name } } } }").await;
+
+        assert!(response.get("errors").is_none(), "{}", response);
+    });
+}
@@ -0,0 +1,57 @@
+use async_std::task;
+use serde_json::json;
+use sqlx::{Pool, Postgres};
+use std::env;
+use tide::http::{Method, Request, Response, StatusCode, Url};
+
+async fn app() -> tide::Server<()> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    let config = fit::Config::from_vars(|name| match name {
+        "DATABASE_URL" => Some(database_url.clone()),
+        _ => None,
+    })
+    .unwrap();
+    let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+
+    fit::app(&config, postgres_pool)
+}
+
+async fn scrape(app: &tide::Server<()>) -> String {
+    let req = Request::new(Method::Get, Url::parse("http://localhost/metrics").unwrap());
+    let mut res: Response = app.respond(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::Ok);
+    res.body_string().await.unwrap()
+}
+
+#[test]
+fn metrics_count_graphql_requests_and_resolver_errors() {
+    task::block_on(async {
+        let app = app().await;
+
+        for query in [
+            "{ routines { id } }",
+            r#"mutation { createRoutine(name: "Leg day") { id } }"#,
+        ] {
+            let mut req = Request::new(
+                Method::Post,
+                Url::parse("http://localhost/graphql").unwrap(),
+            );
+            req.set_body(json!({ "query": query }));
+            let res: Response = app.respond(req).await.unwrap();
+            assert_eq!(res.status(), StatusCode::Ok);
+        }
+
+        let metrics = scrape(&app).await;
+
+        assert!(metrics.contains("graphql_requests_total 2"), "{}", metrics);
+        assert!(metrics.contains(r#"http_requests_total{method="POST",status="200"} 2"#));
+        assert!(metrics.contains(
+            r#"graphql_resolver_duration_seconds_count{operation="QueryRoot.routines"} 1"#
+        ));
+        assert!(metrics.contains(
+            r#"graphql_resolver_errors_total{operation="MutationRoot.createRoutine"} 1"#
+        ));
+        assert!(metrics.contains("db_pool_connections "));
+        assert!(metrics.contains("db_pool_idle_connections "));
+    });
+}
@@ -0,0 +1,137 @@
+use async_std::task;
+use ring::digest::{digest, SHA256};
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+use std::env;
+use tide::http::{Method, Request, Response, StatusCode, Url};
+
+async fn app() -> tide::Server<()> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    let config = fit::Config::from_vars(|name| match name {
+        "DATABASE_URL" => Some(database_url.clone()),
+        _ => None,
+    })
+    .unwrap();
+    let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+
+    fit::app(&config, postgres_pool)
+}
+
+async fn post_graphql(app: &tide::Server<()>, body: Value) -> Value {
+    let mut req = Request::new(
+        Method::Post,
+        Url::parse("http://localhost/graphql").unwrap(),
+    );
+    req.set_body(body);
+
+    let mut res: Response = app.respond(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::Ok);
+    res.body_json().await.unwrap()
+}
+
+async fn get_graphql(app: &tide::Server<()>, params: &[(&str, &str)]) -> Value {
+    let mut url = Url::parse("http://localhost/graphql").unwrap();
+    url.query_pairs_mut().extend_pairs(params);
+
+    let mut res: Response = app.respond(Request::new(Method::Get, url)).await.unwrap();
+    assert_eq!(res.status(), StatusCode::Ok);
+    res.body_json().await.unwrap()
+}
+
+fn persisted_query(query: &str) -> Value {
+    json!({ "persistedQuery": { "version": 1, "sha256Hash": sha256_hex(query) } })
+}
+
+fn sha256_hex(query: &str) -> String {
+    digest(&SHA256, query.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[test]
+fn persisted_queries_are_registered_on_a_miss_and_served_by_hash() {
+    task::block_on(async {
+        let app = app().await;
+        let query = "{ exerciseCount }";
+        let extensions = persisted_query(query);
+
+        let response = post_graphql(&app, json!({ "extensions": extensions })).await;
+        assert_eq!(response["errors"][0]["message"], "PersistedQueryNotFound");
+        assert_eq!(
+            response["errors"][0]["extensions"]["code"],
+            "PERSISTED_QUERY_NOT_FOUND"
+        );
+
+        let response =
+            post_graphql(&app, json!({ "query": query, "extensions": extensions })).await;
+        assert!(response.get("errors").is_none(), "{}", response);
+
+        let response = post_graphql(&app, json!({ "extensions": extensions })).await;
+        assert!(response.get("errors").is_none(), "{}", response);
+        assert!(response["data"]["exerciseCount"].is_number());
+    });
+}
+
+#[test]
+fn persisted_queries_whose_hash_does_not_match_are_rejected() {
+    task::block_on(async {
+        let app = app().await;
+        let query = "{ exerciseCount }";
+        let extensions = persisted_query("{ routineCount: exerciseCount }");
+
+        let response =
+            post_graphql(&app, json!({ "query": query, "extensions": extensions })).await;
+        assert_eq!(
+            response["errors"][0]["message"],
+            "provided sha does not match query"
+        );
+        assert!(response["data"].is_null(), "{}", response);
+
+        let response = post_graphql(&app, json!({ "extensions": extensions })).await;
+        assert_eq!(response["errors"][0]["message"], "PersistedQueryNotFound");
+    });
+}
+
+#[test]
+fn persisted_queries_can_be_sent_in_the_query_string() {
+    task::block_on(async {
+        let app = app().await;
+        let query = "query PersistedOverGet { exerciseCount }";
+        let extensions = persisted_query(query).to_string();
+
+        let response = get_graphql(&app, &[("extensions", &extensions)]).await;
+        assert_eq!(response["errors"][0]["message"], "PersistedQueryNotFound");
+
+        let response = get_graphql(&app, &[("query", query), ("extensions", &extensions)]).await;
+        assert!(response.get("errors").is_none(), "{}", response);
+
+        let response = get_graphql(&app, &[("extensions", &extensions)]).await;
+        assert!(response.get("errors").is_none(), "{}", response);
+        assert!(response["data"]["exerciseCount"].is_number());
+    });
+}
+
+#[test]
+fn persisted_mutations_are_refused_over_get() {
+    task::block_on(async {
+        let app = app().await;
+        let query = r#"mutation { createRoutine(name: "Persisted over GET") { id } }"#;
+        let extensions = persisted_query(query);
+
+        let response =
+            post_graphql(&app, json!({ "query": query, "extensions": extensions })).await;
+        assert_eq!(
+            response["errors"][0]["extensions"]["code"],
+            "UNAUTHENTICATED"
+        );
+
+        let response = get_graphql(&app, &[("extensions", &extensions.to_string())]).await;
+        assert_eq!(
+            response["errors"][0]["extensions"]["code"],
+            "METHOD_NOT_ALLOWED"
+        );
+        assert!(response["data"].is_null(), "{}", response);
+    });
+}
@@ -0,0 +1,61 @@
+use async_std::task;
+use sqlx::{Pool, Postgres};
+use std::env;
+use tide::http::{Method, Request, Response, StatusCode, Url};
+
+async fn app() -> tide::Server<()> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    let config = fit::Config::from_vars(|name| match name {
+        "DATABASE_URL" => Some(database_url.clone()),
+        _ => None,
+    })
+    .unwrap();
+    let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+
+    fit::app(&config, postgres_pool)
+}
+
+async fn get_playground(app: &tide::Server<()>, if_none_match: Option<&str>) -> Response {
+    let mut req = Request::new(Method::Get, Url::parse("http://localhost/").unwrap());
+    if let Some(if_none_match) = if_none_match {
+        req.insert_header("If-None-Match", if_none_match);
+    }
+
+    app.respond(req).await.unwrap()
+}
+
+#[test]
+fn the_playground_is_revalidated_with_its_etag() {
+    task::block_on(async {
+        let app = app().await;
+
+        let mut res = get_playground(&app, None).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(res.content_type().unwrap().essence(), "text/html");
+        assert_eq!(res["Cache-Control"].as_str(), "public, max-age=300");
+        let etag = res["ETag"].as_str().to_owned();
+        assert!(etag.starts_with('"') && etag.ends_with('"'), "{}", etag);
+        assert!(res
+            .body_string()
+            .await
+            .unwrap()
+            .contains("GraphQL Playground"));
+
+        for if_none_match in &[etag.clone(), format!("\"stale\", W/{}", etag)] {
+            let mut res = get_playground(&app, Some(if_none_match)).await;
+            assert_eq!(res.status(), StatusCode::NotModified);
+            assert_eq!(res["ETag"].as_str(), etag);
+            assert_eq!(res["Cache-Control"].as_str(), "public, max-age=300");
+            assert!(res.body_string().await.unwrap().is_empty());
+        }
+
+        let mut res = get_playground(&app, Some("\"stale\"")).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(res["ETag"].as_str(), etag);
+        assert!(res
+            .body_string()
+            .await
+            .unwrap()
+            .contains("GraphQL Playground"));
+    });
+}
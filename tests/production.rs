@@ -0,0 +1,66 @@
+use async_std::task;
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+use std::env;
+use tide::http::{Method, Request, Response, StatusCode, Url};
+
+async fn production_app() -> tide::Server<()> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    let config = fit::Config::from_vars(|name| match name {
+        "DATABASE_URL" => Some(database_url.clone()),
+        "APP_ENV" => Some("production".to_owned()),
+        _ => None,
+    })
+    .unwrap();
+    let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+
+    fit::app(&config, postgres_pool)
+}
+
+async fn post_graphql(app: &tide::Server<()>, query: &str) -> Value {
+    let mut req = Request::new(
+        Method::Post,
+        Url::parse("http://localhost/graphql").unwrap(),
+    );
+    req.set_body(json!({ "query": query }));
+
+    let mut res: Response = app.respond(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::Ok);
+    res.body_json().await.unwrap()
+}
+
+#[test]
+fn production_mode_rejects_introspection() {
+    task::block_on(async {
+        let app = production_app().await;
+        let response = post_graphql(&app, "{ __schema { types { name } } }").await;
+
+        assert!(response["data"].is_null());
+        assert_eq!(
+            response["errors"][0]["extensions"]["code"],
+            "INTROSPECTION_DISABLED"
+        );
+    });
+}
+
+#[test]
+fn production_mode_still_serves_queries() {
+    task::block_on(async {
+        let app = production_app().await;
+        let response = post_graphql(&app, "{ routines { id name } }").await;
+
+        assert!(response.get("errors").is_none(), "{}", response);
+        assert!(response["data"]["routines"].is_array());
+    });
+}
+
+#[test]
+fn production_mode_does_not_serve_the_playground() {
+    task::block_on(async {
+        let app = production_app().await;
+        let req = Request::new(Method::Get, Url::parse("http://localhost/").unwrap());
+
+        let res: Response = app.respond(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NotFound);
+    });
+}
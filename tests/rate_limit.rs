@@ -0,0 +1,79 @@
+use async_std::task;
+use sqlx::{Pool, Postgres};
+use std::env;
+use tide::http::{Method, Request, Response, StatusCode, Url};
+
+async fn rate_limited_app(per_minute: &str) -> tide::Server<()> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    let config = fit::Config::from_vars(|name| match name {
+        "DATABASE_URL" => Some(database_url.clone()),
+        "RATE_LIMIT_PER_MINUTE" => Some(per_minute.to_owned()),
+        _ => None,
+    })
+    .unwrap();
+    let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+
+    fit::app(&config, postgres_pool)
+}
+
+async fn get(app: &tide::Server<()>, path: &str, peer_addr: &str) -> Response {
+    let mut req = Request::new(
+        Method::Get,
+        Url::parse("http://localhost").unwrap().join(path).unwrap(),
+    );
+    req.set_peer_addr(Some(peer_addr));
+
+    app.respond(req).await.unwrap()
+}
+
+async fn get_graphql(app: &tide::Server<()>, peer_addr: &str) -> Response {
+    get(app, "/graphql?query=%7B__typename%7D", peer_addr).await
+}
+
+#[test]
+fn rejects_clients_over_the_limit_with_retry_after() {
+    task::block_on(async {
+        let app = rate_limited_app("2").await;
+
+        for _ in 0..2 {
+            let res = get_graphql(&app, "203.0.113.7:50000").await;
+            assert_eq!(res.status(), StatusCode::Ok);
+        }
+
+        let res = get_graphql(&app, "203.0.113.7:50001").await;
+        assert_eq!(res.status(), StatusCode::TooManyRequests);
+        assert_eq!(res["Retry-After"], "30");
+    });
+}
+
+#[test]
+fn limits_each_client_ip_separately() {
+    task::block_on(async {
+        let app = rate_limited_app("1").await;
+
+        let res = get_graphql(&app, "203.0.113.7:50000").await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        let res = get_graphql(&app, "203.0.113.7:50000").await;
+        assert_eq!(res.status(), StatusCode::TooManyRequests);
+
+        let res = get_graphql(&app, "198.51.100.4:50000").await;
+        assert_eq!(res.status(), StatusCode::Ok);
+    });
+}
+
+#[test]
+fn leaves_health_checks_and_metrics_unlimited() {
+    task::block_on(async {
+        let app = rate_limited_app("1").await;
+
+        for path in &["/health", "/metrics"] {
+            for _ in 0..3 {
+                let res = get(&app, path, "203.0.113.7:50000").await;
+                assert_eq!(res.status(), StatusCode::Ok, "{}", path);
+            }
+        }
+
+        let res = get_graphql(&app, "203.0.113.7:50000").await;
+        assert_eq!(res.status(), StatusCode::Ok);
+    });
+}
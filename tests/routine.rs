@@ -0,0 +1,1375 @@
+use async_std::task;
+use sqlx::{Pool, Postgres};
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod common;
+
+use common::TestDb;
+
+async fn connect() -> Pool<Postgres> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    Pool::connect(&database_url).await.unwrap()
+}
+
+/// Inserts a routine named `name` for `user_id` holding `count` new
+/// exercises, in order, returning their ids.
+async fn insert_routine_with_exercises(
+    postgres_pool: &Pool<Postgres>,
+    user_id: Option<i32>,
+    name: &str,
+    count: i32,
+) -> (i32, Vec<i32>) {
+    let (routine_id,): (i32,) =
+        sqlx::query_as("INSERT INTO routines (name, user_id) VALUES ($1, $2) RETURNING id")
+            .bind(name)
+            .bind(user_id)
+            .fetch_one(postgres_pool)
+            .await
+            .unwrap();
+    let (muscle_id,): (i32,) =
+        sqlx::query_as("INSERT INTO muscles (name) VALUES ($1) RETURNING id")
+            .bind(format!("Muscle for {}", name))
+            .fetch_one(postgres_pool)
+            .await
+            .unwrap();
+
+    let mut exercise_ids = Vec::new();
+    for position in 0..count {
+        let (exercise_id,): (i32,) = sqlx::query_as(
+            "INSERT INTO exercises (name, main_muscle_worked_id) VALUES ($1, $2) RETURNING id",
+        )
+        .bind(format!("Exercise {} for {}", position, name))
+        .bind(muscle_id)
+        .fetch_one(postgres_pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO routine_exercises (routine_id, exercise_id, position) VALUES ($1, $2, $3)",
+        )
+        .bind(routine_id)
+        .bind(exercise_id)
+        .bind(position)
+        .execute(postgres_pool)
+        .await
+        .unwrap();
+        exercise_ids.push(exercise_id);
+    }
+
+    (routine_id, exercise_ids)
+}
+
+#[test]
+fn created_routines_are_listed_for_their_owner() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+
+        let created = db
+            .execute_as(
+                user_id,
+                r#"mutation { createRoutine(name: "Push Day") { id name } }"#,
+            )
+            .await;
+        assert!(created.errors.is_empty(), "{:?}", created.errors);
+        let created = created.data.into_json().unwrap();
+
+        let listed = db
+            .execute_as(user_id, "{ routines { id name } routineCount }")
+            .await;
+        assert!(listed.errors.is_empty(), "{:?}", listed.errors);
+        assert_eq!(
+            listed.data.into_json().unwrap(),
+            serde_json::json!({
+                "routines": [created["createRoutine"]],
+                "routineCount": 1,
+            })
+        );
+    });
+}
+
+#[test]
+fn routine_returns_the_routine_for_a_hit() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let name = format!(
+            "Leg Day {}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        );
+        let (id,): (i32,) = sqlx::query_as("INSERT INTO routines (name) VALUES ($1) RETURNING id")
+            .bind(&name)
+            .fetch_one(&postgres_pool)
+            .await
+            .unwrap();
+
+        let response = fit::build_schema(postgres_pool)
+            .execute(format!("{{ routine(id: {}) {{ name }} }}", id))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(r#"{{routine: {{name: "{}"}}}}"#, name)
+        );
+    });
+}
+
+#[test]
+fn routine_returns_null_without_an_error_for_a_miss() {
+    task::block_on(async {
+        let response = fit::build_schema(connect().await)
+            .execute("{ routine(id: -1) { name } }")
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.to_string(), "{routine: null}");
+    });
+}
+
+#[test]
+fn routine_database_failures_share_a_request_id() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let schema = fit::build_schema(postgres_pool.clone());
+        postgres_pool.close().await;
+
+        let response = schema
+            .execute("{ a: routine(id: 1) { name } b: routine(id: 2) { name } }")
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+
+        assert!(!response.errors.is_empty());
+        let request_id = &errors[0]["extensions"]["requestId"];
+        assert!(request_id.is_string());
+        for error in errors.as_array().unwrap() {
+            assert_eq!(error["extensions"]["code"], "DATABASE_UNAVAILABLE");
+            assert_eq!(&error["extensions"]["requestId"], request_id);
+        }
+    });
+}
+
+#[test]
+fn update_routine_bumps_updated_at() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let (user_id,): (i32,) =
+            sqlx::query_as("INSERT INTO users (email) VALUES ($1) RETURNING id")
+                .bind(format!("lifter{}@example.com", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+        let schema = fit::build_schema(postgres_pool);
+        let user = fit::AuthenticatedUser { id: user_id };
+
+        let created = schema
+            .execute(
+                async_graphql::Request::new(format!(
+                    r#"mutation {{ createRoutine(name: "Push {}") {{ id createdAt updatedAt }} }}"#,
+                    suffix
+                ))
+                .data(user.clone()),
+            )
+            .await;
+        assert!(created.errors.is_empty(), "{:?}", created.errors);
+        let created = created.data.into_json().unwrap()["createRoutine"].clone();
+        assert_eq!(created["createdAt"], created["updatedAt"]);
+
+        let updated = schema
+            .execute(
+                async_graphql::Request::new(format!(
+                    r#"mutation {{ updateRoutine(id: {}, name: "Pull {}", expectedVersion: 1) {{ createdAt updatedAt }} }}"#,
+                    created["id"], suffix
+                ))
+                .data(user),
+            )
+            .await;
+        assert!(updated.errors.is_empty(), "{:?}", updated.errors);
+        let updated = updated.data.into_json().unwrap()["updateRoutine"].clone();
+
+        let timestamp = |value: &serde_json::Value| {
+            chrono::DateTime::parse_from_rfc3339(value.as_str().unwrap()).unwrap()
+        };
+        assert_eq!(updated["createdAt"], created["createdAt"]);
+        assert!(timestamp(&updated["updatedAt"]) > timestamp(&created["updatedAt"]));
+    });
+}
+
+#[test]
+fn update_routine_rejects_a_stale_version() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+        let (id, _) = insert_routine_with_exercises(&db.pool, Some(user_id), "Legs", 0).await;
+        let rename = |name: &str, expected_version: i32| {
+            db.execute_as(
+                user_id,
+                format!(
+                    r#"mutation {{ updateRoutine(id: {}, name: "{}", expectedVersion: {}) {{ version }} }}"#,
+                    id, name, expected_version
+                ),
+            )
+        };
+
+        let response = rename("Legs A", 1).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.to_string(), "{updateRoutine: {version: 2}}");
+
+        let response = rename("Legs B", 1).await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "STALE_VERSION");
+
+        let response = db
+            .execute_as(
+                user_id,
+                format!("{{ routine(id: {}) {{ name version }} }}", id),
+            )
+            .await;
+        assert_eq!(
+            response.data.to_string(),
+            r#"{routine: {name: "Legs A",version: 2}}"#
+        );
+    });
+}
+
+#[test]
+fn update_routine_reports_a_missing_routine() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+
+        let response = db
+            .execute_as(
+                user_id,
+                r#"mutation { updateRoutine(id: -1, name: "Nope", expectedVersion: 1) { id } }"#,
+            )
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+
+        assert_eq!(errors[0]["extensions"]["code"], "NOT_FOUND");
+    });
+}
+
+#[test]
+fn archived_routines_are_hidden_from_routines_but_still_resolve_by_id() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+        let (id, _) = insert_routine_with_exercises(&db.pool, Some(user_id), "Deload", 0).await;
+        let execute = |query: String| db.execute_as(user_id, query);
+
+        for _ in 0..2 {
+            let archived = execute(format!(
+                "mutation {{ archiveRoutine(id: {}) {{ archived }} }}",
+                id
+            ))
+            .await;
+            assert!(archived.errors.is_empty(), "{:?}", archived.errors);
+            assert_eq!(
+                archived.data.to_string(),
+                "{archiveRoutine: {archived: true}}"
+            );
+        }
+
+        let listed = |include_archived: bool| {
+            let response = execute(format!(
+                "{{ routines(includeArchived: {}) {{ id }} }}",
+                include_archived
+            ));
+            async move { response.await.data.to_string() }
+        };
+        assert_eq!(listed(false).await, "{routines: []}");
+        assert_eq!(
+            listed(true).await,
+            format!("{{routines: [{{id: {}}}]}}", id)
+        );
+
+        let response = execute(format!("{{ routine(id: {}) {{ archived }} }}", id)).await;
+        assert_eq!(response.data.to_string(), "{routine: {archived: true}}");
+
+        let unarchived = execute(format!(
+            "mutation {{ unarchiveRoutine(id: {}) {{ archived }} }}",
+            id
+        ))
+        .await;
+        assert!(unarchived.errors.is_empty(), "{:?}", unarchived.errors);
+        assert_eq!(
+            listed(false).await,
+            format!("{{routines: [{{id: {}}}]}}", id)
+        );
+    });
+}
+
+#[test]
+fn deleted_routines_are_hidden_until_restored() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+        let (id, _) = insert_routine_with_exercises(&db.pool, Some(user_id), "Retired", 2).await;
+        let execute = |query: String| {
+            let response = db.execute_as(user_id, query);
+            async move { response.await.data.to_string() }
+        };
+
+        let delete = format!("mutation {{ deleteRoutine(id: {}) }}", id);
+        assert_eq!(execute(delete.clone()).await, "{deleteRoutine: true}");
+        assert_eq!(execute(delete).await, "{deleteRoutine: false}");
+
+        let listed = |include_deleted: bool| {
+            execute(format!(
+                "{{ routines(includeDeleted: {}) {{ id }} }}",
+                include_deleted
+            ))
+        };
+        assert_eq!(listed(false).await, "{routines: []}");
+        assert_eq!(
+            listed(true).await,
+            format!("{{routines: [{{id: {}}}]}}", id)
+        );
+        assert_eq!(
+            execute(format!("{{ routine(id: {}) {{ id }} }}", id)).await,
+            "{routine: null}"
+        );
+        assert_eq!(
+            execute(format!(
+                "{{ routine(id: {}, includeDeleted: true) {{ id }} }}",
+                id
+            ))
+            .await,
+            format!("{{routine: {{id: {}}}}}", id)
+        );
+
+        assert_eq!(
+            execute(format!(
+                "mutation {{ restoreRoutine(id: {}) {{ deletedAt exercises {{ id }} }} }}",
+                id
+            ))
+            .await
+            .matches("id:")
+            .count(),
+            2
+        );
+        assert_eq!(
+            listed(false).await,
+            format!("{{routines: [{{id: {}}}]}}", id)
+        );
+    });
+}
+
+#[test]
+fn restore_routine_reports_a_missing_routine() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+
+        let response = db
+            .execute_as(user_id, "mutation { restoreRoutine(id: -1) { id } }")
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+
+        assert_eq!(errors[0]["extensions"]["code"], "NOT_FOUND");
+    });
+}
+
+#[test]
+fn archive_routine_reports_a_missing_routine() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+
+        let response = db
+            .execute_as(user_id, "mutation { archiveRoutine(id: -1) { id } }")
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+
+        assert_eq!(errors[0]["extensions"]["code"], "NOT_FOUND");
+    });
+}
+
+#[test]
+fn set_exercise_sets_replaces_the_prescribed_sets() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+        let (routine_id, exercise_ids) =
+            insert_routine_with_exercises(&db.pool, Some(user_id), "Upper", 1).await;
+        let exercise_id = exercise_ids[0];
+
+        let set_sets = |sets: &str| {
+            db.execute_as(
+                user_id,
+                format!(
+                    "mutation {{ setExerciseSets(routineId: {}, exerciseId: {}, sets: {}) {{ position }} }}",
+                    routine_id, exercise_id, sets
+                ),
+            )
+        };
+        let first = set_sets("[{ reps: 5, weightKg: 100 }, { reps: 5, weightKg: 100 }]").await;
+        assert!(first.errors.is_empty(), "{:?}", first.errors);
+        let second = set_sets("[{ reps: 8 }]").await;
+        assert!(second.errors.is_empty(), "{:?}", second.errors);
+
+        let response = db
+            .execute_as(
+                user_id,
+                format!(
+                    "{{ routine(id: {}) {{ routineExercises {{ exercise {{ id }} sets {{ setNumber reps weightKg }} }} }} }}",
+                    routine_id
+                ),
+            )
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                "{{routine: {{routineExercises: [{{exercise: {{id: {}}},sets: [{{setNumber: 1,reps: 8,weightKg: null}}]}}]}}}}",
+                exercise_id
+            )
+        );
+    });
+}
+
+#[test]
+fn set_exercise_sets_rejects_an_exercise_outside_the_routine() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+
+        let response = db
+            .execute_as(user_id, "mutation { setExerciseSets(routineId: -1, exerciseId: -1, sets: []) { position } }")
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+
+        assert_eq!(errors[0]["extensions"]["code"], "NOT_FOUND");
+    });
+}
+
+#[test]
+fn duplicate_routine_copies_exercises_and_sets_under_a_free_name() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+        let (routine_id, exercise_ids) =
+            insert_routine_with_exercises(&db.pool, Some(user_id), "Block A", 1).await;
+        let exercise_id = exercise_ids[0];
+        sqlx::query(
+            "INSERT INTO routine_exercise_sets (routine_id, exercise_id, set_number, reps) VALUES ($1, $2, 1, 10)",
+        )
+        .bind(routine_id)
+        .bind(exercise_id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        let response = db
+            .execute_as(
+                user_id,
+                format!(
+                    "mutation {{ duplicateRoutine(id: {}) {{ id name routineExercises {{ exercise {{ id }} sets {{ reps }} }} }} }}",
+                    routine_id
+                ),
+            )
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let copy = response.data.into_json().unwrap()["duplicateRoutine"].clone();
+        assert_ne!(copy["id"], routine_id);
+        assert_eq!(copy["name"], "Block A (copy)");
+        assert_eq!(
+            copy["routineExercises"],
+            serde_json::json!([{ "exercise": { "id": exercise_id }, "sets": [{ "reps": 10 }] }])
+        );
+
+        // Later copies are numbered instead of conflicting with the first.
+        for number in 2..=3 {
+            let response = db
+                .execute_as(
+                    user_id,
+                    format!(
+                        "mutation {{ duplicateRoutine(id: {}) {{ name }} }}",
+                        routine_id
+                    ),
+                )
+                .await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            assert_eq!(
+                response.data.into_json().unwrap()["duplicateRoutine"]["name"],
+                format!("Block A (copy {})", number)
+            );
+        }
+    });
+}
+
+#[test]
+fn duplicate_routine_shortens_long_names_to_fit_the_copy_suffix() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+        let name = "x".repeat(250);
+        let (routine_id, _) =
+            insert_routine_with_exercises(&db.pool, Some(user_id), &name, 0).await;
+        let duplicate = format!(
+            "mutation {{ duplicateRoutine(id: {}) {{ id name }} }}",
+            routine_id
+        );
+
+        for suffix in &[" (copy)", " (copy 2)"] {
+            let response = db.execute_as(user_id, duplicate.clone()).await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            let copy = response.data.into_json().unwrap()["duplicateRoutine"].clone();
+            let copy_name = copy["name"].as_str().unwrap();
+            assert_eq!(copy_name.chars().count(), 255);
+            assert_eq!(
+                copy_name,
+                format!("{}{}", &name[..255 - suffix.len()], suffix)
+            );
+
+            // The copy's name can be saved back as it is.
+            let response = db
+                .execute_as(
+                    user_id,
+                    format!(
+                        r#"mutation {{ updateRoutine(id: {}, name: "{}", expectedVersion: 1) {{ version }} }}"#,
+                        copy["id"], copy_name
+                    ),
+                )
+                .await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+        }
+    });
+}
+
+#[test]
+fn duplicate_routine_reports_a_missing_routine() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+
+        let response = db
+            .execute_as(
+                user_id,
+                r#"mutation { duplicateRoutine(id: -1, newName: "Copy") { id } }"#,
+            )
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+
+        assert_eq!(errors[0]["extensions"]["code"], "NOT_FOUND");
+    });
+}
+
+#[test]
+fn duplicate_routine_treats_a_deleted_routine_as_missing() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+        let (id, _) = insert_routine_with_exercises(&db.pool, Some(user_id), "Gone", 1).await;
+
+        db.execute_as(user_id, format!("mutation {{ deleteRoutine(id: {}) }}", id))
+            .await;
+        let response = db
+            .execute_as(
+                user_id,
+                format!("mutation {{ duplicateRoutine(id: {}) {{ id }} }}", id),
+            )
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+
+        assert_eq!(errors[0]["extensions"]["code"], "NOT_FOUND");
+    });
+}
+
+#[test]
+fn routines_can_be_listed_newest_first() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        for (name, created_at) in &[
+            ("Older", "2021-01-01T00:00:00Z"),
+            ("Newer", "2021-06-01T12:30:00Z"),
+        ] {
+            sqlx::query("INSERT INTO routines (name, created_at) VALUES ($1, $2::TIMESTAMPTZ)")
+                .bind(format!("{} {}", name, suffix))
+                .bind(created_at)
+                .execute(&postgres_pool)
+                .await
+                .unwrap();
+        }
+
+        let response = fit::build_schema(postgres_pool)
+            .execute(format!(
+                r#"{{ routines(nameContains: "{}", orderBy: CREATED_AT_DESC) {{ name createdAt }} }}"#,
+                suffix
+            ))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap()["routines"],
+            serde_json::json!([
+                { "name": format!("Newer {}", suffix), "createdAt": "2021-06-01T12:30:00+00:00" },
+                { "name": format!("Older {}", suffix), "createdAt": "2021-01-01T00:00:00+00:00" },
+            ])
+        );
+    });
+}
+
+#[test]
+fn reorder_routine_exercises_rewrites_positions() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+        let (routine_id, ids) =
+            insert_routine_with_exercises(&db.pool, Some(user_id), "Full Body", 3).await;
+
+        let response = db
+            .execute_as(
+                user_id,
+                format!(
+                    "mutation {{ reorderRoutineExercises(routineId: {}, exerciseIds: [{}, {}, {}]) {{ exercises {{ id }} }} }}",
+                    routine_id, ids[2], ids[0], ids[1]
+                ),
+            )
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                "{{reorderRoutineExercises: {{exercises: [{{id: {}}},{{id: {}}},{{id: {}}}]}}}}",
+                ids[2], ids[0], ids[1]
+            )
+        );
+    });
+}
+
+#[test]
+fn reorder_routine_exercises_rejects_missing_and_unexpected_ids() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+        let (routine_id, ids) =
+            insert_routine_with_exercises(&db.pool, Some(user_id), "Arms", 2).await;
+
+        let response = db
+            .execute_as(
+                user_id,
+                format!(
+                    "mutation {{ reorderRoutineExercises(routineId: {}, exerciseIds: [{}, -1]) {{ id }} }}",
+                    routine_id, ids[0]
+                ),
+            )
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+
+        assert_eq!(errors[0]["extensions"]["code"], "VALIDATION");
+        assert_eq!(errors[0]["extensions"]["field"], "exerciseIds");
+        assert_eq!(
+            errors[0]["message"],
+            format!(
+                "exerciseIds must list each of the routine's exercises once (missing: {}; not in the routine: -1)",
+                ids[1]
+            )
+        );
+    });
+}
+
+#[test]
+fn create_routines_creates_every_routine_or_none() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let (user_id,): (i32,) =
+            sqlx::query_as("INSERT INTO users (email) VALUES ($1) RETURNING id")
+                .bind(format!("program{}@example.com", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+        let schema = fit::build_schema(postgres_pool.clone());
+        let create = |names: String| {
+            schema.execute(
+                async_graphql::Request::new(format!(
+                    "mutation {{ createRoutines(names: {}) {{ name }} }}",
+                    names
+                ))
+                .data(fit::AuthenticatedUser { id: user_id }),
+            )
+        };
+
+        let created = create(format!(r#"["Day B {0}", "Day A {0}"]"#, suffix)).await;
+        assert!(created.errors.is_empty(), "{:?}", created.errors);
+        assert_eq!(
+            created.data.to_string(),
+            format!(
+                r#"{{createRoutines: [{{name: "Day B {0}"}},{{name: "Day A {0}"}}]}}"#,
+                suffix
+            )
+        );
+
+        let conflicting = create(format!(r#"["Day C {0}", "Day A {0}"]"#, suffix)).await;
+        let errors = serde_json::to_value(&conflicting.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "CONFLICT");
+
+        let (day_c,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM routines WHERE name = $1")
+            .bind(format!("Day C {}", suffix))
+            .fetch_one(&postgres_pool)
+            .await
+            .unwrap();
+        assert_eq!(day_c, 0);
+    });
+}
+
+#[test]
+fn routine_count_counts_the_users_unarchived_routines() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let (user_id,): (i32,) =
+            sqlx::query_as("INSERT INTO users (email) VALUES ($1) RETURNING id")
+                .bind(format!("counter{}@example.com", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+        for (name, archived) in &[("Active", false), ("Old", true)] {
+            sqlx::query(
+                "INSERT INTO routines (name, user_id, archived_at) VALUES ($1, $2, CASE WHEN $3 THEN now() END)",
+            )
+            .bind(format!("{} {}", name, suffix))
+            .bind(user_id)
+            .bind(archived)
+            .execute(&postgres_pool)
+            .await
+            .unwrap();
+        }
+
+        let response = fit::build_schema(postgres_pool)
+            .execute(
+                async_graphql::Request::new(
+                    "{ active: routineCount all: routineCount(includeArchived: true) }",
+                )
+                .data(fit::AuthenticatedUser { id: user_id }),
+            )
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.to_string(), "{active: 1,all: 2}");
+    });
+}
+
+#[test]
+fn create_routine_reports_a_name_the_user_already_uses_as_a_conflict() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+        let other_user_id = db.insert_user("other@example.com").await;
+        let mutation = r#"mutation { createRoutine(name: "Push Day") { id } }"#;
+
+        let first = db.execute_as(user_id, mutation).await;
+        assert!(first.errors.is_empty(), "{:?}", first.errors);
+        let second = db.execute_as(user_id, mutation).await;
+        let errors = serde_json::to_value(&second.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "CONFLICT");
+        assert_eq!(
+            errors[0]["message"],
+            r#"A routine named "Push Day" already exists"#
+        );
+
+        // Names are only unique among a user's own routines.
+        let response = db.execute_as(other_user_id, mutation).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+    });
+}
+
+#[test]
+fn routines_without_an_owner_keep_unique_names() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("lifter@example.com").await;
+        let insert = |user_id: Option<i32>| {
+            sqlx::query("INSERT INTO routines (name, user_id) VALUES ('Legacy Day', $1)")
+                .bind(user_id)
+                .execute(&db.pool)
+        };
+
+        insert(None).await.unwrap();
+        insert(Some(user_id)).await.unwrap();
+        let error = insert(None).await.unwrap_err();
+
+        assert_eq!(
+            error.as_database_error().unwrap().code().as_deref(),
+            Some("23505")
+        );
+    });
+}
+
+#[test]
+fn routines_skips_rows_that_fail_to_decode() {
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        // One connection, so the temporary view below shadows `routines` for
+        // every query the schema runs.
+        let postgres_pool: Pool<Postgres> = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(1)
+            .connect(&database_url)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+CREATE TEMPORARY VIEW routines AS
+SELECT *
+FROM (VALUES (1, 'Good'), (2, NULL), (3, 'Also Good')) AS rows (id, name)
+CROSS JOIN (SELECT NULL::INT AS user_id, now() AS created_at, now() AS updated_at, NULL::TIMESTAMPTZ AS archived_at, NULL::TIMESTAMPTZ AS deleted_at, 1 AS version) AS columns
+            "#,
+        )
+        .execute(&postgres_pool)
+        .await
+        .unwrap();
+
+        let response = fit::build_schema(postgres_pool)
+            .execute("{ routines { name } }")
+            .await;
+
+        assert_eq!(
+            response.data.to_string(),
+            r#"{routines: [{name: "Good"},{name: "Also Good"}]}"#
+        );
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors.as_array().unwrap().len(), 1);
+        assert_eq!(errors[0]["extensions"]["code"], "INTERNAL");
+        assert_eq!(errors[0]["path"], serde_json::json!(["routines"]));
+    });
+}
+
+#[test]
+fn create_routine_with_exercises_creates_the_routine_exercises_and_sets() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let (user_id,): (i32,) =
+            sqlx::query_as("INSERT INTO users (email) VALUES ($1) RETURNING id")
+                .bind(format!("builder{}@example.com", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+        let (_, exercise_ids) =
+            insert_routine_with_exercises(&postgres_pool, None, &format!("Source {}", suffix), 2)
+                .await;
+
+        let response = fit::build_schema(postgres_pool)
+            .execute(
+                async_graphql::Request::new(format!(
+                    r#"mutation {{ createRoutineWithExercises(input: {{ name: "Built {}", exercises: [
+                        {{ exerciseId: {}, targetSets: 2, targetReps: 5 }},
+                        {{ exerciseId: {}, targetSets: 1, targetReps: 12 }}
+                    ] }}) {{ routineExercises {{ position exercise {{ id }} sets {{ setNumber reps }} }} }} }}"#,
+                    suffix, exercise_ids[1], exercise_ids[0]
+                ))
+                .data(fit::AuthenticatedUser { id: user_id }),
+            )
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                "{{createRoutineWithExercises: {{routineExercises: [\
+                 {{position: 0,exercise: {{id: {}}},sets: [{{setNumber: 1,reps: 5}},{{setNumber: 2,reps: 5}}]}},\
+                 {{position: 1,exercise: {{id: {}}},sets: [{{setNumber: 1,reps: 12}}]}}]}}}}",
+                exercise_ids[1], exercise_ids[0]
+            )
+        );
+    });
+}
+
+#[test]
+fn create_routine_with_exercises_rolls_back_on_a_missing_exercise() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let (user_id,): (i32,) =
+            sqlx::query_as("INSERT INTO users (email) VALUES ($1) RETURNING id")
+                .bind(format!("rollback{}@example.com", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+        let (_, exercise_ids) =
+            insert_routine_with_exercises(&postgres_pool, None, &format!("Source {}", suffix), 2)
+                .await;
+
+        let response = fit::build_schema(postgres_pool.clone())
+            .execute(
+                async_graphql::Request::new(format!(
+                    r#"mutation {{ createRoutineWithExercises(input: {{ name: "Broken {}", exercises: [
+                        {{ exerciseId: {}, targetSets: 3, targetReps: 8 }},
+                        {{ exerciseId: -1, targetSets: 3, targetReps: 8 }},
+                        {{ exerciseId: {}, targetSets: 3, targetReps: 8 }}
+                    ] }}) {{ id }} }}"#,
+                    suffix, exercise_ids[0], exercise_ids[1]
+                ))
+                .data(fit::AuthenticatedUser { id: user_id }),
+            )
+            .await;
+
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "NOT_FOUND");
+        assert_eq!(errors[0]["message"], "Exercise -1 not found");
+
+        let (created,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM routines WHERE name = $1")
+            .bind(format!("Broken {}", suffix))
+            .fetch_one(&postgres_pool)
+            .await
+            .unwrap();
+        assert_eq!(created, 0);
+    });
+}
+
+#[test]
+fn users_and_anonymous_requests_cannot_read_or_change_someone_elses_routines() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let owner = db.insert_user("owner@example.com").await;
+        let intruder = db.insert_user("intruder@example.com").await;
+        let name = "Private Day";
+        let (routine_id, exercise_ids) =
+            insert_routine_with_exercises(&db.pool, Some(owner), name, 2).await;
+        let execute = |user_id: Option<i32>, query: String| {
+            let mut request = async_graphql::Request::new(query);
+            if let Some(user_id) = user_id {
+                request = request.data(fit::AuthenticatedUser { id: user_id });
+            }
+            db.schema.execute(request)
+        };
+
+        // Anonymous requests only see routines without an owner.
+        for intruder in [Some(intruder), None] {
+            let response = execute(
+                intruder,
+                format!(
+                    "{{ routine(id: {0}) {{ id }} deleted: routine(id: {0}, includeDeleted: true) {{ id }} routinesByIds(ids: [{0}]) {{ id }} routines {{ id }} }}",
+                    routine_id
+                ),
+            )
+            .await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            assert_eq!(
+                response.data.to_string(),
+                "{routine: null,deleted: null,routinesByIds: [],routines: []}"
+            );
+        }
+
+        let response = execute(
+            Some(intruder),
+            format!("mutation {{ deleteRoutine(id: {}) }}", routine_id),
+        )
+        .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.to_string(), "{deleteRoutine: false}");
+
+        let mutations = vec![
+            format!(
+                r#"updateRoutine(id: {}, name: "Stolen", expectedVersion: 1) {{ id }}"#,
+                routine_id
+            ),
+            format!("restoreRoutine(id: {}) {{ id }}", routine_id),
+            format!("duplicateRoutine(id: {}) {{ id }}", routine_id),
+            format!("archiveRoutine(id: {}) {{ id }}", routine_id),
+            format!("unarchiveRoutine(id: {}) {{ id }}", routine_id),
+            format!(
+                "addExerciseToRoutine(routineId: {}, exerciseId: {}) {{ id }}",
+                routine_id, exercise_ids[0]
+            ),
+            format!(
+                "removeExerciseFromRoutine(routineId: {}, exerciseId: {}) {{ id }}",
+                routine_id, exercise_ids[0]
+            ),
+            format!(
+                "reorderRoutineExercises(routineId: {}, exerciseIds: [{}, {}]) {{ id }}",
+                routine_id, exercise_ids[1], exercise_ids[0]
+            ),
+            format!(
+                "setExerciseSets(routineId: {}, exerciseId: {}, sets: []) {{ position }}",
+                routine_id, exercise_ids[0]
+            ),
+            format!("logWorkout(routineId: {}) {{ id }}", routine_id),
+        ];
+        for mutation in &mutations {
+            let response = execute(Some(intruder), format!("mutation {{ {} }}", mutation)).await;
+            let errors = serde_json::to_value(&response.errors).unwrap();
+            assert_eq!(errors[0]["extensions"]["code"], "NOT_FOUND", "{}", mutation);
+            assert_eq!(
+                errors[0]["message"],
+                format!("Routine {} not found", routine_id),
+                "{}",
+                mutation
+            );
+        }
+
+        // Anonymous requests can't change routines at all, and may only log
+        // workouts against routines they can see.
+        for mutation in &mutations {
+            let response = execute(None, format!("mutation {{ {} }}", mutation)).await;
+            let errors = serde_json::to_value(&response.errors).unwrap();
+            let code = if mutation.starts_with("logWorkout") {
+                "NOT_FOUND"
+            } else {
+                "UNAUTHENTICATED"
+            };
+            assert_eq!(errors[0]["extensions"]["code"], code, "{}", mutation);
+        }
+
+        let (routine_name, version, exercise_count, workout_count): (String, i32, i64, i64) =
+            sqlx::query_as(
+                r#"
+SELECT
+    name,
+    version,
+    (SELECT COUNT(*) FROM routine_exercises WHERE routine_id = $1),
+    (SELECT COUNT(*) FROM workouts WHERE routine_id = $1)
+FROM routines
+WHERE id = $1
+                "#,
+            )
+            .bind(routine_id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            (
+                routine_name.as_str(),
+                version,
+                exercise_count,
+                workout_count
+            ),
+            (name, 1, 2, 0)
+        );
+
+        let response = execute(
+            Some(owner),
+            format!(
+                "{{ routine(id: {0}) {{ name }} routinesByIds(ids: [{0}]) {{ name }} }}",
+                routine_id
+            ),
+        )
+        .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                r#"{{routine: {{name: "{0}"}},routinesByIds: [{{name: "{0}"}}]}}"#,
+                name
+            )
+        );
+    });
+}
+
+#[test]
+fn anonymous_requests_can_read_but_not_change_routines_without_an_owner() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let (routine_id, exercise_ids) =
+            insert_routine_with_exercises(&db.pool, None, "Legacy Day", 2).await;
+
+        let response = db
+            .execute(format!(
+                "{{ routine(id: {0}) {{ name }} routines {{ id }} }}",
+                routine_id
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                r#"{{routine: {{name: "Legacy Day"}},routines: [{{id: {}}}]}}"#,
+                routine_id
+            )
+        );
+
+        let mutations = vec![
+            format!(
+                r#"updateRoutine(id: {}, name: "Renamed", expectedVersion: 1) {{ id }}"#,
+                routine_id
+            ),
+            format!("deleteRoutine(id: {})", routine_id),
+            format!("restoreRoutine(id: {}) {{ id }}", routine_id),
+            format!("duplicateRoutine(id: {}) {{ id }}", routine_id),
+            format!("archiveRoutine(id: {}) {{ id }}", routine_id),
+            format!("unarchiveRoutine(id: {}) {{ id }}", routine_id),
+            format!(
+                "addExerciseToRoutine(routineId: {}, exerciseId: {}) {{ id }}",
+                routine_id, exercise_ids[0]
+            ),
+            format!(
+                "removeExerciseFromRoutine(routineId: {}, exerciseId: {}) {{ id }}",
+                routine_id, exercise_ids[0]
+            ),
+            format!(
+                "reorderRoutineExercises(routineId: {}, exerciseIds: [{}, {}]) {{ id }}",
+                routine_id, exercise_ids[1], exercise_ids[0]
+            ),
+            format!(
+                "setExerciseSets(routineId: {}, exerciseId: {}, sets: []) {{ position }}",
+                routine_id, exercise_ids[0]
+            ),
+        ];
+        for mutation in mutations {
+            let response = db.execute(format!("mutation {{ {} }}", mutation)).await;
+            let errors = serde_json::to_value(&response.errors).unwrap();
+            assert_eq!(
+                errors[0]["extensions"]["code"], "UNAUTHENTICATED",
+                "{}",
+                mutation
+            );
+        }
+
+        let (routine_count, name, deleted, archived, exercise_count): (
+            i64,
+            String,
+            bool,
+            bool,
+            i64,
+        ) = sqlx::query_as(
+            r#"
+SELECT
+    (SELECT COUNT(*) FROM routines),
+    name,
+    deleted_at IS NOT NULL,
+    archived_at IS NOT NULL,
+    (SELECT COUNT(*) FROM routine_exercises WHERE routine_id = $1)
+FROM routines
+WHERE id = $1
+            "#,
+        )
+        .bind(routine_id)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            (
+                routine_count,
+                name.as_str(),
+                deleted,
+                archived,
+                exercise_count
+            ),
+            (1, "Legacy Day", false, false, 2)
+        );
+    });
+}
+
+#[test]
+fn exported_routines_can_be_imported_by_another_user() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let mut user_ids = Vec::new();
+        for name in &["sharer", "importer"] {
+            let (user_id,): (i32,) =
+                sqlx::query_as("INSERT INTO users (email) VALUES ($1) RETURNING id")
+                    .bind(format!("{}{}@example.com", name, suffix))
+                    .fetch_one(&postgres_pool)
+                    .await
+                    .unwrap();
+            user_ids.push(user_id);
+        }
+        let (sharer, importer) = (user_ids[0], user_ids[1]);
+        let name = format!("Shared Day {}", suffix);
+        let (routine_id, exercise_ids) =
+            insert_routine_with_exercises(&postgres_pool, Some(sharer), &name, 2).await;
+        for (set_number, reps, weight_kg) in &[(1, 5, Some(100.0)), (2, 3, None)] {
+            sqlx::query(
+                "INSERT INTO routine_exercise_sets (routine_id, exercise_id, set_number, reps, weight_kg) VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(routine_id)
+            .bind(exercise_ids[1])
+            .bind(set_number)
+            .bind(reps)
+            .bind(weight_kg)
+            .execute(&postgres_pool)
+            .await
+            .unwrap();
+        }
+        let schema = fit::build_schema(postgres_pool.clone());
+        let execute = |user_id: i32, query: String| {
+            schema.execute(
+                async_graphql::Request::new(query).data(fit::AuthenticatedUser { id: user_id }),
+            )
+        };
+        let export_query = format!("{{ routineExport(id: {}) }}", routine_id);
+
+        let response = execute(importer, export_query.clone()).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.to_string(), "{routineExport: null}");
+
+        let response = execute(sharer, export_query).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        let mut document: serde_json::Value =
+            serde_json::from_str(data["routineExport"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            document,
+            serde_json::json!({
+                "version": 1,
+                "name": name,
+                "exercises": [
+                    {
+                        "name": format!("Exercise 0 for {}", name),
+                        "mainMuscleWorked": format!("Muscle for {}", name),
+                        "muscleGroup": "other",
+                        "equipment": "other",
+                        "sets": [],
+                    },
+                    {
+                        "name": format!("Exercise 1 for {}", name),
+                        "mainMuscleWorked": format!("Muscle for {}", name),
+                        "muscleGroup": "other",
+                        "equipment": "other",
+                        "sets": [
+                            { "reps": 5, "weightKg": 100.0 },
+                            { "reps": 3, "weightKg": null },
+                        ],
+                    },
+                ],
+            })
+        );
+
+        // As if imported on a server that lacks the first exercise.
+        let new_exercise = format!("Imported Exercise {}", suffix);
+        document["name"] = format!("Imported Day {}", suffix).into();
+        document["exercises"][0]["name"] = new_exercise.clone().into();
+        document["exercises"][0]["muscleGroup"] = "legs".into();
+        let response = execute(
+            importer,
+            format!(
+                "mutation {{ importRoutine(data: {}) {{ id name routineExercises {{ exercise {{ name }} sets {{ reps weightKg }} }} }} }}",
+                serde_json::to_string(&document.to_string()).unwrap()
+            ),
+        )
+        .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        assert_eq!(
+            data["importRoutine"]["routineExercises"],
+            serde_json::json!([
+                { "exercise": { "name": new_exercise }, "sets": [] },
+                {
+                    "exercise": { "name": format!("Exercise 1 for {}", name) },
+                    "sets": [
+                        { "reps": 5, "weightKg": 100.0 },
+                        { "reps": 3, "weightKg": null },
+                    ],
+                },
+            ])
+        );
+
+        let (owner, muscle_group, muscle): (Option<i32>, String, String) = sqlx::query_as(
+            r#"
+SELECT routines.user_id, exercises.muscle_group::TEXT, muscles.name
+FROM routines, exercises
+JOIN muscles ON muscles.id = exercises.main_muscle_worked_id
+WHERE routines.id = $1 AND exercises.name = $2
+            "#,
+        )
+        .bind(data["importRoutine"]["id"].as_i64().unwrap() as i32)
+        .bind(&new_exercise)
+        .fetch_one(&postgres_pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            (owner, muscle_group.as_str(), muscle),
+            (Some(importer), "legs", format!("Muscle for {}", name))
+        );
+
+        document["exercises"][1]["sets"][0]["reps"] = 0.into();
+        let response = execute(
+            importer,
+            format!(
+                "mutation {{ importRoutine(data: {}) {{ id }} }}",
+                serde_json::to_string(&document.to_string()).unwrap()
+            ),
+        )
+        .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(
+            errors[0]["extensions"]["field"],
+            "data.exercises.1.sets.0.reps"
+        );
+
+        let response = execute(
+            importer,
+            r#"mutation { importRoutine(data: "{\"name\": \"Nope\"}") { id } }"#.to_owned(),
+        )
+        .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["field"], "data");
+    });
+}
+
+#[test]
+fn routines_default_to_50_and_clamp_larger_limits_to_200() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("pager@example.com").await;
+        sqlx::query(
+            "INSERT INTO routines (name, user_id) SELECT 'Routine ' || n, $1 FROM generate_series(1, 205) AS n",
+        )
+        .bind(user_id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        let response = db
+            .execute_as(
+                user_id,
+                r#"{
+                    default: routines { id }
+                    clamped: routines(limit: 1000) { id }
+                    rest: routines(limit: 1000, offset: 200) { id }
+                }"#,
+            )
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        assert_eq!(data["default"].as_array().unwrap().len(), 50);
+        assert_eq!(data["clamped"].as_array().unwrap().len(), 200);
+        assert_eq!(data["rest"].as_array().unwrap().len(), 5);
+
+        for (query, message) in &[
+            (
+                "{ routines(limit: -1) { id } }",
+                "limit must not be negative",
+            ),
+            (
+                "{ routines(offset: -1) { id } }",
+                "offset must not be negative",
+            ),
+        ] {
+            let response = db.execute_as(user_id, *query).await;
+            let errors = serde_json::to_value(&response.errors).unwrap();
+            assert_eq!(errors[0]["extensions"]["code"], "VALIDATION", "{}", query);
+            assert_eq!(errors[0]["message"], *message, "{}", query);
+        }
+    });
+}
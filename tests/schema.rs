@@ -0,0 +1,17 @@
+use async_std::task;
+use sqlx::{Pool, Postgres};
+use std::env;
+
+#[test]
+fn executes_queries_through_the_library_schema() {
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+        let schema = fit::build_schema(postgres_pool);
+
+        let response = schema.execute("{ routines { id } }").await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert!(response.data.to_string().contains("routines"));
+    });
+}
@@ -0,0 +1,97 @@
+use async_std::task;
+use sqlx::{Pool, Postgres};
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+async fn connect() -> Pool<Postgres> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    Pool::connect(&database_url).await.unwrap()
+}
+
+async fn insert_exercise(postgres_pool: &Pool<Postgres>, name: &str) {
+    let (muscle_id,): (i32,) =
+        sqlx::query_as("INSERT INTO muscles (name) VALUES ($1) RETURNING id")
+            .bind(format!("Muscle for {}", name))
+            .fetch_one(postgres_pool)
+            .await
+            .unwrap();
+    sqlx::query("INSERT INTO exercises (name, main_muscle_worked_id) VALUES ($1, $2)")
+        .bind(name)
+        .bind(muscle_id)
+        .execute(postgres_pool)
+        .await
+        .unwrap();
+}
+
+#[test]
+fn search_ranks_exact_then_prefix_then_substring_matches() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let term = format!(
+            "Qz{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        );
+
+        insert_exercise(&postgres_pool, &format!("Incline {} Press", term)).await;
+        insert_exercise(&postgres_pool, &format!("{} Press", term)).await;
+        for (name, deleted) in &[(term.clone(), false), (format!("{} Old", term), true)] {
+            sqlx::query(
+                "INSERT INTO routines (name, deleted_at) VALUES ($1, CASE WHEN $2 THEN now() END)",
+            )
+            .bind(name)
+            .bind(deleted)
+            .execute(&postgres_pool)
+            .await
+            .unwrap();
+        }
+
+        let response = fit::build_schema(postgres_pool)
+            .execute(format!(
+                r#"{{ search(query: "  {}  ") {{
+                    __typename
+                    ... on Exercise {{ name }}
+                    ... on Routine {{ name }}
+                }} }}"#,
+                term.to_lowercase()
+            ))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                r#"{{search: [{{__typename: "Routine",name: "{0}"}},{{__typename: "Exercise",name: "{0} Press"}},{{__typename: "Exercise",name: "Incline {0} Press"}}]}}"#,
+                term
+            )
+        );
+    });
+}
+
+#[test]
+fn search_limits_results_to_first() {
+    task::block_on(async {
+        let schema = fit::build_schema(connect().await);
+
+        let response = schema
+            .execute(r#"{ search(query: "e", first: 1) { __typename } }"#)
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap()["search"]
+                .as_array()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let response = schema
+            .execute(r#"{ search(query: "e", first: 101) { __typename } }"#)
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "VALIDATION");
+        assert_eq!(errors[0]["extensions"]["field"], "first");
+    });
+}
@@ -0,0 +1,24 @@
+use async_std::task;
+use sqlx::{Pool, Postgres};
+use std::env;
+
+#[test]
+fn seeding_twice_inserts_nothing_the_second_time() {
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+
+        fit::seed(&postgres_pool).await.unwrap();
+        let (before,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM exercises")
+            .fetch_one(&postgres_pool)
+            .await
+            .unwrap();
+
+        assert_eq!(fit::seed(&postgres_pool).await.unwrap(), 0);
+        let (after,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM exercises")
+            .fetch_one(&postgres_pool)
+            .await
+            .unwrap();
+        assert_eq!(after, before);
+    });
+}
@@ -0,0 +1,68 @@
+use async_std::task;
+use serde_json::{json, Value};
+use std::env;
+use tide::http::{Method, Request, Response, StatusCode, Url};
+
+mod common;
+
+use common::TestDb;
+
+async fn post_graphql(app: &tide::Server<()>, query: &str) -> Value {
+    let mut req = Request::new(
+        Method::Post,
+        Url::parse("http://localhost/graphql").unwrap(),
+    );
+    req.set_body(json!({ "query": query }));
+
+    let mut res: Response = app.respond(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::Ok);
+    res.body_json().await.unwrap()
+}
+
+#[test]
+fn operations_report_how_many_sql_statements_they_ran() {
+    // Counting needs the logger `init_tracing` installs, which is global, so
+    // this is the only test in this binary.
+    env::set_var("RUST_LOG", "off");
+    env::set_var("DEBUG_SQL_COUNT", "1");
+    fit::init_tracing();
+
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let config = fit::Config::from_vars(|name| match name {
+            "DATABASE_URL" => Some(env::var("DATABASE_URL").unwrap()),
+            "DEBUG_SQL_COUNT" => Some("1".to_owned()),
+            _ => None,
+        })
+        .unwrap();
+        let app = fit::app(&config, db.pool.clone());
+
+        let response = post_graphql(&app, "{ exerciseCount }").await;
+        assert!(response.get("errors").is_none(), "{}", response);
+        assert_eq!(response["extensions"]["sqlQueryCount"], 1);
+
+        let query = "{ routines { name exercises { name muscleGroup } } }";
+        for (index, routines) in [["Push", "Pull"], ["Legs", "Core"]].iter().enumerate() {
+            for name in routines {
+                sqlx::query("INSERT INTO routines (name) VALUES ($1)")
+                    .bind(name)
+                    .execute(&db.pool)
+                    .await
+                    .unwrap();
+            }
+
+            // sqlx looks the enum types up once per connection, so the first
+            // request for exercises runs a few more statements than later
+            // ones.
+            if index == 0 {
+                post_graphql(&app, query).await;
+            }
+
+            // One statement for the routines and one batch for their
+            // exercises, however many routines there are.
+            let response = post_graphql(&app, query).await;
+            assert!(response.get("errors").is_none(), "{}", response);
+            assert_eq!(response["extensions"]["sqlQueryCount"], 2);
+        }
+    });
+}
@@ -0,0 +1,56 @@
+use async_graphql::futures_util::{FutureExt, StreamExt};
+use async_graphql::Request;
+use async_std::{future, task};
+use fit::AuthenticatedUser;
+use sqlx::{Pool, Postgres};
+use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[test]
+fn routine_created_sees_the_viewers_routines_created_after_subscribing() {
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+        let schema = fit::build_schema(postgres_pool.clone());
+
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let (user_id,): (i32,) =
+            sqlx::query_as("INSERT INTO users (email) VALUES ($1) RETURNING id")
+                .bind(format!("subscriber-{}@example.com", suffix))
+                .fetch_one(&postgres_pool)
+                .await
+                .unwrap();
+
+        let subscription = "subscription { routineCreated { name } }";
+        let mut stream = Box::pin(
+            schema
+                .execute_stream(Request::new(subscription).data(AuthenticatedUser { id: user_id })),
+        );
+        let mut anonymous_stream = Box::pin(schema.execute_stream(subscription));
+        // Poll once so the subscriptions are registered before the mutation runs.
+        assert!(stream.next().now_or_never().is_none());
+        assert!(anonymous_stream.next().now_or_never().is_none());
+
+        let name = format!("Push Day {}", suffix);
+        let mutation = format!(r#"mutation {{ createRoutine(name: "{}") {{ id }} }}"#, name);
+        let response = schema
+            .execute(Request::new(mutation).data(AuthenticatedUser { id: user_id }))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let event = future::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("no routineCreated event")
+            .unwrap();
+        assert!(event.errors.is_empty(), "{:?}", event.errors);
+        assert_eq!(
+            event.data.to_string(),
+            format!(r#"{{routineCreated: {{name: "{}"}}}}"#, name)
+        );
+        // Routines are only sent to their owner.
+        assert!(anonymous_stream.next().now_or_never().is_none());
+    });
+}
@@ -0,0 +1,70 @@
+use async_std::task;
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+use std::env;
+use tide::http::{Method, Request, Response, StatusCode, Url};
+
+#[test]
+fn graphql_requests_time_out() {
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let config = fit::Config::from_vars(|name| match name {
+            "DATABASE_URL" => Some(database_url.clone()),
+            "REQUEST_TIMEOUT_SECS" => Some("1".to_owned()),
+            _ => None,
+        })
+        .unwrap();
+        let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+        let app = fit::app(&config, postgres_pool.clone());
+
+        // Holding an exclusive lock stalls every read of the table until the
+        // transaction ends.
+        let mut lock = postgres_pool.begin().await.unwrap();
+        sqlx::query("LOCK TABLE exercises IN ACCESS EXCLUSIVE MODE")
+            .execute(&mut lock)
+            .await
+            .unwrap();
+
+        let mut req = Request::new(
+            Method::Post,
+            Url::parse("http://localhost/graphql").unwrap(),
+        );
+        req.set_body(json!({ "query": "{ exerciseCount }" }));
+        let mut res: Response = app.respond(req).await.unwrap();
+        lock.rollback().await.unwrap();
+
+        assert_eq!(res.status(), StatusCode::Ok);
+        let response: Value = res.body_json().await.unwrap();
+        assert_eq!(response["errors"][0]["extensions"]["code"], "TIMEOUT");
+        assert!(response["data"].is_null());
+    });
+}
+
+#[test]
+fn oversized_graphql_bodies_are_refused() {
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let config = fit::Config::from_vars(|name| match name {
+            "DATABASE_URL" => Some(database_url.clone()),
+            "GRAPHQL_MAX_BODY_BYTES" => Some("1024".to_owned()),
+            _ => None,
+        })
+        .unwrap();
+        let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+        let app = fit::app(&config, postgres_pool);
+
+        // Padding the document with a comment keeps it a valid query.
+        for (padding, status) in &[(900, StatusCode::Ok), (1024, StatusCode::PayloadTooLarge)] {
+            let mut req = Request::new(
+                Method::Post,
+                Url::parse("http://localhost/graphql").unwrap(),
+            );
+            req.set_body(json!({
+                "query": format!("#{}\n{{ exerciseCount }}", "x".repeat(*padding)),
+            }));
+            let res: Response = app.respond(req).await.unwrap();
+
+            assert_eq!(res.status(), *status);
+        }
+    });
+}
@@ -0,0 +1,34 @@
+use async_std::task;
+use sqlx::{Pool, Postgres};
+use std::env;
+use tide::http::{Method, Request, Response, StatusCode, Url};
+
+#[test]
+fn oversized_uploads_are_refused() {
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let config = fit::Config::from_vars(|name| match name {
+            "DATABASE_URL" => Some(database_url.clone()),
+            "UPLOAD_MAX_BODY_BYTES" => Some("64".to_owned()),
+            _ => None,
+        })
+        .unwrap();
+        let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+        let app = fit::app(&config, postgres_pool);
+
+        // A small body is read and rejected as an unreadable file instead.
+        for (len, status) in &[
+            (64, StatusCode::BadRequest),
+            (65, StatusCode::PayloadTooLarge),
+        ] {
+            let mut req = Request::new(
+                Method::Post,
+                Url::parse("http://localhost/upload/fit").unwrap(),
+            );
+            req.set_body(vec![0u8; *len]);
+            let res: Response = app.respond(req).await.unwrap();
+
+            assert_eq!(res.status(), *status);
+        }
+    });
+}
@@ -0,0 +1,45 @@
+use async_graphql::Request;
+use async_std::task;
+use fit::AuthenticatedUser;
+use sqlx::{Pool, Postgres};
+use std::env;
+
+#[test]
+fn create_routine_rejects_blank_and_overlong_names() {
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+        let schema = fit::build_schema(postgres_pool);
+
+        for name in &["   ".to_owned(), "x".repeat(256)] {
+            let mutation = format!(r#"mutation {{ createRoutine(name: "{}") {{ id }} }}"#, name);
+            let response = schema
+                .execute(Request::new(mutation).data(AuthenticatedUser { id: 1 }))
+                .await;
+
+            let error = serde_json::to_value(&response.errors[0]).unwrap();
+            assert_eq!(error["extensions"]["code"], "VALIDATION");
+            assert_eq!(error["extensions"]["field"], "name");
+        }
+    });
+}
+
+#[test]
+fn create_routines_names_the_invalid_entry() {
+    task::block_on(async {
+        let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+        let postgres_pool: Pool<Postgres> = Pool::connect(&database_url).await.unwrap();
+        let schema = fit::build_schema(postgres_pool);
+
+        let response = schema
+            .execute(
+                Request::new(r#"mutation { createRoutines(names: ["Legs", " "]) { id } }"#)
+                    .data(AuthenticatedUser { id: 1 }),
+            )
+            .await;
+
+        let error = serde_json::to_value(&response.errors[0]).unwrap();
+        assert_eq!(error["extensions"]["code"], "VALIDATION");
+        assert_eq!(error["extensions"]["field"], "names.1");
+    });
+}
@@ -0,0 +1,192 @@
+use async_std::task;
+use chrono::{Duration, Utc};
+use serde_json::json;
+
+mod common;
+
+use common::TestDb;
+
+#[test]
+fn viewer_is_null_without_a_user() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+
+        let response = db.execute("{ viewer { id } }").await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.to_string(), "{viewer: null}");
+    });
+}
+
+#[test]
+fn viewer_lists_the_same_routines_as_the_routines_query() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("viewer@example.com").await;
+        let other_id = db.insert_user("other@example.com").await;
+        for (name, owner) in &[("Push", user_id), ("Pull", user_id), ("Legs", other_id)] {
+            sqlx::query("INSERT INTO routines (name, user_id) VALUES ($1, $2)")
+                .bind(name)
+                .bind(owner)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+
+        let response = db
+            .execute_as(
+                user_id,
+                r#"{
+                    viewer { id email routines(orderBy: NAME_ASC) { name } }
+                    routines(orderBy: NAME_ASC) { name }
+                }"#,
+            )
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        assert_eq!(data["viewer"]["id"], user_id);
+        assert_eq!(data["viewer"]["email"], "viewer@example.com");
+        assert_eq!(data["viewer"]["routines"], data["routines"]);
+        assert_eq!(
+            data["routines"],
+            json!([{ "name": "Pull" }, { "name": "Push" }])
+        );
+    });
+}
+
+#[test]
+fn recent_workouts_are_the_viewers_newest_first() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let user_id = db.insert_user("runner@example.com").await;
+        let other_id = db.insert_user("walker@example.com").await;
+        let mut workout_ids = Vec::new();
+        for (days_ago, owner) in &[(3, user_id), (1, user_id), (2, user_id), (0, other_id)] {
+            let (workout_id,): (i32,) = sqlx::query_as(
+                "INSERT INTO workouts (performed_at, user_id) VALUES ($1, $2) RETURNING id",
+            )
+            .bind(Utc::now() - Duration::days(*days_ago))
+            .bind(owner)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+            workout_ids.push(workout_id);
+        }
+
+        let response = db
+            .execute_as(
+                user_id,
+                "{ viewer { all: recentWorkouts { id } latest: recentWorkouts(limit: 2) { id } } }",
+            )
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({
+                "viewer": {
+                    "all": [
+                        { "id": workout_ids[1] },
+                        { "id": workout_ids[2] },
+                        { "id": workout_ids[0] },
+                    ],
+                    "latest": [{ "id": workout_ids[1] }, { "id": workout_ids[2] }],
+                },
+            })
+        );
+
+        let response = db
+            .execute_as(user_id, "{ viewer { recentWorkouts(limit: 101) { id } } }")
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["field"], "limit");
+    });
+}
+
+#[test]
+fn anonymous_requests_only_see_rows_without_an_owner() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let owner_id = db.insert_user("owner@example.com").await;
+        let mut routine_ids = Vec::new();
+        for (name, owner) in &[("Owned Push", Some(owner_id)), ("Legacy Pull", None)] {
+            let (routine_id,): (i32,) =
+                sqlx::query_as("INSERT INTO routines (name, user_id) VALUES ($1, $2) RETURNING id")
+                    .bind(name)
+                    .bind(owner)
+                    .fetch_one(&db.pool)
+                    .await
+                    .unwrap();
+            routine_ids.push(routine_id);
+        }
+        // A legacy workout that followed the owner's routine.
+        for owner in &[Some(owner_id), None] {
+            sqlx::query(
+                "INSERT INTO workouts (performed_at, routine_id, user_id) VALUES (now(), $1, $2)",
+            )
+            .bind(routine_ids[0])
+            .bind(owner)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        }
+        let mut entry_ids = Vec::new();
+        for (weight_kg, owner) in &[(80.0, Some(owner_id)), (70.0, None)] {
+            let (entry_id,): (i32,) = sqlx::query_as(
+                "INSERT INTO body_weight_entries (measured_at, weight_kg, user_id) VALUES (now(), $1, $2) RETURNING id",
+            )
+            .bind(weight_kg)
+            .bind(owner)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+            entry_ids.push(entry_id);
+        }
+        let reads = r#"{
+            routines { name }
+            routineCount
+            search(query: "u") { ... on Routine { name } }
+            workouts { routine { name } }
+            bodyWeightEntries { weightKg }
+            latestBodyWeight { weightKg }
+        }"#;
+
+        let response = db.execute(reads).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({
+                "routines": [{ "name": "Legacy Pull" }],
+                "routineCount": 1,
+                "search": [{ "name": "Legacy Pull" }],
+                "workouts": [{ "routine": null }],
+                "bodyWeightEntries": [{ "weightKg": 70.0 }],
+                "latestBodyWeight": { "weightKg": 70.0 },
+            })
+        );
+
+        let response = db
+            .execute(format!(
+                "mutation {{ deleteBodyWeightEntry(id: {}) {{ id }} }}",
+                entry_ids[0]
+            ))
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "NOT_FOUND");
+
+        let response = db.execute_as(owner_id, reads).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({
+                "routines": [{ "name": "Owned Push" }],
+                "routineCount": 1,
+                "search": [{ "name": "Owned Push" }],
+                "workouts": [{ "routine": { "name": "Owned Push" } }],
+                "bodyWeightEntries": [{ "weightKg": 80.0 }],
+                "latestBodyWeight": { "weightKg": 80.0 },
+            })
+        );
+    });
+}
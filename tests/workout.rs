@@ -0,0 +1,598 @@
+use async_std::task;
+use chrono::{DateTime, Duration, TimeZone, Utc, Weekday};
+use sqlx::{Pool, Postgres};
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod common;
+
+use common::TestDb;
+
+async fn connect() -> Pool<Postgres> {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
+    Pool::connect(&database_url).await.unwrap()
+}
+
+async fn insert_exercise(postgres_pool: &Pool<Postgres>, name: &str) -> i32 {
+    let (muscle_id,): (i32,) =
+        sqlx::query_as("INSERT INTO muscles (name) VALUES ($1) RETURNING id")
+            .bind(format!("Muscle for {}", name))
+            .fetch_one(postgres_pool)
+            .await
+            .unwrap();
+    let (exercise_id,): (i32,) = sqlx::query_as(
+        "INSERT INTO exercises (name, main_muscle_worked_id) VALUES ($1, $2) RETURNING id",
+    )
+    .bind(name)
+    .bind(muscle_id)
+    .fetch_one(postgres_pool)
+    .await
+    .unwrap();
+
+    exercise_id
+}
+
+async fn insert_routine(postgres_pool: &Pool<Postgres>, name: &str) -> i32 {
+    let (routine_id,): (i32,) =
+        sqlx::query_as("INSERT INTO routines (name) VALUES ($1) RETURNING id")
+            .bind(name)
+            .fetch_one(postgres_pool)
+            .await
+            .unwrap();
+
+    routine_id
+}
+
+/// Inserts a workout performed at `performed_at` with one set of each
+/// `(exercise_id, reps, weight_kg)`, returning its id.
+async fn insert_workout(
+    postgres_pool: &Pool<Postgres>,
+    performed_at: DateTime<Utc>,
+    sets: &[(i32, i32, Option<f64>)],
+) -> i32 {
+    let (workout_id,): (i32,) =
+        sqlx::query_as("INSERT INTO workouts (performed_at) VALUES ($1) RETURNING id")
+            .bind(performed_at)
+            .fetch_one(postgres_pool)
+            .await
+            .unwrap();
+
+    for (position, (exercise_id, reps, weight_kg)) in sets.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO sets (workout_id, exercise_id, reps, weight_kg, position) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(workout_id)
+        .bind(exercise_id)
+        .bind(reps)
+        .bind(weight_kg)
+        .bind(position as i32)
+        .execute(postgres_pool)
+        .await
+        .unwrap();
+    }
+
+    workout_id
+}
+
+fn days_ago(days: i64) -> DateTime<Utc> {
+    Utc::now() - Duration::days(days)
+}
+
+#[test]
+fn personal_records_pick_the_heaviest_and_most_recent_set() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let squat = insert_exercise(&postgres_pool, &format!("Squat {}", suffix)).await;
+        let plank = insert_exercise(&postgres_pool, &format!("Plank {}", suffix)).await;
+
+        insert_workout(&postgres_pool, days_ago(3), &[(squat, 5, Some(100.0))]).await;
+        let recent = insert_workout(
+            &postgres_pool,
+            days_ago(1),
+            &[(squat, 3, Some(100.0)), (squat, 8, Some(80.0))],
+        )
+        .await;
+        insert_workout(&postgres_pool, days_ago(2), &[(plank, 1, None)]).await;
+
+        let schema = fit::build_schema(postgres_pool);
+        let response = schema
+            .execute(format!(
+                "{{ personalRecords(exerciseId: {}) {{ exercise {{ id }} maxWeightKg repsAtMaxWeight workout {{ id }} }} }}",
+                squat
+            ))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                "{{personalRecords: [{{exercise: {{id: {}}},maxWeightKg: 100,repsAtMaxWeight: 3,workout: {{id: {}}}}}]}}",
+                squat, recent
+            )
+        );
+
+        let response = schema
+            .execute(format!(
+                "{{ personalRecords(exerciseId: {}) {{ maxWeightKg }} }}",
+                plank
+            ))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.to_string(), "{personalRecords: []}");
+    });
+}
+
+#[test]
+fn volume_stats_sum_volume_per_week_and_muscle_group() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        // Workouts aren't scoped to a user, so each run trains in its own
+        // week of a period no other test uses.
+        let monday = Utc.isoywd(1001, 1, Weekday::Mon).and_hms(0, 0, 0)
+            + Duration::weeks((suffix % 30_000) as i64 * 2);
+        let bench = insert_exercise(&postgres_pool, &format!("Bench {}", suffix)).await;
+        let squat = insert_exercise(&postgres_pool, &format!("Squat {}", suffix)).await;
+        sqlx::query("UPDATE exercises SET muscle_group = 'chest' WHERE id = $1")
+            .bind(bench)
+            .execute(&postgres_pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE exercises SET muscle_group = 'legs' WHERE id = $1")
+            .bind(squat)
+            .execute(&postgres_pool)
+            .await
+            .unwrap();
+
+        insert_workout(
+            &postgres_pool,
+            monday + Duration::hours(10),
+            &[(bench, 5, Some(60.0)), (squat, 5, Some(100.0))],
+        )
+        .await;
+        insert_workout(
+            &postgres_pool,
+            monday + Duration::days(3),
+            &[(squat, 10, None)],
+        )
+        .await;
+
+        let schema = fit::build_schema(postgres_pool);
+        let query = |group_by: &str, fill_gaps: bool| {
+            format!(
+                r#"{{ volumeStats(fromDate: "{}", toDate: "{}", groupBy: {}, fillGaps: {}) {{ muscleGroup totalVolumeKg setCount }} }}"#,
+                monday.to_rfc3339(),
+                (monday + Duration::days(13)).to_rfc3339(),
+                group_by,
+                fill_gaps
+            )
+        };
+
+        let response = schema.execute(query("WEEK", false)).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            "{volumeStats: [{muscleGroup: null,totalVolumeKg: 800,setCount: 3}]}"
+        );
+
+        let response = schema.execute(query("MUSCLE_GROUP_PER_WEEK", true)).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            "{volumeStats: [\
+             {muscleGroup: CHEST,totalVolumeKg: 300,setCount: 1},\
+             {muscleGroup: LEGS,totalVolumeKg: 500,setCount: 2},\
+             {muscleGroup: null,totalVolumeKg: 0,setCount: 0}]}"
+        );
+    });
+}
+
+#[test]
+fn volume_stats_rejects_a_backwards_date_range() {
+    task::block_on(async {
+        let response = fit::build_schema(connect().await)
+            .execute(
+                r#"{ volumeStats(fromDate: "2021-12-01T00:00:00Z", toDate: "2021-11-01T00:00:00Z", groupBy: WEEK) { setCount } }"#,
+            )
+            .await;
+
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "VALIDATION");
+        assert_eq!(errors[0]["extensions"]["field"], "toDate");
+    });
+}
+
+#[test]
+fn sets_estimate_a_one_rep_max_for_twelve_reps_or_fewer() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let press = insert_exercise(&postgres_pool, &format!("Press {}", suffix)).await;
+        insert_workout(
+            &postgres_pool,
+            days_ago(1),
+            &[
+                (press, 1, Some(90.0)),
+                (press, 6, Some(80.0)),
+                (press, 15, Some(60.0)),
+                (press, 8, None),
+            ],
+        )
+        .await;
+
+        let response = fit::build_schema(postgres_pool)
+            .execute(format!(
+                "{{ personalRecords(exerciseId: {0}) {{ workout {{ sets {{ estimatedOneRepMaxKg }} }} }} \
+                 bestEstimatedOneRepMax(exerciseId: {0}) \
+                 none: bestEstimatedOneRepMax(exerciseId: -1) }}",
+                press
+            ))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            "{personalRecords: [{workout: {sets: [\
+             {estimatedOneRepMaxKg: 90},{estimatedOneRepMaxKg: 96},\
+             {estimatedOneRepMaxKg: null},{estimatedOneRepMaxKg: null}]}}],\
+             bestEstimatedOneRepMax: 96,none: null}"
+        );
+    });
+}
+
+#[test]
+fn exercise_history_pages_through_sets_newest_first() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let row = insert_exercise(&postgres_pool, &format!("Row {}", suffix)).await;
+        let same_time = days_ago(2);
+        let oldest = insert_workout(&postgres_pool, days_ago(5), &[(row, 10, Some(40.0))]).await;
+        let tied_first = insert_workout(&postgres_pool, same_time, &[(row, 8, Some(50.0))]).await;
+        let tied_second = insert_workout(&postgres_pool, same_time, &[(row, 6, Some(55.0))]).await;
+        let newest = insert_workout(&postgres_pool, days_ago(1), &[(row, 5, Some(60.0))]).await;
+
+        let schema = fit::build_schema(postgres_pool);
+        let mut workout_ids = Vec::new();
+        let mut after = String::new();
+        loop {
+            let response = schema
+                .execute(format!(
+                    "{{ exerciseHistory(exerciseId: {}, first: 3{}) {{ edges {{ workoutId node {{ exercise {{ id }} }} }} pageInfo {{ hasNextPage endCursor }} }} }}",
+                    row, after
+                ))
+                .await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+            let history = response.data.into_json().unwrap()["exerciseHistory"].clone();
+            for edge in history["edges"].as_array().unwrap() {
+                assert_eq!(edge["node"]["exercise"]["id"], row);
+                workout_ids.push(edge["workoutId"].as_i64().unwrap() as i32);
+            }
+            if !history["pageInfo"]["hasNextPage"].as_bool().unwrap() {
+                break;
+            }
+            after = format!(
+                r#", after: "{}""#,
+                history["pageInfo"]["endCursor"].as_str().unwrap()
+            );
+        }
+
+        assert_eq!(workout_ids, vec![newest, tied_second, tied_first, oldest]);
+
+        let response = schema
+            .execute(
+                "{ exerciseHistory(exerciseId: -1) { edges { cursor } pageInfo { hasNextPage } } }",
+            )
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            "{exerciseHistory: {edges: [],pageInfo: {hasNextPage: false}}}"
+        );
+    });
+}
+
+#[test]
+fn training_calendar_and_streaks_span_a_month_boundary() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        // As with volume stats, each run trains in a year of its own that
+        // nothing else uses.
+        let year = 200 + (suffix % 800) as i32;
+        let day = |month, day| Utc.ymd(year, month, day).and_hms(12, 0, 0);
+        let curl = insert_exercise(&postgres_pool, &format!("Curl {}", suffix)).await;
+        for performed_at in &[day(1, 30), day(1, 31), day(2, 1), day(2, 5), day(2, 6)] {
+            insert_workout(&postgres_pool, *performed_at, &[(curl, 10, Some(12.0))]).await;
+        }
+        insert_workout(&postgres_pool, day(2, 2), &[]).await;
+        insert_workout(
+            &postgres_pool,
+            day(2, 2),
+            &[(curl, 8, Some(14.0)), (curl, 8, Some(14.0))],
+        )
+        .await;
+        // 21:00 on February 7th in New York.
+        insert_workout(&postgres_pool, day(2, 8) - Duration::hours(10), &[]).await;
+
+        let schema = fit::build_schema(postgres_pool);
+        let response = schema
+            .execute(format!(
+                r#"{{ utc: trainingCalendar(fromDate: "{0}-01-31", toDate: "{0}-02-08") {{ date workoutCount setCount }}
+                    newYork: trainingCalendar(fromDate: "{0}-02-07", toDate: "{0}-02-08", timeZone: "America/New_York") {{ date }}
+                    trainingStreaks(asOf: "{0}-02-07") {{ currentStreak longestStreak }} }}"#,
+                year
+            ))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let year = format!("{:04}", year);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                "{{utc: [\
+                 {{date: \"{0}-01-31\",workoutCount: 1,setCount: 1}},\
+                 {{date: \"{0}-02-01\",workoutCount: 1,setCount: 1}},\
+                 {{date: \"{0}-02-02\",workoutCount: 2,setCount: 2}},\
+                 {{date: \"{0}-02-05\",workoutCount: 1,setCount: 1}},\
+                 {{date: \"{0}-02-06\",workoutCount: 1,setCount: 1}},\
+                 {{date: \"{0}-02-08\",workoutCount: 1,setCount: 0}}],\
+                 newYork: [{{date: \"{0}-02-07\"}}],\
+                 trainingStreaks: {{currentStreak: 2,longestStreak: 4}}}}",
+                year
+            )
+        );
+    });
+}
+
+#[test]
+fn training_calendar_rejects_an_unknown_time_zone() {
+    task::block_on(async {
+        let response = fit::build_schema(connect().await)
+            .execute(
+                r#"{ trainingCalendar(fromDate: "2021-11-01", toDate: "2021-11-30", timeZone: "Mars/Olympus_Mons") { date } }"#,
+            )
+            .await;
+
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "VALIDATION");
+        assert_eq!(errors[0]["extensions"]["field"], "timeZone");
+    });
+}
+
+#[test]
+fn workout_and_set_notes_can_be_logged_edited_and_cleared() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let exercise_id = insert_exercise(&postgres_pool, &format!("Lunge {}", suffix)).await;
+        let routine_id = insert_routine(&postgres_pool, &format!("Legs {}", suffix)).await;
+        let schema = fit::build_schema(postgres_pool);
+
+        let response = schema
+            .execute(format!(
+                r#"mutation {{ logWorkout(routineId: {}, notes: "Tired", sets: [
+                    {{ exerciseId: {}, reps: 8, notes: "Left knee felt off" }}
+                ]) {{ id sets {{ id notes }} }} }}"#,
+                routine_id, exercise_id
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let workout = response.data.into_json().unwrap()["logWorkout"].clone();
+        assert_eq!(workout["sets"][0]["notes"], "Left knee felt off");
+        let workout_id = workout["id"].as_i64().unwrap();
+        let set_id = workout["sets"][0]["id"].as_i64().unwrap();
+
+        let response = schema
+            .execute(format!(
+                r#"mutation {{
+                    unchanged: updateSetNotes(setId: {0}) {{ notes }}
+                    workout: updateWorkoutNotes(workoutId: {1}, notes: "Slept badly") {{ notes }}
+                }}"#,
+                set_id, workout_id
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            r#"{unchanged: {notes: "Left knee felt off"},workout: {notes: "Slept badly"}}"#
+        );
+
+        let response = schema
+            .execute(format!(
+                "mutation {{ updateSetNotes(setId: {}, notes: null) {{ notes }} }}",
+                set_id
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(response.data.to_string(), "{updateSetNotes: {notes: null}}");
+    });
+}
+
+#[test]
+fn log_workout_rejects_overlong_set_notes() {
+    task::block_on(async {
+        let response = fit::build_schema(connect().await)
+            .execute(format!(
+                r#"mutation {{ logWorkout(routineId: -1, sets: [
+                    {{ exerciseId: -1, reps: 5, notes: "{}" }}
+                ]) {{ id }} }}"#,
+                "x".repeat(2001)
+            ))
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+
+        assert_eq!(errors[0]["extensions"]["code"], "VALIDATION");
+        assert_eq!(errors[0]["extensions"]["field"], "sets.0.notes");
+        assert!(!errors[0]["message"].as_str().unwrap().contains("xxx"));
+    });
+}
+
+#[test]
+fn workouts_report_their_duration_and_sets_their_rest() {
+    task::block_on(async {
+        let postgres_pool = connect().await;
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let exercise_id = insert_exercise(&postgres_pool, &format!("Dip {}", suffix)).await;
+        let routine_id = insert_routine(&postgres_pool, &format!("Push {}", suffix)).await;
+        let schema = fit::build_schema(postgres_pool);
+
+        let response = schema
+            .execute(format!(
+                r#"mutation {{
+                    timed: logWorkout(
+                        routineId: {0},
+                        startedAt: "2022-01-07T18:00:00Z",
+                        finishedAt: "2022-01-07T19:05:30Z",
+                        sets: [{{ exerciseId: {1}, reps: 10 }}, {{ exerciseId: {1}, reps: 8, restSeconds: 90 }}]
+                    ) {{ duration sets {{ restSeconds }} }}
+                    unfinished: logWorkout(routineId: {0}, startedAt: "2022-01-07T18:00:00Z") {{ duration }}
+                }}"#,
+                routine_id, exercise_id
+            ))
+            .await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            "{timed: {duration: 3930,sets: [{restSeconds: null},{restSeconds: 90}]},unfinished: {duration: null}}"
+        );
+    });
+}
+
+#[test]
+fn log_workout_rejects_bad_durations_and_timestamps() {
+    task::block_on(async {
+        let schema = fit::build_schema(connect().await);
+
+        for rest in &["-1", "1.5", "\"90\""] {
+            let response = schema
+                .execute(format!(
+                    "mutation {{ logWorkout(routineId: -1, sets: [{{ exerciseId: -1, reps: 5, restSeconds: {} }}]) {{ id }} }}",
+                    rest
+                ))
+                .await;
+            assert_eq!(response.errors.len(), 1, "restSeconds: {}", rest);
+            assert!(
+                response.errors[0].message.contains("Duration"),
+                "{}",
+                response.errors[0].message
+            );
+        }
+
+        let response = schema
+            .execute(
+                r#"mutation { logWorkout(routineId: -1, startedAt: "2022-01-07T19:00:00Z", finishedAt: "2022-01-07T18:00:00Z") { id } }"#,
+            )
+            .await;
+        let errors = serde_json::to_value(&response.errors).unwrap();
+        assert_eq!(errors[0]["extensions"]["code"], "VALIDATION");
+        assert_eq!(errors[0]["extensions"]["field"], "finishedAt");
+    });
+}
+
+#[test]
+fn users_and_anonymous_requests_cannot_read_or_change_someone_elses_workouts() {
+    task::block_on(async {
+        let db = TestDb::new().await;
+        let owner = db.insert_user("lifter@example.com").await;
+        let other = db.insert_user("snoop@example.com").await;
+        let bench = insert_exercise(&db.pool, "Bench").await;
+        let workout_id = insert_workout(&db.pool, days_ago(1), &[(bench, 5, Some(90.0))]).await;
+        sqlx::query("UPDATE workouts SET user_id = $1 WHERE id = $2")
+            .bind(owner)
+            .bind(workout_id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let (set_id,): (i32,) = sqlx::query_as("SELECT id FROM sets WHERE workout_id = $1")
+            .bind(workout_id)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        let execute = |user_id: Option<i32>, query: String| {
+            let mut request = async_graphql::Request::new(query);
+            if let Some(user_id) = user_id {
+                request = request.data(fit::AuthenticatedUser { id: user_id });
+            }
+            db.schema.execute(request)
+        };
+        let reads = format!(
+            r#"{{
+                personalRecords(exerciseId: {0}) {{ maxWeightKg }}
+                exerciseHistory(exerciseId: {0}) {{ edges {{ node {{ id }} }} }}
+                bestEstimatedOneRepMax(exerciseId: {0})
+                workouts {{ id }}
+            }}"#,
+            bench
+        );
+
+        // Anonymous requests only see workouts without an owner.
+        for other in [Some(other), None] {
+            let response = execute(other, reads.clone()).await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            assert_eq!(
+                response.data.to_string(),
+                "{personalRecords: [],exerciseHistory: {edges: []},bestEstimatedOneRepMax: null,workouts: []}"
+            );
+
+            for mutation in &[
+                format!(
+                    r#"updateWorkoutNotes(workoutId: {}, notes: "mine now") {{ id }}"#,
+                    workout_id
+                ),
+                format!(
+                    r#"updateSetNotes(setId: {}, notes: "mine now") {{ id }}"#,
+                    set_id
+                ),
+            ] {
+                let response = execute(other, format!("mutation {{ {} }}", mutation)).await;
+                let errors = serde_json::to_value(&response.errors).unwrap();
+                assert_eq!(errors[0]["extensions"]["code"], "NOT_FOUND", "{}", mutation);
+            }
+        }
+
+        let (workout_notes, set_notes): (Option<String>, Option<String>) = sqlx::query_as(
+            "SELECT workouts.notes, sets.notes FROM workouts JOIN sets ON sets.workout_id = workouts.id WHERE sets.id = $1",
+        )
+        .bind(set_id)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!((workout_notes, set_notes), (None, None));
+
+        let response = execute(Some(owner), reads).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.to_string(),
+            format!(
+                "{{personalRecords: [{{maxWeightKg: 90}}],exerciseHistory: {{edges: [{{node: {{id: {}}}}}]}},bestEstimatedOneRepMax: 105,workouts: [{{id: {}}}]}}",
+                set_id, workout_id
+            )
+        );
+    });
+}